@@ -1,23 +1,43 @@
-//! Minimal CPIO "newc" parser (read-only).
+//! Minimal CPIO parser and "newc" writer.
 //!
-//! - Supports the "newc" ASCII-hex header format (magic: "070701").
-//! - Iterates entries, returning name, mode, and a slice of file data.
+//! - Supports the "newc" ASCII-hex header format (magic: "070701"), its "crc" variant (magic:
+//!   "070702") whose file data is checksummed, and the portable "odc" ASCII-octal format (magic:
+//!   "070707"). Archives may mix them.
+//! - Iterates entries, returning name, header metadata, format, and a slice of file data.
+//! - Hard links are grouped by [`hard_links`], and the target of symlinks is their data.
 //! - Stops at "TRAILER!!!".
 //! - No allocations for file data; names are validated UTF-8 and borrowed from the archive.
+//! - With the `alloc` feature, archives can be indexed as a directory tree by `CpioTree`, to
+//!   look up files by normalised paths.
+//! - Archives can be written with [`NewcWriter`], to a `std::io::Write` with the `std` feature.
 //!
 //! This is meant for initrd/initramfs usage in a kernel.
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
 use core::{fmt, str};
 
+#[cfg(feature = "alloc")]
+mod tree;
+mod writer;
+
+#[cfg(feature = "alloc")]
+pub use tree::*;
+pub use writer::*;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CpioError {
     UnexpectedEof,
     BadMagic,
+    /// A numeric header field is not valid hex, or octal for "odc" headers.
     BadHex,
     BadUtf8,
     BadAlignment,
+    /// The file data of a "crc" entry does not match the checksum of its header.
+    BadChecksum,
 }
 
 impl fmt::Display for CpioError {
@@ -25,25 +45,133 @@ impl fmt::Display for CpioError {
         use CpioError::*;
         let s = match self {
             UnexpectedEof => "unexpected end of archive",
-            BadMagic => "bad cpio magic (expected 070701)",
-            BadHex => "invalid hex field in header",
+            BadMagic => "bad cpio magic (expected 070701, 070702 or 070707)",
+            BadHex => "invalid numeric field in header",
             BadUtf8 => "filename is not valid UTF-8",
             BadAlignment => "alignment overflow/invalid",
+            BadChecksum => "file data does not match its checksum",
         };
         f.write_str(s)
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for CpioError {}
+
+/// Header format of an entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// "New ASCII" format, with hex fields (magic: "070701").
+    Newc,
+    /// "New ASCII" format with a checksum of the file data (magic: "070702").
+    Crc,
+    /// Portable "old ASCII" format of POSIX.1, with octal fields (magic: "070707").
+    Odc,
+}
+
+impl Format {
+    fn from_magic(magic: &[u8]) -> Option<Self> {
+        match magic {
+            b"070701" => Some(Format::Newc),
+            b"070702" => Some(Format::Crc),
+            b"070707" => Some(Format::Odc),
+            _ => None,
+        }
+    }
+
+    /// Length of the header, magic included.
+    fn header_len(self) -> usize {
+        match self {
+            // magic[6] + 13 fields * 8 hex chars
+            Format::Newc | Format::Crc => 110,
+            // magic[6] + 8 fields * 6 octal chars + 2 fields * 11 octal chars
+            Format::Odc => 76,
+        }
+    }
+
+    /// Boundary to which the name and the file data are padded.
+    fn alignment(self) -> usize {
+        match self {
+            Format::Newc | Format::Crc => 4,
+            Format::Odc => 1,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct CpioEntry<'a> {
     /// Path name (as stored in archive), UTF-8.
     pub name: &'a str,
     /// POSIX mode (includes file type bits).
     pub mode: u32,
+    /// Header format of the entry.
+    pub format: Format,
+    /// Inode number, identifying the file together with `dev_major` and `dev_minor`.
+    pub ino: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Number of links to the file, which is also the number of entries of the archive for it
+    /// if it is not a directory.
+    pub nlink: u32,
+    /// Modification time, in seconds since the Unix epoch.
+    pub mtime: u32,
+    /// Device containing the file.
+    pub dev_major: u32,
+    pub dev_minor: u32,
+    /// Device of character and block device nodes.
+    pub rdev_major: u32,
+    pub rdev_minor: u32,
     /// File payload (empty for directories and special entries).
+    ///
+    /// The payload of a file with several hard links is usually only carried by the last
+    /// entry for it, see [`hard_links`]. The payload of a symlink is its target.
     pub data: &'a [u8],
 }
 
+/// Identity of a file of an archive, shared by the entries of its hard links.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId {
+    pub dev_major: u32,
+    pub dev_minor: u32,
+    pub ino: u32,
+}
+
+impl<'a> CpioEntry<'a> {
+    pub fn file_id(&self) -> FileId {
+        FileId {
+            dev_major: self.dev_major,
+            dev_minor: self.dev_minor,
+            ino: self.ino,
+        }
+    }
+
+    /// Returns whether other entries of the archive are hard links to the same file.
+    pub fn is_hard_link(&self) -> bool {
+        self.nlink > 1 && !is_dir(self.mode)
+    }
+
+    /// Returns the target of a symlink, or `None` for other entries.
+    pub fn symlink_target(&self) -> Result<Option<&'a str>, CpioError> {
+        if !is_symlink(self.mode) {
+            return Ok(None);
+        }
+        str::from_utf8(self.data)
+            .map(Some)
+            .map_err(|_| CpioError::BadUtf8)
+    }
+
+    /// Returns the ownership, permissions and modification time of the entry, to write it to
+    /// another archive.
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            mode: self.mode & 0o7777,
+            uid: self.uid,
+            gid: self.gid,
+            mtime: self.mtime,
+        }
+    }
+}
+
 fn read_exact<'a>(buf: &'a [u8], off: &mut usize, n: usize) -> Result<&'a [u8], CpioError> {
     let start = *off;
     let end = start.checked_add(n).ok_or(CpioError::UnexpectedEof)?;
@@ -54,10 +182,9 @@ fn read_exact<'a>(buf: &'a [u8], off: &mut usize, n: usize) -> Result<&'a [u8],
     Ok(&buf[start..end])
 }
 
-fn align_up_4(x: usize) -> Result<usize, CpioError> {
-    // (x + 3) & !3, with overflow checking
-    let y = x.checked_add(3).ok_or(CpioError::BadAlignment)?;
-    Ok(y & !3)
+fn align_up(x: usize, align: usize) -> Result<usize, CpioError> {
+    x.checked_next_multiple_of(align)
+        .ok_or(CpioError::BadAlignment)
 }
 
 fn parse_hex_u32(field: &[u8]) -> Result<u32, CpioError> {
@@ -66,7 +193,99 @@ fn parse_hex_u32(field: &[u8]) -> Result<u32, CpioError> {
     u32::from_str_radix(s, 16).map_err(|_| CpioError::BadHex)
 }
 
-/// Iterator over entries in a `newc` archive.
+fn parse_octal(field: &[u8]) -> Result<u64, CpioError> {
+    // field is ASCII octal, 6 or 11 chars
+    let s = core::str::from_utf8(field).map_err(|_| CpioError::BadHex)?;
+    u64::from_str_radix(s, 8).map_err(|_| CpioError::BadHex)
+}
+
+fn parse_octal_u32(field: &[u8]) -> Result<u32, CpioError> {
+    u32::try_from(parse_octal(field)?).map_err(|_| CpioError::BadHex)
+}
+
+/// Fields of a header.
+struct Header {
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    filesize: usize,
+    dev: (u32, u32),
+    rdev: (u32, u32),
+    namesize: usize,
+    /// Checksum of the file data, for "crc" headers.
+    check: u32,
+}
+
+impl Header {
+    fn parse(format: Format, hdr: &[u8]) -> Result<Self, CpioError> {
+        match format {
+            // offsets in header after magic:
+            // ino      [6..14]
+            // mode     [14..22]
+            // uid      [22..30]
+            // gid      [30..38]
+            // nlink    [38..46]
+            // mtime    [46..54]
+            // filesize [54..62]
+            // devmajor [62..70]
+            // devminor [70..78]
+            // rdevmaj  [78..86]
+            // rdevmin  [86..94]
+            // namesize [94..102]
+            // check    [102..110]
+            Format::Newc | Format::Crc => Ok(Self {
+                ino: parse_hex_u32(&hdr[6..14])?,
+                mode: parse_hex_u32(&hdr[14..22])?,
+                uid: parse_hex_u32(&hdr[22..30])?,
+                gid: parse_hex_u32(&hdr[30..38])?,
+                nlink: parse_hex_u32(&hdr[38..46])?,
+                mtime: parse_hex_u32(&hdr[46..54])?,
+                filesize: parse_hex_u32(&hdr[54..62])? as usize,
+                dev: (parse_hex_u32(&hdr[62..70])?, parse_hex_u32(&hdr[70..78])?),
+                rdev: (parse_hex_u32(&hdr[78..86])?, parse_hex_u32(&hdr[86..94])?),
+                namesize: parse_hex_u32(&hdr[94..102])? as usize,
+                check: parse_hex_u32(&hdr[102..110])?,
+            }),
+            // offsets in header after magic:
+            // dev      [6..12]
+            // ino      [12..18]
+            // mode     [18..24]
+            // uid      [24..30]
+            // gid      [30..36]
+            // nlink    [36..42]
+            // rdev     [42..48]
+            // mtime    [48..59]
+            // namesize [59..65]
+            // filesize [65..76]
+            //
+            // Device numbers are 16-bit, with the major number in the upper byte.
+            Format::Odc => Ok(Self {
+                ino: parse_octal_u32(&hdr[12..18])?,
+                mode: parse_octal_u32(&hdr[18..24])?,
+                uid: parse_octal_u32(&hdr[24..30])?,
+                gid: parse_octal_u32(&hdr[30..36])?,
+                nlink: parse_octal_u32(&hdr[36..42])?,
+                mtime: parse_octal_u32(&hdr[48..59])?,
+                dev: split_old_dev(parse_octal_u32(&hdr[6..12])?),
+                rdev: split_old_dev(parse_octal_u32(&hdr[42..48])?),
+                filesize: usize::try_from(parse_octal(&hdr[65..76])?)
+                    .map_err(|_| CpioError::UnexpectedEof)?,
+                namesize: parse_octal_u32(&hdr[59..65])? as usize,
+                check: 0,
+            }),
+        }
+    }
+}
+
+fn split_old_dev(dev: u32) -> (u32, u32) {
+    (dev >> 8, dev & 0xff)
+}
+
+/// Iterator over entries in a `newc`, `crc` or `odc` archive.
+#[derive(Clone)]
 pub struct NewcIter<'a> {
     buf: &'a [u8],
     off: usize,
@@ -81,111 +300,99 @@ impl<'a> NewcIter<'a> {
             done: false,
         }
     }
-}
-
-impl<'a> Iterator for NewcIter<'a> {
-    type Item = Result<CpioEntry<'a>, CpioError>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.done {
-            return None;
-        }
 
-        // Each newc header is 110 bytes:
-        // magic[6] + 13 fields * 8 hex chars.
-        const HDR_LEN: usize = 110;
+    /// Reads the entry at the current offset, which is the trailer if `None` is returned.
+    fn read_entry(&mut self) -> Result<Option<CpioEntry<'a>>, CpioError> {
+        let magic = self
+            .buf
+            .get(self.off..self.off + 6)
+            .ok_or(CpioError::UnexpectedEof)?;
+        let format = Format::from_magic(magic).ok_or(CpioError::BadMagic)?;
+        let hdr = read_exact(self.buf, &mut self.off, format.header_len())?;
+        let hdr = Header::parse(format, hdr)?;
 
-        let hdr = match read_exact(self.buf, &mut self.off, HDR_LEN) {
-            Ok(h) => h,
-            Err(e) => return Some(Err(e)),
+        // name includes a trailing NUL, and namesize is at least 1.
+        let name_bytes = read_exact(self.buf, &mut self.off, hdr.namesize)?;
+        let Some((0, name)) = name_bytes.split_last() else {
+            // Not strictly required to error, but helps catch malformed archives.
+            return Err(CpioError::BadUtf8);
         };
+        let name = str::from_utf8(name).map_err(|_| CpioError::BadUtf8)?;
 
-        // Magic: "070701" (newc). (There is also "070702" for CRC; we reject it here.)
-        if &hdr[0..6] != b"070701" {
-            return Some(Err(CpioError::BadMagic));
-        }
+        // Align after name.
+        self.skip_padding(format)?;
 
-        // Parse only the fields we need:
-        // offsets in header after magic:
-        // ino      [6..14]
-        // mode     [14..22]
-        // uid      [22..30]
-        // gid      [30..38]
-        // nlink    [38..46]
-        // mtime    [46..54]
-        // filesize [54..62]
-        // devmajor [62..70]
-        // devminor [70..78]
-        // rdevmaj  [78..86]
-        // rdevmin  [86..94]
-        // namesize [94..102]
-        // check    [102..110]
-        let mode = match parse_hex_u32(&hdr[14..22]) {
-            Ok(v) => v,
-            Err(e) => return Some(Err(e)),
-        };
-        let filesize = match parse_hex_u32(&hdr[54..62]) {
-            Ok(v) => v as usize,
-            Err(e) => return Some(Err(e)),
-        };
-        let namesize = match parse_hex_u32(&hdr[94..102]) {
-            Ok(v) => v as usize,
-            Err(e) => return Some(Err(e)),
-        };
+        // Read file data.
+        let data = read_exact(self.buf, &mut self.off, hdr.filesize)?;
 
-        // name includes a trailing NUL, and namesize is at least 1.
-        let name_bytes = match read_exact(self.buf, &mut self.off, namesize) {
-            Ok(v) => v,
-            Err(e) => return Some(Err(e)),
-        };
+        // Align again after file data.
+        self.skip_padding(format)?;
 
-        if name_bytes.is_empty() || *name_bytes.last().unwrap() != 0 {
-            // Not strictly required to error, but helps catch malformed archives.
-            return Some(Err(CpioError::BadUtf8));
+        // End marker
+        if name == "TRAILER!!!" {
+            return Ok(None);
         }
 
-        let name_nul_stripped = &name_bytes[..name_bytes.len() - 1];
-        let name = match str::from_utf8(name_nul_stripped) {
-            Ok(s) => s,
-            Err(_) => return Some(Err(CpioError::BadUtf8)),
-        };
-
-        // Align to 4-byte boundary after name.
-        let aligned = match align_up_4(self.off) {
-            Ok(v) => v,
-            Err(e) => return Some(Err(e)),
-        };
-        if aligned > self.buf.len() {
-            return Some(Err(CpioError::UnexpectedEof));
+        // Only the data of regular files is checksummed
+        if format == Format::Crc && is_reg(hdr.mode) && checksum(data) != hdr.check {
+            return Err(CpioError::BadChecksum);
         }
-        self.off = aligned;
 
-        // Read file data.
-        let data = match read_exact(self.buf, &mut self.off, filesize) {
-            Ok(v) => v,
-            Err(e) => return Some(Err(e)),
-        };
+        Ok(Some(CpioEntry {
+            name,
+            mode: hdr.mode,
+            format,
+            ino: hdr.ino,
+            uid: hdr.uid,
+            gid: hdr.gid,
+            nlink: hdr.nlink,
+            mtime: hdr.mtime,
+            dev_major: hdr.dev.0,
+            dev_minor: hdr.dev.1,
+            rdev_major: hdr.rdev.0,
+            rdev_minor: hdr.rdev.1,
+            data,
+        }))
+    }
 
-        // Align again after file data.
-        let aligned = match align_up_4(self.off) {
-            Ok(v) => v,
-            Err(e) => return Some(Err(e)),
-        };
+    fn skip_padding(&mut self, format: Format) -> Result<(), CpioError> {
+        let aligned = align_up(self.off, format.alignment())?;
         if aligned > self.buf.len() {
-            return Some(Err(CpioError::UnexpectedEof));
+            return Err(CpioError::UnexpectedEof);
         }
         self.off = aligned;
+        Ok(())
+    }
+}
 
-        // End marker
-        if name == "TRAILER!!!" {
-            self.done = true;
+impl<'a> Iterator for NewcIter<'a> {
+    type Item = Result<CpioEntry<'a>, CpioError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
             return None;
         }
 
-        Some(Ok(CpioEntry { name, mode, data }))
+        match self.read_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // The position of the next entry is unknown
+                self.done = true;
+                Some(Err(e))
+            }
+        }
     }
 }
 
+/// Checksum of the file data of "crc" entries: the sum of its bytes, modulo 2^32.
+pub fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |sum, &b| sum.wrapping_add(b.into()))
+}
+
 /// Convenience: find a file by exact path and return its payload.
 pub fn find_file<'a>(archive: &'a [u8], path: &str) -> Result<Option<&'a [u8]>, CpioError> {
     for ent in NewcIter::new(archive) {
@@ -197,10 +404,56 @@ pub fn find_file<'a>(archive: &'a [u8], path: &str) -> Result<Option<&'a [u8]>,
     Ok(None)
 }
 
+/// Returns the entries of the archive which are hard links to the file `id`, in order.
+///
+/// Archivers store the payload of the file once, usually with its last entry, so that the
+/// payload of each of its links is given by [`HardLinks::data`].
+pub fn hard_links(archive: &[u8], id: FileId) -> HardLinks<'_> {
+    HardLinks {
+        iter: NewcIter::new(archive),
+        id,
+    }
+}
+
+/// Iterator over the hard links to a file, returned by [`hard_links`].
+#[derive(Clone)]
+pub struct HardLinks<'a> {
+    iter: NewcIter<'a>,
+    id: FileId,
+}
+
+impl<'a> HardLinks<'a> {
+    /// Returns the payload of the file, carried by the last of its non-empty entries.
+    pub fn data(self) -> Result<&'a [u8], CpioError> {
+        let mut data: &[u8] = &[];
+        for entry in self {
+            let entry = entry?;
+            if !entry.data.is_empty() {
+                data = entry.data;
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl<'a> Iterator for HardLinks<'a> {
+    type Item = Result<CpioEntry<'a>, CpioError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find(|entry| match entry {
+            Ok(entry) => entry.file_id() == self.id && !is_dir(entry.mode),
+            Err(_) => true,
+        })
+    }
+}
+
 // Optional: file type helpers (POSIX mode bits)
 pub const S_IFMT: u32 = 0o170000;
 pub const S_IFREG: u32 = 0o100000;
 pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFBLK: u32 = 0o060000;
 
 pub fn is_dir(mode: u32) -> bool {
     (mode & S_IFMT) == S_IFDIR
@@ -208,11 +461,70 @@ pub fn is_dir(mode: u32) -> bool {
 pub fn is_reg(mode: u32) -> bool {
     (mode & S_IFMT) == S_IFREG
 }
+pub fn is_symlink(mode: u32) -> bool {
+    (mode & S_IFMT) == S_IFLNK
+}
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
+    use std::{format, vec::Vec};
+
     use super::*;
 
+    /// Returns a "newc" or "crc" entry with the given magic.
+    fn newc_entry(
+        magic: &str,
+        name: &str,
+        ino: u32,
+        mode: u32,
+        nlink: u32,
+        data: &[u8],
+        check: u32,
+    ) -> Vec<u8> {
+        let mut entry = format!(
+            "{magic}{ino:08X}{mode:08X}{:08X}{:08X}{nlink:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{check:08X}",
+            0,
+            0,
+            0,
+            data.len(),
+            0,
+            0,
+            0,
+            0,
+            name.len() + 1,
+        )
+        .into_bytes();
+        entry.extend_from_slice(name.as_bytes());
+        entry.push(0);
+        entry.resize(entry.len().next_multiple_of(4), 0);
+        entry.extend_from_slice(data);
+        entry.resize(entry.len().next_multiple_of(4), 0);
+        entry
+    }
+
+    /// Returns an "odc" entry, which has no padding.
+    fn odc_entry(name: &str, mode: u32, data: &[u8]) -> Vec<u8> {
+        let mut entry = format!(
+            "070707{:06o}{:06o}{mode:06o}{:06o}{:06o}{:06o}{:06o}{:011o}{:06o}{:011o}",
+            0,
+            1,
+            0,
+            0,
+            1,
+            0,
+            0,
+            name.len() + 1,
+            data.len(),
+        )
+        .into_bytes();
+        entry.extend_from_slice(name.as_bytes());
+        entry.push(0);
+        entry.extend_from_slice(data);
+        entry
+    }
+
     #[test]
     fn parses_empty_archive() {
         // This test only checks that the iterator doesn't panic on empty input.
@@ -227,10 +539,10 @@ mod tests {
     fn parses_minimal_archive() {
         let archive = include_bytes!("../tests/data/initrd-minimal.cpio");
 
-        let mut iter = NewcIter::new(archive);
+        let iter = NewcIter::new(archive);
         assert_eq!(iter.count(), 3); // ., bin/, bin/init
 
-        let mut iter = NewcIter::new(archive);
+        let iter = NewcIter::new(archive);
         let entry = iter
             .filter_map(|e| e.ok())
             .find(|e| e.name == "bin/init")
@@ -242,4 +554,98 @@ mod tests {
             b"\x13\x05\x00\x00\x93\x08\xa0\x02\x73\x00\x00\x00\x6f\x00\x00\x00"
         );
     }
+
+    #[test]
+    fn parses_crc_archive() {
+        let data = b"\x13\x05\x00\x00\x73";
+        let mut archive = newc_entry("070702", "bin", 1, 0o040755, 1, b"", 0);
+        archive.extend(newc_entry("070702", "bin/init", 1, 0o100755, 1, data, 0x8b));
+        archive.extend(newc_entry("070702", "TRAILER!!!", 1, 0, 1, b"", 0));
+
+        let entries: Vec<_> = NewcIter::new(&archive).map(Result::unwrap).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].format, Format::Crc);
+        assert_eq!((entries[1].name, entries[1].data), ("bin/init", &data[..]));
+        assert_eq!(checksum(data), 0x8b);
+
+        let mut archive = newc_entry("070702", "bin/init", 1, 0o100755, 1, data, 0x8c);
+        archive.extend(newc_entry("070702", "TRAILER!!!", 1, 0, 1, b"", 0));
+        let mut iter = NewcIter::new(&archive);
+        assert_eq!(iter.next().unwrap().unwrap_err(), CpioError::BadChecksum);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn parses_odc_archive() {
+        let mut archive = odc_entry(".", 0o040755, b"");
+        archive.extend(odc_entry("init", 0o100755, b"hello"));
+        archive.extend(newc_entry("070701", "etc", 1, 0o040755, 1, b"", 0));
+        archive.extend(odc_entry("TRAILER!!!", 0, b""));
+
+        let entries: Vec<_> = NewcIter::new(&archive).map(Result::unwrap).collect();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|e| (e.name, e.mode, e.format, e.data))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (".", 0o040755, Format::Odc, &b""[..]),
+                ("init", 0o100755, Format::Odc, b"hello"),
+                ("etc", 0o040755, Format::Newc, b""),
+            ]
+        );
+        assert_eq!(find_file(&archive, "init"), Ok(Some(&b"hello"[..])));
+
+        let mut archive = odc_entry("init", 0o100755, b"hello");
+        archive[20] = b'9';
+        assert_eq!(
+            NewcIter::new(&archive).next().unwrap().unwrap_err(),
+            CpioError::BadHex
+        );
+        assert_eq!(
+            NewcIter::new(b"070703").next().unwrap().unwrap_err(),
+            CpioError::BadMagic
+        );
+    }
+
+    #[test]
+    fn groups_hard_links() {
+        let mut archive = newc_entry("070701", "bin", 1, 0o040755, 2, b"", 0);
+        archive.extend(newc_entry("070701", "bin/sh", 2, 0o100755, 2, b"", 0));
+        archive.extend(newc_entry(
+            "070701", "bin/ls", 3, 0o120777, 1, b"busybox", 0,
+        ));
+        archive.extend(newc_entry(
+            "070701",
+            "bin/busybox",
+            2,
+            0o100755,
+            2,
+            b"\x7fELF",
+            0,
+        ));
+        archive.extend(newc_entry("070701", "TRAILER!!!", 0, 0, 1, b"", 0));
+
+        let entries: Vec<_> = NewcIter::new(&archive).map(Result::unwrap).collect();
+        let [bin, sh, ls, busybox] = &entries[..] else {
+            panic!("unexpected entries: {entries:?}");
+        };
+        assert!(!bin.is_hard_link());
+        assert!(sh.is_hard_link() && busybox.is_hard_link());
+        assert!(!ls.is_hard_link());
+        assert_eq!(sh.file_id(), busybox.file_id());
+
+        let links: Vec<_> = hard_links(&archive, sh.file_id())
+            .map(|e| e.unwrap().name)
+            .collect();
+        assert_eq!(links, ["bin/sh", "bin/busybox"]);
+        assert_eq!(
+            hard_links(&archive, sh.file_id()).data(),
+            Ok(&b"\x7fELF"[..])
+        );
+
+        assert_eq!(ls.symlink_target(), Ok(Some("busybox")));
+        assert_eq!(sh.symlink_target(), Ok(None));
+    }
 }
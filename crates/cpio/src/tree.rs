@@ -0,0 +1,451 @@
+//! Indexed directory tree over an archive.
+
+use alloc::{
+    collections::{BTreeMap, btree_map},
+    vec::Vec,
+};
+use core::{fmt, str};
+
+use crate::{CpioEntry, CpioError, FileId, NewcIter, S_IFDIR, is_dir, is_symlink};
+
+/// Index of the root directory in the nodes of a tree.
+const ROOT: usize = 0;
+
+/// Maximum number of symlinks followed by a lookup, as on Linux.
+const MAX_SYMLINKS: usize = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LookupError {
+    /// No file has this path.
+    NotFound,
+    /// A component of the path which is not the last one is not a directory.
+    NotDir,
+    /// Too many symlinks were followed, which is usually caused by a loop.
+    TooManyLinks,
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LookupError::NotFound => "no such file or directory",
+            LookupError::NotDir => "not a directory",
+            LookupError::TooManyLinks => "too many levels of symbolic links",
+        };
+        f.write_str(s)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LookupError {}
+
+/// Status of a file of a [`CpioTree`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stat {
+    /// Number identifying the file in the tree, shared by its hard links.
+    pub ino: usize,
+    /// POSIX mode (includes file type bits).
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u32,
+    pub mtime: u32,
+    /// Size of the payload of the file.
+    pub size: usize,
+    pub rdev_major: u32,
+    pub rdev_minor: u32,
+}
+
+struct Node<'a> {
+    name: &'a str,
+    parent: usize,
+    /// Entry of the node, or `None` for a directory only implied by the paths of others.
+    entry: Option<CpioEntry<'a>>,
+    /// Payload of the node, shared by all the hard links of a file.
+    data: &'a [u8],
+    ino: usize,
+    children: BTreeMap<&'a str, usize>,
+}
+
+impl Node<'_> {
+    fn mode(&self) -> u32 {
+        self.entry.map_or(S_IFDIR | 0o755, |e| e.mode)
+    }
+}
+
+/// Returns the components of `path` naming a file, ignoring empty and `.` components.
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|c| !c.is_empty() && *c != ".")
+}
+
+/// Directory tree of the entries of an archive, to use it as a file system.
+///
+/// Paths are normalised, ignoring leading `/` and `./` as well as empty and `.` components, so
+/// that `init`, `./init` and `/init` name the same file. Directories implied by the path of an
+/// entry but missing from the archive are added, owned by root with mode 0755. As when
+/// extracting the archive, the last of several entries with the same path wins.
+pub struct CpioTree<'a> {
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> CpioTree<'a> {
+    /// Indexes the entries of `archive`.
+    pub fn new(archive: &'a [u8]) -> Result<Self, CpioError> {
+        let mut tree = Self { nodes: Vec::new() };
+        tree.add_node("", ROOT);
+
+        // First node of each hard-linked file, whose inode number its other links share, and
+        // payload of the file
+        let mut links: BTreeMap<FileId, (usize, &'a [u8])> = BTreeMap::new();
+
+        for entry in NewcIter::new(archive) {
+            let entry = entry?;
+
+            let mut index = ROOT;
+            for name in components(entry.name) {
+                index = match name {
+                    ".." => tree.nodes[index].parent,
+                    name => match tree.nodes[index].children.get(name) {
+                        Some(&child) => child,
+                        None => tree.add_node(name, index),
+                    },
+                };
+            }
+            if index == ROOT && !is_dir(entry.mode) {
+                continue;
+            }
+
+            let node = &mut tree.nodes[index];
+            node.entry = Some(entry);
+            node.data = entry.data;
+            node.ino = index;
+
+            if entry.is_hard_link() {
+                let (first, data) = links.entry(entry.file_id()).or_insert((index, &[]));
+                node.ino = *first;
+                if !entry.data.is_empty() {
+                    *data = entry.data;
+                }
+            }
+        }
+
+        // Archivers store the payload of hard-linked files with only one of their links
+        for node in &mut tree.nodes {
+            if let Some(entry) = node.entry.filter(CpioEntry::is_hard_link) {
+                node.data = links[&entry.file_id()].1;
+            }
+        }
+
+        Ok(tree)
+    }
+
+    fn add_node(&mut self, name: &'a str, parent: usize) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            name,
+            parent,
+            entry: None,
+            data: &[],
+            ino: index,
+            children: BTreeMap::new(),
+        });
+        if index != ROOT {
+            self.nodes[parent].children.insert(name, index);
+        }
+        index
+    }
+
+    fn entry(&self, index: usize) -> TreeEntry<'_, 'a> {
+        TreeEntry { tree: self, index }
+    }
+
+    pub fn root(&self) -> TreeEntry<'_, 'a> {
+        self.entry(ROOT)
+    }
+
+    /// Returns the file at `path`, following symlinks, including one at `path` itself.
+    ///
+    /// Relative paths are resolved from the root directory, and `..` in the root directory is
+    /// the root directory itself.
+    pub fn lookup(&self, path: &str) -> Result<TreeEntry<'_, 'a>, LookupError> {
+        self.walk(ROOT, path, true, &mut 0).map(|i| self.entry(i))
+    }
+
+    /// Returns the file at `path` like [`CpioTree::lookup`], but returns a symlink at `path`
+    /// itself instead of following it.
+    pub fn lookup_nofollow(&self, path: &str) -> Result<TreeEntry<'_, 'a>, LookupError> {
+        self.walk(ROOT, path, false, &mut 0).map(|i| self.entry(i))
+    }
+
+    /// Returns the status of the file at `path`, following symlinks.
+    pub fn stat(&self, path: &str) -> Result<Stat, LookupError> {
+        self.lookup(path).map(|e| e.stat())
+    }
+
+    /// Returns the entries of the directory at `path`, following symlinks.
+    pub fn read_dir(&self, path: &str) -> Result<ReadDir<'_, 'a>, LookupError> {
+        let dir = self.lookup(path)?;
+        if !dir.is_dir() {
+            return Err(LookupError::NotDir);
+        }
+        Ok(dir.read_dir())
+    }
+
+    /// Resolves `path` from the directory `start`, counting the symlinks followed in `links`.
+    fn walk(
+        &self,
+        start: usize,
+        path: &str,
+        follow: bool,
+        links: &mut usize,
+    ) -> Result<usize, LookupError> {
+        let mut index = if path.starts_with('/') { ROOT } else { start };
+        let mut components = components(path).peekable();
+
+        while let Some(name) = components.next() {
+            let node = &self.nodes[index];
+            if !is_dir(node.mode()) {
+                return Err(LookupError::NotDir);
+            }
+            index = match name {
+                ".." => node.parent,
+                name => *node.children.get(name).ok_or(LookupError::NotFound)?,
+            };
+
+            let node = &self.nodes[index];
+            let last = components.peek().is_none();
+            if is_symlink(node.mode()) && (follow || !last) {
+                *links += 1;
+                if *links > MAX_SYMLINKS {
+                    return Err(LookupError::TooManyLinks);
+                }
+                let target = str::from_utf8(node.data).map_err(|_| LookupError::NotFound)?;
+                index = self.walk(node.parent, target, true, links)?;
+            }
+        }
+
+        Ok(index)
+    }
+}
+
+/// File of a [`CpioTree`].
+#[derive(Clone, Copy)]
+pub struct TreeEntry<'t, 'a> {
+    tree: &'t CpioTree<'a>,
+    index: usize,
+}
+
+impl<'t, 'a> TreeEntry<'t, 'a> {
+    fn node(&self) -> &'t Node<'a> {
+        &self.tree.nodes[self.index]
+    }
+
+    /// Returns the name of the file in its directory, which is empty for the root directory.
+    pub fn name(&self) -> &'a str {
+        self.node().name
+    }
+
+    /// Returns the entry of the archive for the file, or `None` for a directory only implied by
+    /// the paths of others.
+    pub fn entry(&self) -> Option<&'t CpioEntry<'a>> {
+        self.node().entry.as_ref()
+    }
+
+    /// Returns the payload of the file, which is the target of a symlink.
+    pub fn data(&self) -> &'a [u8] {
+        self.node().data
+    }
+
+    pub fn is_dir(&self) -> bool {
+        is_dir(self.node().mode())
+    }
+
+    pub fn stat(&self) -> Stat {
+        let node = self.node();
+        let mut stat = Stat {
+            ino: node.ino,
+            mode: node.mode(),
+            uid: 0,
+            gid: 0,
+            nlink: 2,
+            mtime: 0,
+            size: node.data.len(),
+            rdev_major: 0,
+            rdev_minor: 0,
+        };
+        if let Some(entry) = &node.entry {
+            stat.uid = entry.uid;
+            stat.gid = entry.gid;
+            stat.nlink = entry.nlink;
+            stat.mtime = entry.mtime;
+            stat.rdev_major = entry.rdev_major;
+            stat.rdev_minor = entry.rdev_minor;
+        }
+        stat
+    }
+
+    /// Returns the directory containing the file, which is the root directory for itself.
+    pub fn parent(&self) -> TreeEntry<'t, 'a> {
+        self.tree.entry(self.node().parent)
+    }
+
+    /// Returns the entries of the directory in name order, without `.` and `..`, or no entries
+    /// for other files.
+    pub fn read_dir(&self) -> ReadDir<'t, 'a> {
+        ReadDir {
+            tree: self.tree,
+            iter: self.node().children.values(),
+        }
+    }
+}
+
+impl fmt::Debug for TreeEntry<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreeEntry")
+            .field("name", &self.name())
+            .field("stat", &self.stat())
+            .finish()
+    }
+}
+
+/// Iterator over the entries of a directory, returned by [`TreeEntry::read_dir`].
+pub struct ReadDir<'t, 'a> {
+    tree: &'t CpioTree<'a>,
+    iter: btree_map::Values<'t, &'a str, usize>,
+}
+
+impl<'t, 'a> Iterator for ReadDir<'t, 'a> {
+    type Item = TreeEntry<'t, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|&index| self.tree.entry(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{DeviceKind, Metadata, NewcWriter, is_reg};
+
+    /// Writes an archive into `buf` with `f`, and returns it.
+    fn write_archive(
+        buf: &mut [u8],
+        f: impl FnOnce(&mut NewcWriter<&mut [u8]>) -> Result<(), crate::WriteError<crate::BufferFull>>,
+    ) -> &[u8] {
+        let len = buf.len();
+        let mut w = NewcWriter::new(&mut buf[..]);
+        f(&mut w).unwrap();
+        let rest = w.finish().unwrap().len();
+        &buf[..len - rest]
+    }
+
+    fn names<'a>(dir: ReadDir<'_, 'a>) -> Vec<&'a str> {
+        dir.map(|e| e.name()).collect()
+    }
+
+    #[test]
+    fn normalises_paths() {
+        let mut buf = [0u8; 2048];
+        let archive = write_archive(&mut buf, |w| {
+            w.append_dir(".", Metadata::new(0o700))?;
+            w.append_file("./init", Metadata::new(0o755), b"init")?;
+            w.append_file("/etc//passwd", Metadata::new(0o644), b"root")?;
+            w.append_file("usr/lib/libc.so", Metadata::new(0o644), b"libc")?;
+            w.append_device("dev/console", Metadata::new(0o600), DeviceKind::Char, 5, 1)
+        });
+        let tree = CpioTree::new(archive).unwrap();
+
+        for path in ["init", "./init", "/init", "etc/../init", "/../init"] {
+            assert_eq!(tree.lookup(path).unwrap().data(), b"init", "{path}");
+        }
+        assert_eq!(tree.lookup("etc/passwd").unwrap().data(), b"root");
+        assert_eq!(tree.stat("/").unwrap().mode, S_IFDIR | 0o700);
+        assert_eq!(tree.root().name(), "");
+
+        // Directories missing from the archive are synthesised
+        let usr = tree.lookup("usr").unwrap();
+        assert!(usr.is_dir() && usr.entry().is_none());
+        assert_eq!(usr.stat().mode, S_IFDIR | 0o755);
+        assert_eq!(usr.parent().name(), "");
+
+        assert_eq!(
+            names(tree.read_dir("/").unwrap()),
+            ["dev", "etc", "init", "usr"]
+        );
+        assert_eq!(names(tree.read_dir("usr/lib/").unwrap()), ["libc.so"]);
+
+        let console = tree.stat("dev/console").unwrap();
+        assert_eq!((console.rdev_major, console.rdev_minor), (5, 1));
+
+        assert_eq!(tree.lookup("usr/bin").unwrap_err(), LookupError::NotFound);
+        assert_eq!(tree.lookup("init/sh").unwrap_err(), LookupError::NotDir);
+        assert!(matches!(tree.read_dir("init"), Err(LookupError::NotDir)));
+    }
+
+    #[test]
+    fn follows_symlinks() {
+        let mut buf = [0u8; 2048];
+        let archive = write_archive(&mut buf, |w| {
+            w.append_file("usr/bin/busybox", Metadata::new(0o755), b"\x7fELF")?;
+            w.append_symlink("bin", Metadata::new(0o777), "usr/bin")?;
+            w.append_symlink("usr/bin/sh", Metadata::new(0o777), "busybox")?;
+            w.append_symlink("sbin", Metadata::new(0o777), "/bin/../../bin")?;
+            w.append_symlink("loop", Metadata::new(0o777), "loop")
+        });
+        let tree = CpioTree::new(archive).unwrap();
+
+        let sh = tree.lookup("/bin/sh").unwrap();
+        assert!(is_reg(sh.stat().mode));
+        assert_eq!(sh.data(), b"\x7fELF");
+        assert_eq!(tree.lookup("sbin/sh").unwrap().name(), "busybox");
+
+        let link = tree.lookup_nofollow("bin/sh").unwrap();
+        assert_eq!((link.name(), link.data()), ("sh", &b"busybox"[..]));
+        assert_eq!(names(tree.read_dir("bin").unwrap()), ["busybox", "sh"]);
+
+        assert_eq!(tree.lookup("loop").unwrap_err(), LookupError::TooManyLinks);
+        assert!(tree.lookup_nofollow("loop").is_ok());
+    }
+
+    #[test]
+    fn shares_hard_links() {
+        // Hard links as written by GNU cpio, with the payload in the last entry
+        let mut archive = Vec::new();
+        for (name, data) in [("bin/sh", &b""[..]), ("bin/busybox", b"\x7fELF")] {
+            archive.extend_from_slice(
+                alloc::format!(
+                    "070701{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}",
+                    7,
+                    0o100755,
+                    0,
+                    0,
+                    2,
+                    0,
+                    data.len(),
+                    8,
+                    1,
+                    0,
+                    0,
+                    name.len() + 1,
+                    0,
+                )
+                .as_bytes(),
+            );
+            archive.extend_from_slice(name.as_bytes());
+            archive.push(0);
+            archive.resize(archive.len().next_multiple_of(4), 0);
+            archive.extend_from_slice(data);
+            archive.resize(archive.len().next_multiple_of(4), 0);
+        }
+        let mut buf = [0u8; 256];
+        archive.extend_from_slice(write_archive(&mut buf, |_| Ok(())));
+
+        let tree = CpioTree::new(&archive).unwrap();
+        let sh = tree.stat("bin/sh").unwrap();
+        let busybox = tree.stat("bin/busybox").unwrap();
+        assert_eq!(sh, busybox);
+        assert_eq!((sh.nlink, sh.size), (2, 4));
+        assert_eq!(tree.lookup("bin/sh").unwrap().data(), b"\x7fELF");
+    }
+}
@@ -0,0 +1,392 @@
+//! Writer of `newc` archives.
+
+use core::fmt;
+
+use crate::{S_IFBLK, S_IFCHR, S_IFDIR, S_IFLNK, S_IFREG};
+
+const TRAILER: &str = "TRAILER!!!";
+
+/// Destination of the archives written by [`NewcWriter`].
+pub trait Write {
+    type Error;
+
+    /// Writes the whole of `buf`, or fails.
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Error returned when writing past the end of a slice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferFull;
+
+impl fmt::Display for BufferFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("buffer full")
+    }
+}
+
+/// Writes at the start of the slice, which is then advanced past the written bytes.
+impl Write for &mut [u8] {
+    type Error = BufferFull;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), BufferFull> {
+        if buf.len() > self.len() {
+            return Err(BufferFull);
+        }
+        let (head, tail) = core::mem::take(self).split_at_mut(buf.len());
+        head.copy_from_slice(buf);
+        *self = tail;
+        Ok(())
+    }
+}
+
+/// Adapter writing archives to a [`std::io::Write`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StdWriter<W>(W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> StdWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self(inner)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Write for StdWriter<W> {
+    type Error = std::io::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), std::io::Error> {
+        self.0.write_all(buf)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteError<E> {
+    /// The underlying writer failed.
+    Io(E),
+    /// A name is empty, contains a NUL byte or is the name of the trailer.
+    BadName,
+    /// The data of an entry does not fit in the 32-bit size field of its header.
+    TooLarge,
+}
+
+impl<E: fmt::Display> fmt::Display for WriteError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::Io(e) => write!(f, "write error: {e}"),
+            WriteError::BadName => f.write_str("invalid entry name"),
+            WriteError::TooLarge => f.write_str("entry too large for a newc archive"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error> std::error::Error for WriteError<E> {}
+
+/// Ownership, permissions and modification time of an entry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Permission bits; the file type bits are set from the kind of entry written.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Modification time, in seconds since the Unix epoch.
+    pub mtime: u32,
+}
+
+impl Metadata {
+    /// Returns metadata owned by root, with the permission bits `mode`.
+    pub const fn new(mode: u32) -> Self {
+        Self {
+            mode,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+        }
+    }
+}
+
+/// Kind of a device node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceKind {
+    Char,
+    Block,
+}
+
+/// Writer of `newc` archives, readable by [`NewcIter`](crate::NewcIter).
+///
+/// Entries are appended in order, and each one is given its own inode number. Parents must be
+/// appended before their children for the archive to be extracted by most tools. The archive is
+/// complete once [`NewcWriter::finish`] has written its trailer.
+pub struct NewcWriter<W> {
+    inner: W,
+    /// Number of bytes written so far, for padding.
+    pos: usize,
+    /// Inode number of the next entry.
+    ino: u32,
+}
+
+impl<W: Write> NewcWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pos: 0,
+            ino: 1,
+        }
+    }
+
+    /// Appends a directory.
+    pub fn append_dir(&mut self, name: &str, meta: Metadata) -> Result<(), WriteError<W::Error>> {
+        self.append(name, S_IFDIR, meta, 2, (0, 0), b"")
+    }
+
+    /// Appends a regular file with the contents `data`.
+    pub fn append_file(
+        &mut self,
+        name: &str,
+        meta: Metadata,
+        data: &[u8],
+    ) -> Result<(), WriteError<W::Error>> {
+        self.append(name, S_IFREG, meta, 1, (0, 0), data)
+    }
+
+    /// Appends a symbolic link pointing to `target`.
+    pub fn append_symlink(
+        &mut self,
+        name: &str,
+        meta: Metadata,
+        target: &str,
+    ) -> Result<(), WriteError<W::Error>> {
+        self.append(name, S_IFLNK, meta, 1, (0, 0), target.as_bytes())
+    }
+
+    /// Appends a character or block device node, for the device `major:minor`.
+    pub fn append_device(
+        &mut self,
+        name: &str,
+        meta: Metadata,
+        kind: DeviceKind,
+        major: u32,
+        minor: u32,
+    ) -> Result<(), WriteError<W::Error>> {
+        let ty = match kind {
+            DeviceKind::Char => S_IFCHR,
+            DeviceKind::Block => S_IFBLK,
+        };
+        self.append(name, ty, meta, 1, (major, minor), b"")
+    }
+
+    /// Writes the trailer ending the archive, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, WriteError<W::Error>> {
+        self.write_entry(TRAILER, 0, Metadata::default(), 0, 1, (0, 0), b"")?;
+        Ok(self.inner)
+    }
+
+    fn append(
+        &mut self,
+        name: &str,
+        ty: u32,
+        meta: Metadata,
+        nlink: u32,
+        rdev: (u32, u32),
+        data: &[u8],
+    ) -> Result<(), WriteError<W::Error>> {
+        if name.is_empty() || name.contains('\0') || name == TRAILER {
+            return Err(WriteError::BadName);
+        }
+
+        let ino = self.ino;
+        self.write_entry(name, ino, meta, ty, nlink, rdev, data)?;
+        self.ino = self.ino.wrapping_add(1);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_entry(
+        &mut self,
+        name: &str,
+        ino: u32,
+        meta: Metadata,
+        ty: u32,
+        nlink: u32,
+        (rdev_major, rdev_minor): (u32, u32),
+        data: &[u8],
+    ) -> Result<(), WriteError<W::Error>> {
+        let filesize = u32::try_from(data.len()).map_err(|_| WriteError::TooLarge)?;
+        // The name is stored with its NUL terminator
+        let namesize = u32::try_from(name.len() + 1).map_err(|_| WriteError::TooLarge)?;
+        let mode = if ty == 0 {
+            0
+        } else {
+            ty | (meta.mode & 0o7777)
+        };
+
+        let fields = [
+            ino, mode, meta.uid, meta.gid, nlink, meta.mtime, filesize, 0, // devmajor
+            0, // devminor
+            rdev_major, rdev_minor, namesize, 0, // check
+        ];
+
+        let mut hdr = [0u8; 110];
+        hdr[..6].copy_from_slice(b"070701");
+        for (field, value) in hdr[6..].as_chunks_mut::<8>().0.iter_mut().zip(fields) {
+            encode_hex(field, value);
+        }
+
+        self.write(&hdr)?;
+        self.write(name.as_bytes())?;
+        self.write(b"\0")?;
+        self.pad()?;
+        self.write(data)?;
+        self.pad()
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<(), WriteError<W::Error>> {
+        self.inner.write_all(buf).map_err(WriteError::Io)?;
+        self.pos += buf.len();
+        Ok(())
+    }
+
+    /// Pads the archive with zeroes to the next 4-byte boundary.
+    fn pad(&mut self) -> Result<(), WriteError<W::Error>> {
+        let len = self.pos.next_multiple_of(4) - self.pos;
+        self.write(&[0; 3][..len])
+    }
+}
+
+/// Encodes `value` as 8 uppercase hex digits, as `newc` headers do.
+fn encode_hex(field: &mut [u8; 8], value: u32) {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    for (i, byte) in field.iter_mut().enumerate() {
+        *byte = DIGITS[(value >> (28 - 4 * i)) as usize & 0xf];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewcIter, find_file, is_dir, is_reg};
+
+    /// Writes a small archive into `buf`, and returns its length.
+    fn write_archive(buf: &mut [u8]) -> Result<usize, WriteError<BufferFull>> {
+        let len = buf.len();
+        let mut w = NewcWriter::new(&mut buf[..]);
+        w.append_dir(".", Metadata::new(0o755))?;
+        w.append_dir("bin", Metadata::new(0o755))?;
+        w.append_file("bin/init", Metadata::new(0o755), b"\x13\x05\x00\x00\x73")?;
+        w.append_symlink("sbin", Metadata::new(0o777), "bin")?;
+        w.append_device(
+            "dev/console",
+            Metadata {
+                mode: 0o600,
+                uid: 1000,
+                gid: 5,
+                mtime: 0x6500_0000,
+            },
+            DeviceKind::Char,
+            5,
+            1,
+        )?;
+        let rest = w.finish()?;
+        Ok(len - rest.len())
+    }
+
+    #[test]
+    fn writes_archive() {
+        let mut buf = [0u8; 1024];
+        let len = write_archive(&mut buf).unwrap();
+        let archive = &buf[..len];
+        assert_eq!(len % 4, 0);
+
+        assert_eq!(NewcIter::new(archive).count(), 5);
+
+        let mut entries = NewcIter::new(archive).map(Result::unwrap);
+        let [root, bin, init, sbin, console] = core::array::from_fn(|_| entries.next().unwrap());
+        assert_eq!((root.name, is_dir(root.mode)), (".", true));
+        assert_eq!((bin.name, bin.mode), ("bin", 0o040755));
+        assert_eq!(init.mode, 0o100755);
+        assert!(is_reg(init.mode));
+        assert_eq!(init.data, b"\x13\x05\x00\x00\x73");
+        assert_eq!((sbin.mode, sbin.data), (0o120777, &b"bin"[..]));
+        assert_eq!((console.mode, console.data), (0o020600, &b""[..]));
+        assert_eq!(
+            (
+                console.ino,
+                console.nlink,
+                console.rdev_major,
+                console.rdev_minor
+            ),
+            (5, 1, 5, 1)
+        );
+        assert_eq!(
+            console.metadata(),
+            Metadata {
+                mode: 0o600,
+                uid: 1000,
+                gid: 5,
+                mtime: 0x6500_0000,
+            }
+        );
+        assert_eq!((bin.ino, bin.nlink), (2, 2));
+        assert_eq!(sbin.symlink_target(), Ok(Some("bin")));
+
+        assert_eq!(
+            find_file(archive, "bin/init").unwrap(),
+            Some(&b"\x13\x05\x00\x00\x73"[..])
+        );
+    }
+
+    #[test]
+    fn writes_header_fields() {
+        let mut buf = [0u8; 1024];
+        let len = write_archive(&mut buf).unwrap();
+        let archive = &buf[..len];
+
+        let console = archive
+            .windows(12)
+            .position(|w| w == b"dev/console\0")
+            .unwrap()
+            - 110;
+        let hdr = &archive[console..console + 110];
+        assert_eq!(
+            hdr,
+            concat!(
+                "070701", "00000005", "00002180", "000003E8", "00000005", "00000001", "65000000",
+                "00000000", "00000000", "00000000", "00000005", "00000001", "0000000C", "00000000",
+            )
+            .as_bytes()
+        );
+    }
+
+    #[test]
+    fn write_errors() {
+        let mut buf = [0u8; 64];
+        let mut w = NewcWriter::new(&mut buf[..]);
+        assert_eq!(
+            w.append_dir("", Metadata::default()),
+            Err(WriteError::BadName)
+        );
+        assert_eq!(
+            w.append_file("a\0b", Metadata::default(), b""),
+            Err(WriteError::BadName)
+        );
+        assert_eq!(
+            w.append_dir(TRAILER, Metadata::default()),
+            Err(WriteError::BadName)
+        );
+        assert_eq!(
+            w.append_dir("bin", Metadata::default()),
+            Err(WriteError::Io(BufferFull))
+        );
+
+        let mut buf = [0u8; 1024];
+        assert_eq!(
+            write_archive(&mut buf[..200]),
+            Err(WriteError::Io(BufferFull))
+        );
+    }
+}
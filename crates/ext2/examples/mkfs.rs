@@ -0,0 +1,37 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    hash::{BuildHasher, RandomState},
+    io,
+};
+
+use ext2::{FormatOptions, StdIoWrapper};
+
+/// Returns a random version 4 uuid.
+fn random_uuid() -> [u8; 16] {
+    let mut uuid = [0; 16];
+    for half in uuid.as_chunks_mut::<8>().0 {
+        *half = RandomState::new().hash_one(half.as_ptr()).to_le_bytes();
+    }
+    uuid[6] = (uuid[6] & 0x0f) | 0x40;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    uuid
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+    let (Some(path), label) = (args.next(), args.next()) else {
+        eprintln!("usage: mkfs <image> [label]");
+        std::process::exit(1);
+    };
+
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let options = FormatOptions {
+        label: label.as_deref().unwrap_or(""),
+        uuid: random_uuid(),
+        ..Default::default()
+    };
+    ext2::format(&mut StdIoWrapper::new(file), options)?;
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use alloc::vec::Vec;
+
+use crate::{
+    error::Error,
+    fs::FileSystem,
+    io::{Read, Seek, Write},
+};
+
+/// Version of the ext2 on-disk ACL format.
+const ACL_VERSION: u32 = 1;
+
+const ACL_USER_OBJ: u16 = 0x01;
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP_OBJ: u16 = 0x04;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_OTHER: u16 = 0x20;
+
+/// Whom an ACL entry applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclTag {
+    /// The owner of the file.
+    UserObj,
+    /// The user with the given id.
+    User(u32),
+    /// The owning group of the file.
+    GroupObj,
+    /// The group with the given id.
+    Group(u32),
+    /// The maximum permissions granted by named users, groups and the owning group.
+    Mask,
+    /// Everyone else.
+    Other,
+}
+
+/// An entry of an ACL, granting the permissions `perm` (a combination of 4 for read, 2 for
+/// write and 1 for execute) to `tag`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AclEntry {
+    pub tag: AclTag,
+    pub perm: u16,
+}
+
+/// A POSIX access control list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Acl {
+    entries: Vec<AclEntry>,
+}
+
+impl Acl {
+    /// Returns the entries of this ACL, in the order they are stored.
+    pub fn entries(&self) -> &[AclEntry] {
+        &self.entries
+    }
+
+    /// Parses an ACL in the ext2 format, where entries without an id are shortened.
+    fn from_bytes<E>(buf: &[u8]) -> Result<Self, Error<E>> {
+        let (version, mut rest) = buf.split_at_checked(4).ok_or(Error::CorruptedFileSystem)?;
+        if u32::from_le_bytes(version.try_into().unwrap()) != ACL_VERSION {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        let mut entries = Vec::new();
+        while !rest.is_empty() {
+            let header = rest.get(..4).ok_or(Error::CorruptedFileSystem)?;
+            let tag = u16::from_le_bytes([header[0], header[1]]);
+            let perm = u16::from_le_bytes([header[2], header[3]]);
+
+            let (tag, len) = match tag {
+                ACL_USER | ACL_GROUP => {
+                    let id = rest.get(4..8).ok_or(Error::CorruptedFileSystem)?;
+                    let id = u32::from_le_bytes(id.try_into().unwrap());
+                    let tag = if tag == ACL_USER {
+                        AclTag::User(id)
+                    } else {
+                        AclTag::Group(id)
+                    };
+                    (tag, 8)
+                }
+                ACL_USER_OBJ => (AclTag::UserObj, 4),
+                ACL_GROUP_OBJ => (AclTag::GroupObj, 4),
+                ACL_MASK => (AclTag::Mask, 4),
+                ACL_OTHER => (AclTag::Other, 4),
+                _ => return Err(Error::CorruptedFileSystem),
+            };
+
+            entries.push(AclEntry { tag, perm });
+            rest = &rest[len..];
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Returns the access ACL of inode `ino`, stored in its `system.posix_acl_access`
+    /// attribute, or `None` if it only has the permissions of its mode.
+    pub fn access_acl(&self, ino: u32) -> Result<Option<Acl>, Error<IO::Error>> {
+        self.acl(ino, "system.posix_acl_access")
+    }
+
+    /// Returns the default ACL of directory `ino`, inherited by the files created in it, or
+    /// `None` if it has none.
+    pub fn default_acl(&self, ino: u32) -> Result<Option<Acl>, Error<IO::Error>> {
+        self.acl(ino, "system.posix_acl_default")
+    }
+
+    fn acl(&self, ino: u32, name: &str) -> Result<Option<Acl>, Error<IO::Error>> {
+        self.get_xattr(ino, name)?
+            .map(|value| Acl::from_bytes(&value))
+            .transpose()
+    }
+}
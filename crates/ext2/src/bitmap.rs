@@ -0,0 +1,185 @@
+use alloc::vec;
+
+use crate::{
+    error::Error,
+    fs::FileSystem,
+    io::{Read, Seek, Write},
+};
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Allocates a zeroed block, preferably in group `goal`.
+    pub(crate) fn alloc_block(&self, goal: u32) -> Result<u32, Error<IO::Error>> {
+        if self.free_blocks_count() == 0 {
+            return Err(Error::NotEnoughSpace);
+        }
+
+        let group_count = self.group_count();
+
+        for group in (0..group_count).map(|i| (goal + i) % group_count) {
+            let (bitmap, free) = {
+                let gd = &self.groups.borrow()[group as usize];
+                (gd.block_bitmap, gd.free_blocks_count)
+            };
+            if free == 0 {
+                continue;
+            }
+
+            let Some(bit) = self.set_first_free_bit(bitmap, self.blocks_in_group(group))? else {
+                continue;
+            };
+
+            {
+                // Saturate, as the counters may be stale
+                let mut groups = self.groups.borrow_mut();
+                let gd = &mut groups[group as usize];
+                gd.free_blocks_count = gd.free_blocks_count.saturating_sub(1);
+                let mut sb = self.sb.borrow_mut();
+                sb.free_blocks_count = sb.free_blocks_count.saturating_sub(1);
+            }
+            self.write_group_desc(group)?;
+            self.write_superblock()?;
+
+            let block = self.first_data_block() + group * self.blocks_per_group() + bit;
+            self.write_block(block, &vec![0; self.block_size() as usize])?;
+
+            return Ok(block);
+        }
+
+        Err(Error::NotEnoughSpace)
+    }
+
+    /// Releases a block previously allocated with [`FileSystem::alloc_block`].
+    pub(crate) fn free_block(&self, block: u32) -> Result<(), Error<IO::Error>> {
+        if block < self.first_data_block() || block >= self.blocks_count() {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        let group = (block - self.first_data_block()) / self.blocks_per_group();
+        let bit = (block - self.first_data_block()) % self.blocks_per_group();
+
+        let bitmap = self.groups.borrow()[group as usize].block_bitmap;
+        if !self.clear_bit(bitmap, bit)? {
+            // Already free, so the counters don't account for it
+            return Ok(());
+        }
+
+        self.groups.borrow_mut()[group as usize].free_blocks_count += 1;
+        self.sb.borrow_mut().free_blocks_count += 1;
+        self.write_group_desc(group)?;
+        self.write_superblock()
+    }
+
+    /// Allocates an inode, preferably in group `goal`.
+    pub(crate) fn alloc_inode(&self, goal: u32, is_dir: bool) -> Result<u32, Error<IO::Error>> {
+        if self.free_inodes_count() == 0 {
+            return Err(Error::NotEnoughSpace);
+        }
+
+        let group_count = self.group_count();
+        let inodes_per_group = self.inodes_per_group();
+
+        for group in (0..group_count).map(|i| (goal + i) % group_count) {
+            let (bitmap, free) = {
+                let gd = &self.groups.borrow()[group as usize];
+                (gd.inode_bitmap, gd.free_inodes_count)
+            };
+            if free == 0 {
+                continue;
+            }
+
+            let Some(bit) = self.set_first_free_bit(bitmap, inodes_per_group)? else {
+                continue;
+            };
+
+            let ino = group * inodes_per_group + bit + 1;
+            if ino < self.first_ino() {
+                // Reserved inodes are always marked as used, so this can only be corruption
+                return Err(Error::CorruptedFileSystem);
+            }
+
+            {
+                // Saturate, as the counters may be stale
+                let mut groups = self.groups.borrow_mut();
+                let gd = &mut groups[group as usize];
+                gd.free_inodes_count = gd.free_inodes_count.saturating_sub(1);
+                if is_dir {
+                    gd.used_dirs_count += 1;
+                }
+                let mut sb = self.sb.borrow_mut();
+                sb.free_inodes_count = sb.free_inodes_count.saturating_sub(1);
+            }
+            self.write_group_desc(group)?;
+            self.write_superblock()?;
+
+            return Ok(ino);
+        }
+
+        Err(Error::NotEnoughSpace)
+    }
+
+    /// Releases an inode previously allocated with [`FileSystem::alloc_inode`].
+    pub(crate) fn free_inode(&self, ino: u32, is_dir: bool) -> Result<(), Error<IO::Error>> {
+        if ino < self.first_ino() || ino > self.inodes_count() {
+            return Err(Error::InvalidInput);
+        }
+
+        let group = (ino - 1) / self.inodes_per_group();
+        let bit = (ino - 1) % self.inodes_per_group();
+
+        let bitmap = self.groups.borrow()[group as usize].inode_bitmap;
+        if !self.clear_bit(bitmap, bit)? {
+            // Already free, so the counters don't account for it
+            return Ok(());
+        }
+
+        {
+            let mut groups = self.groups.borrow_mut();
+            let gd = &mut groups[group as usize];
+            gd.free_inodes_count += 1;
+            if is_dir {
+                // Saturate, as the counter may be stale
+                gd.used_dirs_count = gd.used_dirs_count.saturating_sub(1);
+            }
+        }
+        self.sb.borrow_mut().free_inodes_count += 1;
+        self.write_group_desc(group)?;
+        self.write_superblock()
+    }
+
+    /// Returns the number of blocks in `group`, which is smaller for the last group.
+    pub(crate) fn blocks_in_group(&self, group: u32) -> u32 {
+        let first = group * self.blocks_per_group();
+        self.blocks_per_group()
+            .min(self.blocks_count() - self.first_data_block() - first)
+    }
+
+    /// Finds the first clear bit among the first `len` bits of `bitmap` and sets it.
+    fn set_first_free_bit(&self, bitmap: u32, len: u32) -> Result<Option<u32>, Error<IO::Error>> {
+        let mut buf = vec![0; self.block_size() as usize];
+        self.read_block(bitmap, &mut buf)?;
+
+        let Some(bit) = (0..len).find(|&i| buf[i as usize / 8] & (1 << (i % 8)) == 0) else {
+            return Ok(None);
+        };
+
+        buf[bit as usize / 8] |= 1 << (bit % 8);
+        self.write_block(bitmap, &buf)?;
+
+        Ok(Some(bit))
+    }
+
+    /// Clears bit `bit` of `bitmap`, returning `false` if it was already clear.
+    fn clear_bit(&self, bitmap: u32, bit: u32) -> Result<bool, Error<IO::Error>> {
+        let mut buf = vec![0; self.block_size() as usize];
+        self.read_block(bitmap, &mut buf)?;
+
+        let (byte, mask) = (bit as usize / 8, 1 << (bit % 8));
+        if buf[byte] & mask == 0 {
+            return Ok(false);
+        }
+
+        buf[byte] &= !mask;
+        self.write_block(bitmap, &buf)?;
+        Ok(true)
+    }
+}
@@ -1,42 +1,225 @@
+use core::mem::size_of;
+
+/// Plain-old-data structures that can be read from and written to disk as-is.
+///
+/// # Safety
+///
+/// Implementors must be `repr(C, packed)` structs made only of integers and integer arrays, so
+/// that every bit pattern is a valid value and there is no padding.
+pub(crate) unsafe trait OnDisk: Copy {
+    /// Decodes `Self` from the first `size_of::<Self>()` bytes of `buf`.
+    ///
+    /// Panics if `buf` is too short.
+    fn from_bytes(buf: &[u8]) -> Self {
+        assert!(buf.len() >= size_of::<Self>());
+        // SAFETY: the buffer is large enough, and the trait contract guarantees that any bit
+        // pattern is a valid `Self`
+        unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const Self) }
+    }
+
+    /// Encodes `self` into the first `size_of::<Self>()` bytes of `buf`.
+    ///
+    /// Panics if `buf` is too short.
+    fn to_bytes(&self, buf: &mut [u8]) {
+        assert!(buf.len() >= size_of::<Self>());
+        // SAFETY: the buffer is large enough and `Self` has no padding
+        unsafe { core::ptr::write_unaligned(buf.as_mut_ptr() as *mut Self, *self) }
+    }
+}
+
 #[repr(C, packed)]
 #[derive(Debug, Copy, Clone)]
-struct SuperBlock {
-    inodes_count: u32,      // total number of inodes both used and free
-    blocks_count: u32,      // total number of blocks both used, free and reserved
-    r_blocks_count: u32,    // total number of blocks reserved for root
-    free_blocks_count: u32, // total number of free blocks, including reserved
-    free_inodes_count: u32, // total number of free inodes
-    first_data_block: u32,  // first data block, ie. the id of the block containing the superblock
-    log_block_size: u32,    // log2 of the block size
-    log_frag_size: u32,     // log2 of the fragment size
-    blocks_per_group: u32,  // number of blocks per group
-    frags_per_group: u32,   // number of fragments per group
-    inodes_per_group: u32,  // number of inodes per group
-    mtime: u32,             // time of last mount
-    wtime: u32,             // time of last write access to the file system
-    mnt_count: u16,         // number of times the file system has been mounted since last fsck
-    max_mnt_count: u16,     // maximum number of times the file system can be mounted before fsck
-    magic: u16,             // magic number (should be 0xEF53)
-    state: u16,             // file system state
-    errors: u16,            // error behavior of the fs
-    minor_rev_level: u16,   // minor revision level of the file system
-    lastcheck: u32,         // time of last check
-    checkinterval: u32,     // max. time between checks
-    creator_os: u32,        // OS from which the file system was created
-    rev_level: u32,         // revision level of the file system
-    def_resuid: u16,        // default uid for reserved blocks
-    def_resgid: u16,        // default gid for reserved blocks
+#[allow(dead_code)]
+pub(crate) struct SuperBlock {
+    pub(crate) inodes_count: u32, // total number of inodes both used and free
+    pub(crate) blocks_count: u32, // total number of blocks both used, free and reserved
+    pub(crate) r_blocks_count: u32, // total number of blocks reserved for root
+    pub(crate) free_blocks_count: u32, // total number of free blocks, including reserved
+    pub(crate) free_inodes_count: u32, // total number of free inodes
+    pub(crate) first_data_block: u32, // first data block, ie. the id of the block containing the superblock
+    pub(crate) log_block_size: u32,   // log2 of the block size
+    pub(crate) log_frag_size: u32,    // log2 of the fragment size
+    pub(crate) blocks_per_group: u32, // number of blocks per group
+    pub(crate) frags_per_group: u32,  // number of fragments per group
+    pub(crate) inodes_per_group: u32, // number of inodes per group
+    pub(crate) mtime: u32,            // time of last mount
+    pub(crate) wtime: u32,            // time of last write access to the file system
+    pub(crate) mnt_count: u16, // number of times the file system has been mounted since last fsck
+    pub(crate) max_mnt_count: u16, // maximum number of times the file system can be mounted before fsck
+    pub(crate) magic: u16,         // magic number (should be 0xEF53)
+    pub(crate) state: u16,         // file system state
+    pub(crate) errors: u16,        // error behavior of the fs
+    pub(crate) minor_rev_level: u16, // minor revision level of the file system
+    pub(crate) lastcheck: u32,     // time of last check
+    pub(crate) checkinterval: u32, // max. time between checks
+    pub(crate) creator_os: u32,    // OS from which the file system was created
+    pub(crate) rev_level: u32,     // revision level of the file system
+    pub(crate) def_resuid: u16,    // default uid for reserved blocks
+    pub(crate) def_resgid: u16,    // default gid for reserved blocks
+    // Revision 1 (dynamic) fields, zero in revision 0 file systems
+    pub(crate) first_ino: u32,           // first non-reserved inode
+    pub(crate) inode_size: u16,          // size of an inode on disk
+    pub(crate) block_group_nr: u16,      // block group hosting this superblock copy
+    pub(crate) feature_compat: u32,      // compatible feature set
+    pub(crate) feature_incompat: u32,    // incompatible feature set
+    pub(crate) feature_ro_compat: u32,   // read-only compatible feature set
+    pub(crate) uuid: [u8; 16],           // volume id
+    pub(crate) volume_name: [u8; 16],    // volume name, zero-padded
+    pub(crate) last_mounted: [u8; 64],   // path where the file system was last mounted
+    pub(crate) algo_bitmap: u32,         // compression algorithms
+    pub(crate) prealloc_blocks: u8,      // number of blocks to preallocate for files
+    pub(crate) prealloc_dir_blocks: u8,  // number of blocks to preallocate for directories
+    pub(crate) reserved_gdt_blocks: u16, // blocks reserved after the GDT for growing it
+    pub(crate) journal_uuid: [u8; 16],   // uuid of the journal superblock
+    pub(crate) journal_inum: u32,        // inode number of the journal file
+    pub(crate) journal_dev: u32,         // device number of the journal file
+    pub(crate) last_orphan: u32,         // head of the list of inodes to delete
+    pub(crate) hash_seed: [u32; 4],      // seed for the directory index hash
+    pub(crate) def_hash_version: u8,     // default directory index hash algorithm
+    pub(crate) jnl_backup_type: u8,      // how the journal inode is backed up
+    pub(crate) desc_size: u16,           // size of group descriptors with the 64bit feature
+    pub(crate) default_mount_opts: u32,  // default mount options
+    pub(crate) first_meta_bg: u32,       // first metablock block group
+    pub(crate) mkfs_time: u32,           // time when the file system was created
+    pub(crate) jnl_blocks: [u32; 17],    // backup of the block pointers and size of the journal
+    pub(crate) blocks_count_hi: u32,     // upper 32 bits of blocks_count with the 64bit feature
+    pub(crate) r_blocks_count_hi: u32,   // upper 32 bits of r_blocks_count with the 64bit feature
+    pub(crate) free_blocks_hi: u32, // upper 32 bits of free_blocks_count with the 64bit feature
+    pub(crate) min_extra_isize: u16, // minimum extra inode space used by all inodes
+    pub(crate) want_extra_isize: u16, // extra inode space new inodes should use
+    pub(crate) flags: u32,          // miscellaneous flags
+}
+
+// SAFETY: packed struct of integers
+unsafe impl OnDisk for SuperBlock {}
+
+impl SuperBlock {
+    /// Byte offset of the superblock from the beginning of the device.
+    pub(crate) const OFFSET: u64 = 1024;
+    /// Size of the superblock on disk, regardless of how many fields are actually used.
+    pub(crate) const SIZE: usize = 1024;
+    /// Value of the `magic` field of a valid ext2 file system.
+    pub(crate) const MAGIC: u16 = 0xEF53;
+
+    /// Original ext2 format, with fixed 128-byte inodes.
+    pub(crate) const GOOD_OLD_REV: u32 = 0;
+    /// Size of an inode in revision 0 file systems.
+    pub(crate) const GOOD_OLD_INODE_SIZE: u32 = 128;
+    /// First non-reserved inode in revision 0 file systems.
+    pub(crate) const GOOD_OLD_FIRST_INO: u32 = 11;
+
+    /// Dynamic revision, with variable inode sizes and feature flags.
+    pub(crate) const DYNAMIC_REV: u32 = 1;
+
+    /// Directory names are hashed with unsigned chars, rather than signed ones.
+    pub(crate) const FLAGS_UNSIGNED_HASH: u32 = 0x0002;
+
+    /// Maximum value of `log_block_size` (ie. 64 KiB blocks).
+    pub(crate) const MAX_LOG_BLOCK_SIZE: u32 = 6;
+
+    /// The file system has an ext3 journal.
+    pub(crate) const FEATURE_COMPAT_HAS_JOURNAL: u32 = 0x0004;
+    /// Directories may be indexed with a hash tree.
+    pub(crate) const FEATURE_COMPAT_DIR_INDEX: u32 = 0x0020;
+
+    /// Directory entries record the file type.
+    pub(crate) const FEATURE_INCOMPAT_FILETYPE: u32 = 0x0002;
+    /// The journal holds transactions which must be replayed before using the file system.
+    pub(crate) const FEATURE_INCOMPAT_RECOVER: u32 = 0x0004;
+    /// Incompatible features understood by this implementation.
+    pub(crate) const FEATURE_INCOMPAT_SUPP: u32 =
+        Self::FEATURE_INCOMPAT_FILETYPE | Self::FEATURE_INCOMPAT_RECOVER;
+
+    /// Superblock and GDT backups are only kept in groups 0, 1 and powers of 3, 5 and 7.
+    pub(crate) const FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+    /// Regular files may be larger than 2 GiB.
+    pub(crate) const FEATURE_RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+    /// Read-only compatible features understood by this implementation.
+    pub(crate) const FEATURE_RO_COMPAT_SUPP: u32 =
+        Self::FEATURE_RO_COMPAT_SPARSE_SUPER | Self::FEATURE_RO_COMPAT_LARGE_FILE;
+
+    /// Returns the size of a block in bytes.
+    pub(crate) fn block_size(&self) -> u32 {
+        1024 << self.log_block_size
+    }
+
+    /// Returns the number of block groups in the file system.
+    pub(crate) fn group_count(&self) -> u32 {
+        (self.blocks_count - self.first_data_block).div_ceil(self.blocks_per_group)
+    }
+
+    /// Returns the size of an inode on disk.
+    pub(crate) fn inode_size(&self) -> u32 {
+        if self.rev_level == Self::GOOD_OLD_REV {
+            Self::GOOD_OLD_INODE_SIZE
+        } else {
+            self.inode_size as u32
+        }
+    }
+
+    /// Returns the first inode number which is not reserved.
+    pub(crate) fn first_ino(&self) -> u32 {
+        if self.rev_level == Self::GOOD_OLD_REV {
+            Self::GOOD_OLD_FIRST_INO
+        } else {
+            self.first_ino
+        }
+    }
+
+    pub(crate) fn has_compat(&self, feature: u32) -> bool {
+        self.feature_compat & feature != 0
+    }
+
+    pub(crate) fn has_incompat(&self, feature: u32) -> bool {
+        self.feature_incompat & feature != 0
+    }
+
+    pub(crate) fn has_ro_compat(&self, feature: u32) -> bool {
+        self.feature_ro_compat & feature != 0
+    }
+
+    /// Returns `true` if `group` holds a backup of the superblock and of the GDT.
+    ///
+    /// Group 0 always holds the primary copies.
+    pub(crate) fn group_has_super(&self, group: u32) -> bool {
+        fn is_power_of(mut n: u32, base: u32) -> bool {
+            while n > 1 && n.is_multiple_of(base) {
+                n /= base;
+            }
+            n == 1
+        }
+
+        !self.has_ro_compat(Self::FEATURE_RO_COMPAT_SPARSE_SUPER)
+            || group <= 1
+            || is_power_of(group, 3)
+            || is_power_of(group, 5)
+            || is_power_of(group, 7)
+    }
+
+    /// Returns the number of blocks used by the group descriptor table.
+    pub(crate) fn gdt_blocks(&self) -> u32 {
+        (self.group_count() * BlockGroupDesc::SIZE as u32).div_ceil(self.block_size())
+    }
 }
 
 #[repr(C, packed)]
 #[derive(Debug, Copy, Clone)]
-struct BlockGroupDesc {
-    block_bitmap: u32,      // block id of the first block of the block bitmap
-    inode_bitmap: u32,      // block id of the first block of the inode bitmap
-    inode_table: u32,       // block id of the first block of the inode table
-    free_blocks_count: u16, // number of free blocks in the group
-    free_inodes_count: u16, // number of free inodes in the group
-    used_dirs_count: u16,   // number of inode allocated to dirs in the group
-    pad: u16,
-    reserved: [u8; 12],
+#[allow(dead_code)]
+pub(crate) struct BlockGroupDesc {
+    pub(crate) block_bitmap: u32, // block id of the first block of the block bitmap
+    pub(crate) inode_bitmap: u32, // block id of the first block of the inode bitmap
+    pub(crate) inode_table: u32,  // block id of the first block of the inode table
+    pub(crate) free_blocks_count: u16, // number of free blocks in the group
+    pub(crate) free_inodes_count: u16, // number of free inodes in the group
+    pub(crate) used_dirs_count: u16, // number of inode allocated to dirs in the group
+    pub(crate) pad: u16,
+    pub(crate) reserved: [u8; 12],
+}
+
+// SAFETY: packed struct of integers
+unsafe impl OnDisk for BlockGroupDesc {}
+
+impl BlockGroupDesc {
+    /// Size of a group descriptor on disk.
+    pub(crate) const SIZE: usize = size_of::<Self>();
 }
@@ -0,0 +1,596 @@
+//! Offline consistency checks.
+//!
+//! [`FileSystem::check`] walks the whole file system and compares what it finds with the
+//! bookkeeping kept in the bitmaps, the group descriptors and the superblock, much like
+//! `e2fsck -f` does. Simple problems, like wrong counters, can be repaired in place.
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::{
+    dir::DirEntry,
+    error::Error,
+    fs::FileSystem,
+    inode::{FileType, Inode},
+    io::{Read, Seek, Write},
+};
+
+/// Inode reserved for the blocks backing the growth of the group descriptor table.
+const RESIZE_INO: u32 = 7;
+
+/// An inconsistency found by [`FileSystem::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Problem {
+    /// A block pointer of inode `ino` points outside of the file system.
+    BadBlockPointer { ino: u32, block: u32 },
+    /// Block `block` of inode `ino` is also used by another inode or by the file system
+    /// metadata.
+    DuplicateBlock { ino: u32, block: u32 },
+    /// The record at byte `offset` of directory `dir` has an invalid length, or its name
+    /// doesn't fit in it. The rest of the block is skipped.
+    BadRecordLength { dir: u32, offset: u64 },
+    /// The record at byte `offset` of directory `dir` points to inode `ino`, which is out of
+    /// range or not in use.
+    BadEntryInode { dir: u32, offset: u64, ino: u32 },
+    /// The `.` or `..` entry of directory `dir` is missing or points to the wrong inode.
+    BadDotEntry { dir: u32 },
+    /// The link count of inode `ino` is `found`, but `expected` entries point to it.
+    LinkCount { ino: u32, found: u16, expected: u16 },
+    /// Inode `ino` is in use, but no directory entry points to it.
+    OrphanedInode { ino: u32 },
+    /// The bit of inode `ino` in the inode bitmap doesn't match whether it's in use.
+    InodeBitmap { ino: u32, in_use: bool },
+    /// The bit of block `block` in the block bitmap doesn't match whether it's in use.
+    BlockBitmap { block: u32, in_use: bool },
+    /// The descriptor of `group` records `found` free blocks instead of `expected`.
+    GroupFreeBlocks {
+        group: u32,
+        found: u32,
+        expected: u32,
+    },
+    /// The descriptor of `group` records `found` free inodes instead of `expected`.
+    GroupFreeInodes {
+        group: u32,
+        found: u32,
+        expected: u32,
+    },
+    /// The descriptor of `group` records `found` directories instead of `expected`.
+    GroupUsedDirs {
+        group: u32,
+        found: u32,
+        expected: u32,
+    },
+    /// The superblock records `found` free blocks instead of `expected`.
+    FreeBlocks { found: u32, expected: u32 },
+    /// The superblock records `found` free inodes instead of `expected`.
+    FreeInodes { found: u32, expected: u32 },
+}
+
+impl Problem {
+    /// Returns `true` if this problem is fixed by [`FileSystem::check`] in repair mode.
+    pub fn is_repairable(&self) -> bool {
+        matches!(
+            self,
+            Problem::LinkCount { .. }
+                | Problem::InodeBitmap { .. }
+                | Problem::BlockBitmap { .. }
+                | Problem::GroupFreeBlocks { .. }
+                | Problem::GroupFreeInodes { .. }
+                | Problem::GroupUsedDirs { .. }
+                | Problem::FreeBlocks { .. }
+                | Problem::FreeInodes { .. }
+        )
+    }
+}
+
+/// The outcome of [`FileSystem::check`].
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// All the problems found, in the order they were detected.
+    pub problems: Vec<Problem>,
+    /// `true` if the repairable problems have been fixed.
+    pub repaired: bool,
+}
+
+impl Report {
+    /// Returns `true` if no problem was found.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Returns the problems which are still present on disk.
+    pub fn remaining(&self) -> impl Iterator<Item = &Problem> {
+        self.problems
+            .iter()
+            .filter(|p| !(self.repaired && p.is_repairable()))
+    }
+}
+
+/// A fixed-size set of bits.
+struct Bitset(Vec<u8>);
+
+impl Bitset {
+    fn new(len: u32) -> Self {
+        Self(vec![0; len.div_ceil(8) as usize])
+    }
+
+    fn get(&self, i: u32) -> bool {
+        self.0[i as usize / 8] & (1 << (i % 8)) != 0
+    }
+
+    /// Sets bit `i`, returning its previous value.
+    fn set(&mut self, i: u32) -> bool {
+        let old = self.get(i);
+        self.0[i as usize / 8] |= 1 << (i % 8);
+        old
+    }
+}
+
+/// What the walk found out about the file system.
+struct State {
+    report: Report,
+    /// Blocks used by metadata or by any inode in use
+    blocks: Bitset,
+    /// Attribute blocks, which may be shared by several inodes
+    xattr_blocks: Bitset,
+    /// Inodes in use, indexed by inode number
+    in_use: Bitset,
+    /// File type of every inode, indexed by inode number
+    types: Vec<FileType>,
+    /// Number of directory entries pointing to every inode, indexed by inode number
+    refs: Vec<u16>,
+    /// Directories reached from the root
+    reached: Bitset,
+}
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Checks the consistency of the whole file system.
+    ///
+    /// If `repair` is true, link counts, bitmaps and free counters are rewritten to match the
+    /// actual usage. Other problems are only reported. Blocks of inodes which are in use but
+    /// unreachable are never released.
+    pub fn check(&self, repair: bool) -> Result<Report, Error<IO::Error>> {
+        if repair {
+            self.check_writable()?;
+        }
+
+        let inodes_count = self.inodes_count();
+        let mut state = State {
+            report: Report::default(),
+            blocks: Bitset::new(self.blocks_count()),
+            xattr_blocks: Bitset::new(self.blocks_count()),
+            in_use: Bitset::new(inodes_count + 1),
+            types: vec![FileType::Unknown; inodes_count as usize + 1],
+            refs: vec![0; inodes_count as usize + 1],
+            reached: Bitset::new(inodes_count + 1),
+        };
+
+        self.mark_metadata(&mut state);
+        self.check_inodes(&mut state)?;
+        self.check_dirs(&mut state)?;
+        self.check_links(&mut state, repair)?;
+        self.check_inode_bitmaps(&mut state, repair)?;
+        self.check_block_bitmaps(&mut state, repair)?;
+        self.check_counts(&mut state, repair)?;
+
+        state.report.repaired = repair;
+        Ok(state.report)
+    }
+
+    /// Marks the blocks holding superblocks, group descriptors, bitmaps and inode tables.
+    fn mark_metadata(&self, state: &mut State) {
+        let sb = *self.sb.borrow();
+        let inode_table_blocks = (sb.inodes_per_group * sb.inode_size()).div_ceil(sb.block_size());
+
+        for (group, gd) in self.groups.borrow().iter().enumerate() {
+            let group = group as u32;
+
+            if sb.group_has_super(group) {
+                let start = sb.first_data_block + group * sb.blocks_per_group;
+                let end = (start + 1 + sb.gdt_blocks() + sb.reserved_gdt_blocks as u32)
+                    .min(sb.blocks_count);
+                for block in start..end {
+                    state.blocks.set(block);
+                }
+            }
+
+            state.blocks.set(gd.block_bitmap);
+            state.blocks.set(gd.inode_bitmap);
+            for block in gd.inode_table..gd.inode_table + inode_table_blocks {
+                state.blocks.set(block);
+            }
+        }
+    }
+
+    /// Finds the inodes in use and marks their blocks.
+    ///
+    /// Reserved inodes are always in use, while the others are in use if they have links.
+    fn check_inodes(&self, state: &mut State) -> Result<(), Error<IO::Error>> {
+        for ino in 1..=self.inodes_count() {
+            let inode = self.inode(ino)?;
+            if ino >= self.first_ino() && inode.links_count() == 0 {
+                continue;
+            }
+
+            state.in_use.set(ino);
+            state.types[ino as usize] = inode.file_type();
+            self.mark_xattr_block(state, ino, inode.file_acl());
+
+            // Device numbers and fast symlink targets are stored in place of the block pointers
+            if !matches!(
+                inode.file_type(),
+                FileType::RegularFile | FileType::Directory | FileType::Symlink
+            ) || inode.is_fast_symlink(self.block_size())
+            {
+                continue;
+            }
+
+            let blocks = inode.block();
+            for &block in &blocks[..Inode::DIRECT_BLOCKS] {
+                self.mark_tree(state, ino, block, 0)?;
+            }
+            for (depth, slot) in [Inode::IND_BLOCK, Inode::DIND_BLOCK, Inode::TIND_BLOCK]
+                .into_iter()
+                .enumerate()
+            {
+                self.mark_tree(state, ino, blocks[slot], depth as u32 + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Marks attribute block `block` of inode `ino` as used.
+    fn mark_xattr_block(&self, state: &mut State, ino: u32, block: u32) {
+        if block == 0 {
+            return;
+        }
+
+        if block < self.first_data_block() || block >= self.blocks_count() {
+            state
+                .report
+                .problems
+                .push(Problem::BadBlockPointer { ino, block });
+            return;
+        }
+
+        let shared = state.xattr_blocks.set(block);
+        if state.blocks.set(block) && !shared {
+            state
+                .report
+                .problems
+                .push(Problem::DuplicateBlock { ino, block });
+        }
+    }
+
+    /// Marks `block` as used by inode `ino`, together with the blocks it points to if it's an
+    /// indirect block with `depth` levels below it.
+    fn mark_tree(
+        &self,
+        state: &mut State,
+        ino: u32,
+        block: u32,
+        depth: u32,
+    ) -> Result<(), Error<IO::Error>> {
+        if block == 0 {
+            return Ok(());
+        }
+
+        if block < self.first_data_block() || block >= self.blocks_count() {
+            state
+                .report
+                .problems
+                .push(Problem::BadBlockPointer { ino, block });
+            return Ok(());
+        }
+
+        // The resize inode owns the reserved GDT blocks, which are also metadata
+        if state.blocks.set(block) && ino != RESIZE_INO {
+            state
+                .report
+                .problems
+                .push(Problem::DuplicateBlock { ino, block });
+            return Ok(());
+        }
+
+        if depth > 0 {
+            let mut buf = vec![0; self.block_size() as usize];
+            self.read_block(block, &mut buf)?;
+            for ptr in buf.as_chunks::<4>().0 {
+                self.mark_tree(state, ino, u32::from_le_bytes(*ptr), depth - 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Walks the directory tree from the root, validating the records and counting the
+    /// entries pointing to each inode.
+    fn check_dirs(&self, state: &mut State) -> Result<(), Error<IO::Error>> {
+        let block_size = self.block_size() as u64;
+        let mut buf = vec![0; block_size as usize];
+
+        // Directories to visit, with their parent
+        let mut queue = VecDeque::from([(Inode::ROOT_INO, Inode::ROOT_INO)]);
+        state.reached.set(Inode::ROOT_INO);
+
+        while let Some((dir, parent)) = queue.pop_front() {
+            let inode = self.inode(dir)?;
+            let mut dots_ok = true;
+            let mut index = 0;
+
+            for lblock in 0..inode.size().div_ceil(block_size) {
+                let block = match self.map_block(&inode, lblock) {
+                    Ok(0) | Err(Error::CorruptedFileSystem) => continue,
+                    Ok(block) => block,
+                    Err(e) => return Err(e),
+                };
+                self.read_block(block, &mut buf)?;
+
+                let mut offset = 0;
+                while offset < block_size as usize {
+                    let pos = lblock * block_size + offset as u64;
+                    if offset + DirEntry::HEADER_SIZE > block_size as usize {
+                        state
+                            .report
+                            .problems
+                            .push(Problem::BadRecordLength { dir, offset: pos });
+                        break;
+                    }
+
+                    let ino = u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap());
+                    let rec_len =
+                        u16::from_le_bytes(buf[offset + 4..offset + 6].try_into().unwrap())
+                            as usize;
+                    let name_len = buf[offset + 6] as usize;
+
+                    if rec_len < DirEntry::record_size(name_len)
+                        || rec_len % 4 != 0
+                        || offset + rec_len > block_size as usize
+                    {
+                        state
+                            .report
+                            .problems
+                            .push(Problem::BadRecordLength { dir, offset: pos });
+                        break;
+                    }
+
+                    let name = &buf[offset + DirEntry::HEADER_SIZE..][..name_len];
+                    offset += rec_len;
+
+                    if ino == 0 {
+                        continue;
+                    }
+
+                    // The first two entries are always `.` and `..`
+                    let expected = match index {
+                        0 => Some((&b"."[..], dir)),
+                        1 => Some((&b".."[..], parent)),
+                        _ => None,
+                    };
+                    index += 1;
+                    if let Some((dot_name, dot_ino)) = expected {
+                        dots_ok &= name == dot_name && ino == dot_ino;
+                    }
+
+                    if ino > self.inodes_count() || !state.in_use.get(ino) {
+                        state.report.problems.push(Problem::BadEntryInode {
+                            dir,
+                            offset: pos,
+                            ino,
+                        });
+                        continue;
+                    }
+
+                    state.refs[ino as usize] = state.refs[ino as usize].saturating_add(1);
+
+                    if name != b"."
+                        && name != b".."
+                        && state.types[ino as usize] == FileType::Directory
+                        && !state.reached.set(ino)
+                    {
+                        queue.push_back((ino, dir));
+                    }
+                }
+            }
+
+            if !dots_ok || index < 2 {
+                state.report.problems.push(Problem::BadDotEntry { dir });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compares the link count of each inode with the number of entries pointing to it.
+    fn check_links(&self, state: &mut State, repair: bool) -> Result<(), Error<IO::Error>> {
+        for ino in 1..=self.inodes_count() {
+            if !state.in_use.get(ino) {
+                continue;
+            }
+
+            let expected = state.refs[ino as usize];
+            if expected == 0 {
+                if ino >= self.first_ino() {
+                    state.report.problems.push(Problem::OrphanedInode { ino });
+                }
+                continue;
+            }
+
+            let mut inode = self.inode(ino)?;
+            if inode.links_count() != expected {
+                state.report.problems.push(Problem::LinkCount {
+                    ino,
+                    found: inode.links_count(),
+                    expected,
+                });
+
+                if repair {
+                    inode.set_links_count(expected);
+                    self.write_inode(ino, &inode)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compares the inode bitmaps with the inodes in use.
+    fn check_inode_bitmaps(&self, state: &mut State, repair: bool) -> Result<(), Error<IO::Error>> {
+        let inodes_per_group = self.inodes_per_group();
+        let mut buf = vec![0; self.block_size() as usize];
+
+        for group in 0..self.group_count() {
+            let bitmap = self.groups.borrow()[group as usize].inode_bitmap;
+            self.read_block(bitmap, &mut buf)?;
+
+            let mut dirty = false;
+            for bit in 0..inodes_per_group {
+                let ino = group * inodes_per_group + bit + 1;
+                let in_use = state.in_use.get(ino);
+                if bitmap_bit(&buf, bit) != in_use {
+                    state
+                        .report
+                        .problems
+                        .push(Problem::InodeBitmap { ino, in_use });
+                    set_bitmap_bit(&mut buf, bit, in_use);
+                    dirty = true;
+                }
+            }
+
+            if repair && dirty {
+                self.write_block(bitmap, &buf)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compares the block bitmaps with the blocks in use.
+    fn check_block_bitmaps(&self, state: &mut State, repair: bool) -> Result<(), Error<IO::Error>> {
+        let mut buf = vec![0; self.block_size() as usize];
+
+        for group in 0..self.group_count() {
+            let bitmap = self.groups.borrow()[group as usize].block_bitmap;
+            self.read_block(bitmap, &mut buf)?;
+
+            let first = self.first_data_block() + group * self.blocks_per_group();
+            let mut dirty = false;
+            for bit in 0..self.blocks_in_group(group) {
+                let block = first + bit;
+                let in_use = state.blocks.get(block);
+                if bitmap_bit(&buf, bit) != in_use {
+                    state
+                        .report
+                        .problems
+                        .push(Problem::BlockBitmap { block, in_use });
+                    set_bitmap_bit(&mut buf, bit, in_use);
+                    dirty = true;
+                }
+            }
+
+            if repair && dirty {
+                self.write_block(bitmap, &buf)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compares the counters in the group descriptors and in the superblock with the actual
+    /// usage.
+    fn check_counts(&self, state: &mut State, repair: bool) -> Result<(), Error<IO::Error>> {
+        let inodes_per_group = self.inodes_per_group();
+        let (mut free_blocks, mut free_inodes) = (0, 0);
+
+        for group in 0..self.group_count() {
+            let first_block = self.first_data_block() + group * self.blocks_per_group();
+            let group_blocks = self.blocks_in_group(group);
+            let group_free_blocks = (first_block..first_block + group_blocks)
+                .filter(|&b| !state.blocks.get(b))
+                .count() as u32;
+
+            let inodes = group * inodes_per_group + 1..=(group + 1) * inodes_per_group;
+            let group_free_inodes = inodes.clone().filter(|&i| !state.in_use.get(i)).count() as u32;
+            let group_dirs = inodes
+                .filter(|&i| state.in_use.get(i) && state.types[i as usize] == FileType::Directory)
+                .count() as u32;
+
+            free_blocks += group_free_blocks;
+            free_inodes += group_free_inodes;
+
+            let gd = self.groups.borrow()[group as usize];
+            let mut problems = Vec::new();
+            if gd.free_blocks_count as u32 != group_free_blocks {
+                problems.push(Problem::GroupFreeBlocks {
+                    group,
+                    found: gd.free_blocks_count as u32,
+                    expected: group_free_blocks,
+                });
+            }
+            if gd.free_inodes_count as u32 != group_free_inodes {
+                problems.push(Problem::GroupFreeInodes {
+                    group,
+                    found: gd.free_inodes_count as u32,
+                    expected: group_free_inodes,
+                });
+            }
+            if gd.used_dirs_count as u32 != group_dirs {
+                problems.push(Problem::GroupUsedDirs {
+                    group,
+                    found: gd.used_dirs_count as u32,
+                    expected: group_dirs,
+                });
+            }
+
+            if repair && !problems.is_empty() {
+                {
+                    let mut groups = self.groups.borrow_mut();
+                    let gd = &mut groups[group as usize];
+                    gd.free_blocks_count = group_free_blocks as u16;
+                    gd.free_inodes_count = group_free_inodes as u16;
+                    gd.used_dirs_count = group_dirs as u16;
+                }
+                self.write_group_desc(group)?;
+            }
+            state.report.problems.append(&mut problems);
+        }
+
+        let (found_blocks, found_inodes) = (self.free_blocks_count(), self.free_inodes_count());
+        if found_blocks != free_blocks {
+            state.report.problems.push(Problem::FreeBlocks {
+                found: found_blocks,
+                expected: free_blocks,
+            });
+        }
+        if found_inodes != free_inodes {
+            state.report.problems.push(Problem::FreeInodes {
+                found: found_inodes,
+                expected: free_inodes,
+            });
+        }
+
+        if repair && (found_blocks != free_blocks || found_inodes != free_inodes) {
+            {
+                let mut sb = self.sb.borrow_mut();
+                sb.free_blocks_count = free_blocks;
+                sb.free_inodes_count = free_inodes;
+            }
+            self.write_superblock()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn bitmap_bit(buf: &[u8], bit: u32) -> bool {
+    buf[bit as usize / 8] & (1 << (bit % 8)) != 0
+}
+
+fn set_bitmap_bit(buf: &mut [u8], bit: u32, value: bool) {
+    if value {
+        buf[bit as usize / 8] |= 1 << (bit % 8);
+    } else {
+        buf[bit as usize / 8] &= !(1 << (bit % 8));
+    }
+}
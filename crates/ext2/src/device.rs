@@ -0,0 +1,256 @@
+use alloc::{vec, vec::Vec};
+use core::{mem::ManuallyDrop, ptr};
+
+use crate::{
+    error::IoError,
+    fs::IntoStorage,
+    io::{IoBase, Read, Seek, SeekFrom, Write},
+};
+
+/// A storage device which is read and written in whole sectors, eg. a disk driver.
+///
+/// Use [`BlockDeviceIo`] to mount a file system on top of it.
+pub trait BlockDevice {
+    type Error: IoError;
+
+    /// Returns the size of a sector in bytes.
+    fn sector_size(&self) -> usize;
+
+    /// Returns the capacity of the device in sectors.
+    fn sector_count(&self) -> u64;
+
+    /// Reads `buf.len() / sector_size()` consecutive sectors, starting from sector `lba`.
+    ///
+    /// The length of `buf` is always a multiple of the sector size.
+    fn read_sectors(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Writes `buf.len() / sector_size()` consecutive sectors, starting from sector `lba`.
+    ///
+    /// The length of `buf` is always a multiple of the sector size.
+    fn write_sectors(&mut self, lba: u64, buf: &[u8]) -> Result<(), Self::Error>;
+
+    /// Waits until all the sectors written so far have reached persistent storage.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Number of lines kept in the cache of a [`BlockDeviceIo`].
+const CACHE_LINES: usize = 8;
+
+/// Size of a cache line, unless sectors are larger than this.
+const CACHE_LINE_SIZE: usize = 4096;
+
+/// A cached run of consecutive sectors.
+struct CacheLine {
+    index: u64,
+    data: Vec<u8>,
+    dirty: bool,
+    last_used: u64,
+}
+
+/// Byte-oriented access to a [`BlockDevice`], through a small write-back cache.
+///
+/// The device is split into lines of a few sectors. Partial reads and writes go through the
+/// cache, while transfers covering whole lines which are not cached go straight to the device.
+///
+/// Modified lines are only written to the device when they are evicted or when the adapter is
+/// flushed. Dropping the adapter writes them back as well, but errors are then lost, so call
+/// [`Write::flush`] beforehand to handle them.
+pub struct BlockDeviceIo<D: BlockDevice> {
+    dev: D,
+    pos: u64,
+    line_size: usize,
+    lines: Vec<CacheLine>,
+    clock: u64,
+}
+
+impl<D: BlockDevice> BlockDeviceIo<D> {
+    /// Creates an adapter for `dev`, positioned at its beginning.
+    pub fn new(dev: D) -> Self {
+        let line_size = CACHE_LINE_SIZE.next_multiple_of(dev.sector_size());
+
+        Self {
+            dev,
+            pos: 0,
+            line_size,
+            lines: Vec::with_capacity(CACHE_LINES),
+            clock: 0,
+        }
+    }
+
+    /// Returns the capacity of the device in bytes.
+    pub fn len(&self) -> u64 {
+        self.dev.sector_count() * self.dev.sector_size() as u64
+    }
+
+    /// Returns `true` if the device has no sectors.
+    pub fn is_empty(&self) -> bool {
+        self.dev.sector_count() == 0
+    }
+
+    /// Flushes the cache and returns the underlying device.
+    pub fn into_inner(mut self) -> Result<D, D::Error> {
+        self.flush()?;
+
+        // The cache is clean, so skip the write-back done when dropping the adapter
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: the fields are moved out or dropped exactly once, and `this` is never used again
+        unsafe {
+            ptr::drop_in_place(&mut this.lines);
+            Ok(ptr::read(&this.dev))
+        }
+    }
+
+    /// Returns the number of bytes in line `index`, which is smaller for the last line.
+    fn line_len(&self, index: u64) -> usize {
+        let start = index * self.line_size as u64;
+        (self.len() - start).min(self.line_size as u64) as usize
+    }
+
+    /// Returns the first sector of line `index`.
+    fn line_lba(&self, index: u64) -> u64 {
+        index * (self.line_size / self.dev.sector_size()) as u64
+    }
+
+    /// Returns the slot of the cache holding line `index`, if any.
+    fn lookup(&mut self, index: u64) -> Option<usize> {
+        let slot = self.lines.iter().position(|l| l.index == index)?;
+        self.clock += 1;
+        self.lines[slot].last_used = self.clock;
+        Some(slot)
+    }
+
+    /// Brings line `index` into the cache, evicting the least recently used line if needed, and
+    /// returns its slot.
+    fn load(&mut self, index: u64) -> Result<usize, D::Error> {
+        if let Some(slot) = self.lookup(index) {
+            return Ok(slot);
+        }
+
+        let len = self.line_len(index);
+        let mut data = vec![0; len];
+        self.dev.read_sectors(self.line_lba(index), &mut data)?;
+
+        self.clock += 1;
+        let line = CacheLine {
+            index,
+            data,
+            dirty: false,
+            last_used: self.clock,
+        };
+
+        if self.lines.len() < CACHE_LINES {
+            self.lines.push(line);
+            return Ok(self.lines.len() - 1);
+        }
+
+        let (slot, _) = self
+            .lines
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, l)| l.last_used)
+            .unwrap();
+        self.write_back(slot)?;
+        self.lines[slot] = line;
+
+        Ok(slot)
+    }
+
+    /// Writes the line in `slot` to the device if it was modified.
+    fn write_back(&mut self, slot: usize) -> Result<(), D::Error> {
+        if self.lines[slot].dirty {
+            let lba = self.line_lba(self.lines[slot].index);
+            self.dev.write_sectors(lba, &self.lines[slot].data)?;
+            self.lines[slot].dirty = false;
+        }
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> IoBase for BlockDeviceIo<D> {
+    type Error = D::Error;
+}
+
+impl<D: BlockDevice> Read for BlockDeviceIo<D> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.pos >= self.len() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Read at most up to the end of the current line
+        let index = self.pos / self.line_size as u64;
+        let offset = (self.pos % self.line_size as u64) as usize;
+        let line_len = self.line_len(index);
+        let n = buf.len().min(line_len - offset);
+
+        if n == line_len && self.lookup(index).is_none() {
+            self.dev.read_sectors(self.line_lba(index), &mut buf[..n])?;
+        } else {
+            let slot = self.load(index)?;
+            buf[..n].copy_from_slice(&self.lines[slot].data[offset..offset + n]);
+        }
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<D: BlockDevice> Write for BlockDeviceIo<D> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.pos >= self.len() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Write at most up to the end of the current line
+        let index = self.pos / self.line_size as u64;
+        let offset = (self.pos % self.line_size as u64) as usize;
+        let line_len = self.line_len(index);
+        let n = buf.len().min(line_len - offset);
+
+        if n == line_len && self.lookup(index).is_none() {
+            self.dev.write_sectors(self.line_lba(index), &buf[..n])?;
+        } else {
+            let slot = self.load(index)?;
+            let line = &mut self.lines[slot];
+            line.data[offset..offset + n].copy_from_slice(&buf[..n]);
+            line.dirty = true;
+        }
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        for slot in 0..self.lines.len() {
+            self.write_back(slot)?;
+        }
+        self.dev.flush()
+    }
+}
+
+impl<D: BlockDevice> Drop for BlockDeviceIo<D> {
+    fn drop(&mut self) {
+        // Best effort, as there is no way to report an error from here
+        let _ = self.flush();
+    }
+}
+
+impl<D: BlockDevice> Seek for BlockDeviceIo<D> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let pos = match pos {
+            SeekFrom::Start(off) => Some(off),
+            SeekFrom::End(off) => self.len().checked_add_signed(off),
+            SeekFrom::Current(off) => self.pos.checked_add_signed(off),
+        };
+
+        self.pos = pos.ok_or_else(D::Error::new_invalid_input_error)?;
+        Ok(self.pos)
+    }
+}
+
+impl<D: BlockDevice> IntoStorage<BlockDeviceIo<D>> for D {
+    fn into_storage(self) -> BlockDeviceIo<D> {
+        BlockDeviceIo::new(self)
+    }
+}
@@ -0,0 +1,498 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    blocks::SuperBlock,
+    error::Error,
+    file::File,
+    fs::FileSystem,
+    inode::{FileType, Inode},
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+/// Maximum length of a file name.
+pub const MAX_NAME_LEN: usize = 255;
+
+/// An entry in a directory.
+#[derive(Clone)]
+pub struct DirEntry {
+    ino: u32,
+    file_type: u8,
+    name: [u8; MAX_NAME_LEN],
+    name_len: u8,
+}
+
+impl DirEntry {
+    /// Size of the fixed part of a directory entry, ie. without the name.
+    pub(crate) const HEADER_SIZE: usize = 8;
+
+    /// Returns the inode number this entry points to.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// Returns the raw name of this entry.
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.name[..self.name_len as usize]
+    }
+
+    /// Returns the name of this entry, or `None` if it is not valid UTF-8.
+    pub fn name(&self) -> Option<&str> {
+        core::str::from_utf8(self.name_bytes()).ok()
+    }
+
+    /// Returns the number of bytes needed to store an entry with a name of `name_len` bytes.
+    pub(crate) fn record_size(name_len: usize) -> usize {
+        (Self::HEADER_SIZE + name_len).next_multiple_of(4)
+    }
+
+    /// Returns the type of the file this entry points to.
+    ///
+    /// File systems without the `filetype` feature don't store this information in directory
+    /// entries, in which case [`FileType::Unknown`] is returned and the inode must be read instead.
+    pub fn file_type(&self) -> FileType {
+        match self.file_type {
+            1 => FileType::RegularFile,
+            2 => FileType::Directory,
+            3 => FileType::CharDevice,
+            4 => FileType::BlockDevice,
+            5 => FileType::Fifo,
+            6 => FileType::Socket,
+            7 => FileType::Symlink,
+            _ => FileType::Unknown,
+        }
+    }
+}
+
+impl core::fmt::Debug for DirEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DirEntry")
+            .field("ino", &self.ino)
+            .field("file_type", &self.file_type())
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// A directory.
+pub struct Dir<'a, IO: Read + Write + Seek> {
+    fs: &'a FileSystem<IO>,
+    ino: u32,
+    inode: Inode,
+}
+
+impl<'a, IO: Read + Write + Seek> Dir<'a, IO> {
+    pub(crate) fn new(
+        fs: &'a FileSystem<IO>,
+        ino: u32,
+        inode: Inode,
+    ) -> Result<Self, Error<IO::Error>> {
+        if inode.file_type() != FileType::Directory {
+            return Err(Error::NotADirectory);
+        }
+
+        Ok(Self { fs, ino, inode })
+    }
+
+    /// Returns the inode number of this directory.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// Returns an iterator over the entries of this directory, including `.` and `..`.
+    pub fn iter(&self) -> DirIter<'a, IO> {
+        DirIter {
+            file: File::new(self.fs, self.ino, self.inode),
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Looks up an entry of this directory by name.
+    ///
+    /// Indexed directories are searched through their hash tree, other directories linearly.
+    pub fn find(&self, name: &[u8]) -> Result<Option<DirEntry>, Error<IO::Error>> {
+        let loc = self.fs.locate_entry(&self.inode, name)?;
+        Ok(loc.map(|loc| loc.entry(self.fs.has_filetype())))
+    }
+}
+
+/// Iterator over the entries of a directory.
+pub struct DirIter<'a, IO: Read + Write + Seek> {
+    file: File<'a, IO>,
+    offset: u64,
+    done: bool,
+}
+
+impl<IO: Read + Write + Seek> DirIter<'_, IO> {
+    fn read_entry(&mut self) -> Result<Option<DirEntry>, Error<IO::Error>> {
+        let block_size = self.file.fs().block_size() as u64;
+
+        while self.offset < self.file.len() {
+            let mut hdr = [0; DirEntry::HEADER_SIZE];
+            self.file.seek(SeekFrom::Start(self.offset))?;
+            self.file.read_exact(&mut hdr)?;
+
+            let ino = u32::from_le_bytes(hdr[0..4].try_into().unwrap());
+            let rec_len = u16::from_le_bytes(hdr[4..6].try_into().unwrap()) as u64;
+            let name_len = hdr[6];
+            let file_type = if self.file.fs().has_filetype() {
+                hdr[7]
+            } else {
+                0
+            };
+
+            // Records are 4-byte aligned, must hold their name and never cross a block boundary
+            let in_block = self.offset % block_size;
+            if rec_len < (DirEntry::HEADER_SIZE + name_len as usize) as u64
+                || rec_len % 4 != 0
+                || in_block + rec_len > block_size
+            {
+                return Err(Error::CorruptedFileSystem);
+            }
+
+            self.offset += rec_len;
+
+            // Unused entry
+            if ino == 0 {
+                continue;
+            }
+
+            let mut entry = DirEntry {
+                ino,
+                file_type,
+                name: [0; MAX_NAME_LEN],
+                name_len,
+            };
+            self.file.read_exact(&mut entry.name[..name_len as usize])?;
+
+            return Ok(Some(entry));
+        }
+
+        Ok(None)
+    }
+}
+
+impl<IO: Read + Write + Seek> Iterator for DirIter<'_, IO> {
+    type Item = Result<DirEntry, Error<IO::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let res = self.read_entry().transpose();
+        if !matches!(res, Some(Ok(_))) {
+            self.done = true;
+        }
+        res
+    }
+}
+
+/// Position of an entry inside a directory block.
+struct EntryLocation {
+    block: u32,
+    buf: Vec<u8>,
+    offset: usize,
+    prev: Option<usize>,
+}
+
+impl EntryLocation {
+    /// Returns the entry of the record at this location.
+    fn entry(&self, has_filetype: bool) -> DirEntry {
+        let rec = &self.buf[self.offset..];
+        let name_len = rec[6];
+
+        let mut entry = DirEntry {
+            ino: u32::from_le_bytes(rec[0..4].try_into().unwrap()),
+            file_type: if has_filetype { rec[7] } else { 0 },
+            name: [0; MAX_NAME_LEN],
+            name_len,
+        };
+        let name = &rec[DirEntry::HEADER_SIZE..DirEntry::HEADER_SIZE + name_len as usize];
+        entry.name[..name_len as usize].copy_from_slice(name);
+        entry
+    }
+}
+
+/// Header of a directory record.
+struct RecordHeader {
+    ino: u32,
+    rec_len: usize,
+    name_len: usize,
+}
+
+impl RecordHeader {
+    /// Parses and validates the record at `offset` in directory block `buf`.
+    fn parse<E>(buf: &[u8], offset: usize) -> Result<Self, Error<E>> {
+        let hdr = buf
+            .get(offset..offset + DirEntry::HEADER_SIZE)
+            .ok_or(Error::CorruptedFileSystem)?;
+
+        let rec = Self {
+            ino: u32::from_le_bytes(hdr[0..4].try_into().unwrap()),
+            rec_len: u16::from_le_bytes(hdr[4..6].try_into().unwrap()) as usize,
+            name_len: hdr[6] as usize,
+        };
+
+        if rec.rec_len < DirEntry::HEADER_SIZE + rec.name_len
+            || rec.rec_len % 4 != 0
+            || offset + rec.rec_len > buf.len()
+        {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        Ok(rec)
+    }
+
+    fn name<'b>(&self, buf: &'b [u8], offset: usize) -> &'b [u8] {
+        let start = offset + DirEntry::HEADER_SIZE;
+        &buf[start..start + self.name_len]
+    }
+}
+
+/// Writes a directory record at `offset` in directory block `buf`.
+pub(crate) fn write_record(
+    buf: &mut [u8],
+    offset: usize,
+    ino: u32,
+    rec_len: usize,
+    name: &[u8],
+    ft: u8,
+) {
+    let rec = &mut buf[offset..offset + rec_len];
+    rec[0..4].copy_from_slice(&ino.to_le_bytes());
+    rec[4..6].copy_from_slice(&(rec_len as u16).to_le_bytes());
+    rec[6] = name.len() as u8;
+    rec[7] = ft;
+    rec[DirEntry::HEADER_SIZE..DirEntry::HEADER_SIZE + name.len()].copy_from_slice(name);
+}
+
+/// Places a record for `name` in the first record of directory block `buf` with at least
+/// `needed` bytes of slack space, returning `false` if there is none.
+fn insert_record<E>(
+    buf: &mut [u8],
+    needed: usize,
+    name: &[u8],
+    ino: u32,
+    ft: u8,
+) -> Result<bool, Error<E>> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        let rec = RecordHeader::parse(buf, offset)?;
+        let used = if rec.ino == 0 {
+            0
+        } else {
+            DirEntry::record_size(rec.name_len)
+        };
+
+        if rec.rec_len - used >= needed {
+            // Shrink the existing record and use its slack space for the new one
+            if used != 0 {
+                buf[offset + 4..offset + 6].copy_from_slice(&(used as u16).to_le_bytes());
+            }
+            write_record(buf, offset + used, ino, rec.rec_len - used, name, ft);
+            return Ok(true);
+        }
+
+        offset += rec.rec_len;
+    }
+
+    Ok(false)
+}
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Adds an entry named `name` pointing to inode `ino` to directory `dir_ino`.
+    ///
+    /// The entry is placed in the first record with enough slack space, or in a new block
+    /// appended to the directory. `dir` is updated and written back to disk.
+    ///
+    /// In indexed directories the entry goes to the leaf covering the hash of its name. If that
+    /// leaf is full the index is dropped, as splitting leaves is not supported, and the directory
+    /// becomes a linear one.
+    pub(crate) fn add_entry(
+        &self,
+        dir_ino: u32,
+        dir: &mut Inode,
+        name: &[u8],
+        ino: u32,
+        file_type: FileType,
+    ) -> Result<(), Error<IO::Error>> {
+        let block_size = self.block_size() as usize;
+        let needed = DirEntry::record_size(name.len());
+        let ft = self.dir_entry_file_type(file_type);
+
+        let mut buf = vec![0; block_size];
+        let block_count = dir.size() / block_size as u64;
+
+        if dir.flags() & Inode::INDEX_FL != 0 {
+            if let Some(leaves) = self.dx_leaves(dir, name)? {
+                let block = self.read_dir_block(dir, leaves[0], &mut buf)?;
+                if insert_record(&mut buf, needed, name, ino, ft)? {
+                    self.write_block(block, &buf)?;
+
+                    dir.touch(self.now());
+                    return self.write_inode(dir_ino, dir);
+                }
+            }
+
+            // Linear insertion would break the index, so drop it rather than splitting the leaf
+            dir.set_flags(dir.flags() & !Inode::INDEX_FL);
+        }
+
+        for index in 0..block_count {
+            let block = self.read_dir_block(dir, index, &mut buf)?;
+
+            if insert_record(&mut buf, needed, name, ino, ft)? {
+                self.write_block(block, &buf)?;
+
+                dir.touch(self.now());
+                return self.write_inode(dir_ino, dir);
+            }
+        }
+
+        // No space left in the existing blocks
+        let block = self.bmap(dir_ino, dir, block_count, true)?;
+        buf.fill(0);
+        write_record(&mut buf, 0, ino, block_size, name, ft);
+        self.write_block(block, &buf)?;
+
+        dir.set_size((block_count + 1) * block_size as u64);
+        dir.touch(self.now());
+        self.write_inode(dir_ino, dir)
+    }
+
+    /// Removes the entry named `name` from directory `dir_ino`, returning the inode number it
+    /// pointed to.
+    ///
+    /// `dir` is updated and written back to disk.
+    pub(crate) fn remove_entry(
+        &self,
+        dir_ino: u32,
+        dir: &mut Inode,
+        name: &[u8],
+    ) -> Result<u32, Error<IO::Error>> {
+        let mut loc = self.locate_entry(dir, name)?.ok_or(Error::NotFound)?;
+        let rec = RecordHeader::parse(&loc.buf, loc.offset)?;
+
+        match loc.prev {
+            // Merge the record into the previous one
+            Some(prev) => {
+                let prev_len = RecordHeader::parse(&loc.buf, prev)?.rec_len + rec.rec_len;
+                loc.buf[prev + 4..prev + 6].copy_from_slice(&(prev_len as u16).to_le_bytes());
+            }
+            // The first record of a block can only be marked as unused
+            None => loc.buf[loc.offset..loc.offset + 4].fill(0),
+        }
+
+        self.write_block(loc.block, &loc.buf)?;
+
+        dir.touch(self.now());
+        self.write_inode(dir_ino, dir)?;
+
+        Ok(rec.ino)
+    }
+
+    /// Makes the entry named `name` in directory `dir` point to inode `ino`.
+    pub(crate) fn relink_entry(
+        &self,
+        dir: &Inode,
+        name: &[u8],
+        ino: u32,
+    ) -> Result<(), Error<IO::Error>> {
+        let mut loc = self.locate_entry(dir, name)?.ok_or(Error::NotFound)?;
+        loc.buf[loc.offset..loc.offset + 4].copy_from_slice(&ino.to_le_bytes());
+        self.write_block(loc.block, &loc.buf)
+    }
+
+    /// Returns `true` if directory `ino` only contains `.` and `..`.
+    pub(crate) fn is_dir_empty(&self, ino: u32) -> Result<bool, Error<IO::Error>> {
+        for entry in self.dir(ino)?.iter() {
+            let entry = entry?;
+            if entry.name_bytes() != b"." && entry.name_bytes() != b".." {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Initializes the first block of a new directory with the `.` and `..` entries.
+    pub(crate) fn init_dir_block(
+        &self,
+        block: u32,
+        ino: u32,
+        parent: u32,
+    ) -> Result<(), Error<IO::Error>> {
+        let block_size = self.block_size() as usize;
+        let ft = self.dir_entry_file_type(FileType::Directory);
+        let dot_len = DirEntry::record_size(1);
+
+        let mut buf = vec![0; block_size];
+        write_record(&mut buf, 0, ino, dot_len, b".", ft);
+        write_record(&mut buf, dot_len, parent, block_size - dot_len, b"..", ft);
+        self.write_block(block, &buf)
+    }
+
+    /// Returns the value of the `file_type` field of directory entries pointing to files of
+    /// type `file_type`.
+    fn dir_entry_file_type(&self, file_type: FileType) -> u8 {
+        // Without the `filetype` feature this byte is the upper half of `name_len`
+        if !self.has_filetype() {
+            return 0;
+        }
+
+        match file_type {
+            FileType::RegularFile => 1,
+            FileType::Directory => 2,
+            FileType::CharDevice => 3,
+            FileType::BlockDevice => 4,
+            FileType::Fifo => 5,
+            FileType::Socket => 6,
+            FileType::Symlink => 7,
+            FileType::Unknown => 0,
+        }
+    }
+
+    /// Returns `true` if directory entries record the type of the file they point to.
+    pub(crate) fn has_filetype(&self) -> bool {
+        self.sb
+            .borrow()
+            .has_incompat(SuperBlock::FEATURE_INCOMPAT_FILETYPE)
+    }
+
+    /// Finds the record of the entry named `name` in directory `dir`.
+    fn locate_entry(
+        &self,
+        dir: &Inode,
+        name: &[u8],
+    ) -> Result<Option<EntryLocation>, Error<IO::Error>> {
+        let block_size = self.block_size() as usize;
+        let mut buf = vec![0; block_size];
+
+        let blocks = match self.dx_leaves(dir, name)? {
+            Some(leaves) => leaves,
+            None => (0..dir.size() / block_size as u64).collect(),
+        };
+
+        for index in blocks {
+            let block = self.read_dir_block(dir, index, &mut buf)?;
+
+            let mut offset = 0;
+            let mut prev = None;
+            while offset < block_size {
+                let rec = RecordHeader::parse(&buf, offset)?;
+                if rec.ino != 0 && rec.name(&buf, offset) == name {
+                    return Ok(Some(EntryLocation {
+                        block,
+                        buf,
+                        offset,
+                        prev,
+                    }));
+                }
+                prev = Some(offset);
+                offset += rec.rec_len;
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -1,9 +1,42 @@
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Error<T> {
+    /// An error returned by the underlying storage.
     Io(T),
+    /// The storage ended before all the requested data could be read.
     UnexpectedEof,
+    /// The storage accepted no data while writing.
     WriteZero,
+    /// The superblock does not contain the ext2 magic number.
+    InvalidMagic,
+    /// The file system uses a revision level which is not supported.
+    UnsupportedRevision(u32),
+    /// The file system uses incompatible features which are not supported, given as a mask.
+    UnsupportedFeature(u32),
+    /// The journal uses incompatible features which are not supported, given as a mask.
+    UnsupportedJournalFeature(u32),
+    /// The on-disk structures are inconsistent.
+    CorruptedFileSystem,
+    /// An invalid argument was provided, eg. a nonexistent inode number or a negative seek.
+    InvalidInput,
+    /// A path component does not exist.
+    NotFound,
+    /// A path component used as a directory is not a directory.
+    NotADirectory,
+    /// Too many symbolic links were encountered while resolving a path.
+    TooManySymlinks,
+    /// A file with the same name already exists.
+    AlreadyExists,
+    /// The directory to remove is not empty.
+    DirectoryIsNotEmpty,
+    /// The operation is not allowed on directories.
+    IsADirectory,
+    /// There are no free blocks or inodes left.
+    NotEnoughSpace,
+    /// The file would be larger than the maximum supported size.
+    FileTooLarge,
+    /// The file system was mounted read-only.
+    ReadOnlyFileSystem,
 }
 
 impl<T: IoError> From<T> for Error<T> {
@@ -19,6 +52,22 @@ impl From<Error<std::io::Error>> for std::io::Error {
             Error::Io(e) => e,
             Error::UnexpectedEof => Self::new(std::io::ErrorKind::UnexpectedEof, e),
             Error::WriteZero => Self::new(std::io::ErrorKind::WriteZero, e),
+            Error::InvalidMagic | Error::CorruptedFileSystem => {
+                Self::new(std::io::ErrorKind::InvalidData, e)
+            }
+            Error::UnsupportedRevision(_)
+            | Error::UnsupportedFeature(_)
+            | Error::UnsupportedJournalFeature(_) => Self::new(std::io::ErrorKind::Unsupported, e),
+            Error::InvalidInput => Self::new(std::io::ErrorKind::InvalidInput, e),
+            Error::NotFound => Self::new(std::io::ErrorKind::NotFound, e),
+            Error::NotADirectory => Self::new(std::io::ErrorKind::NotADirectory, e),
+            Error::TooManySymlinks => Self::other(e),
+            Error::AlreadyExists => Self::new(std::io::ErrorKind::AlreadyExists, e),
+            Error::DirectoryIsNotEmpty => Self::new(std::io::ErrorKind::DirectoryNotEmpty, e),
+            Error::IsADirectory => Self::new(std::io::ErrorKind::IsADirectory, e),
+            Error::NotEnoughSpace => Self::new(std::io::ErrorKind::StorageFull, e),
+            Error::FileTooLarge => Self::new(std::io::ErrorKind::FileTooLarge, e),
+            Error::ReadOnlyFileSystem => Self::new(std::io::ErrorKind::ReadOnlyFilesystem, e),
         }
     }
 }
@@ -29,6 +78,23 @@ impl<T: core::fmt::Display> core::fmt::Display for Error<T> {
             Error::Io(e) => write!(f, "IO error: {}", e),
             Error::UnexpectedEof => write!(f, "unexpected EOF"),
             Error::WriteZero => write!(f, "write zero"),
+            Error::InvalidMagic => write!(f, "invalid ext2 magic number"),
+            Error::UnsupportedRevision(rev) => write!(f, "unsupported revision level {}", rev),
+            Error::UnsupportedFeature(mask) => write!(f, "unsupported features {:#x}", mask),
+            Error::UnsupportedJournalFeature(mask) => {
+                write!(f, "unsupported journal features {:#x}", mask)
+            }
+            Error::CorruptedFileSystem => write!(f, "corrupted file system"),
+            Error::InvalidInput => write!(f, "invalid input"),
+            Error::NotFound => write!(f, "no such file or directory"),
+            Error::NotADirectory => write!(f, "not a directory"),
+            Error::TooManySymlinks => write!(f, "too many levels of symbolic links"),
+            Error::AlreadyExists => write!(f, "file already exists"),
+            Error::DirectoryIsNotEmpty => write!(f, "directory is not empty"),
+            Error::IsADirectory => write!(f, "is a directory"),
+            Error::NotEnoughSpace => write!(f, "not enough space"),
+            Error::FileTooLarge => write!(f, "file too large"),
+            Error::ReadOnlyFileSystem => write!(f, "read-only file system"),
         }
     }
 }
@@ -47,6 +113,7 @@ pub trait IoError: core::fmt::Debug {
     fn is_interrupted(&self) -> bool;
     fn new_unexpected_eof_error() -> Self;
     fn new_write_zero_error() -> Self;
+    fn new_invalid_input_error() -> Self;
 }
 
 impl<T: core::fmt::Debug + IoError> IoError for Error<T> {
@@ -64,6 +131,10 @@ impl<T: core::fmt::Debug + IoError> IoError for Error<T> {
     fn new_write_zero_error() -> Self {
         Self::WriteZero
     }
+
+    fn new_invalid_input_error() -> Self {
+        Self::InvalidInput
+    }
 }
 
 #[cfg(feature = "std")]
@@ -82,4 +153,8 @@ impl IoError for std::io::Error {
             "failed to write whole buffer",
         )
     }
+
+    fn new_invalid_input_error() -> Self {
+        Self::new(std::io::ErrorKind::InvalidInput, "invalid input")
+    }
 }
@@ -0,0 +1,150 @@
+use crate::{
+    error::Error,
+    fs::FileSystem,
+    inode::{FileType, Inode},
+    io::{IoBase, Read, Seek, SeekFrom, Write},
+};
+
+/// A handle to the contents of an inode.
+///
+/// Reads past the end of the file return 0 bytes, while reads from unallocated blocks (holes)
+/// return zeroes. Writes past the end of the file extend it, allocating blocks as needed.
+///
+/// The handle keeps its own copy of the inode, so changes made through other handles to the same
+/// file are not visible to it.
+pub struct File<'a, IO: Read + Write + Seek> {
+    fs: &'a FileSystem<IO>,
+    ino: u32,
+    inode: Inode,
+    offset: u64,
+}
+
+impl<'a, IO: Read + Write + Seek> File<'a, IO> {
+    pub(crate) fn new(fs: &'a FileSystem<IO>, ino: u32, inode: Inode) -> Self {
+        Self {
+            fs,
+            ino,
+            inode,
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn fs(&self) -> &'a FileSystem<IO> {
+        self.fs
+    }
+
+    /// Returns the inode number of this file.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// Returns the inode of this file.
+    pub fn inode(&self) -> &Inode {
+        &self.inode
+    }
+
+    /// Returns the size of this file in bytes.
+    pub fn len(&self) -> u64 {
+        self.inode.size()
+    }
+
+    /// Returns `true` if this file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Truncates or extends this file to `size` bytes.
+    ///
+    /// Extending a file doesn't allocate any block, and the new space reads as zeroes. The
+    /// current position is left unchanged.
+    pub fn set_len(&mut self, size: u64) -> Result<(), Error<IO::Error>> {
+        self.fs.check_writable()?;
+        self.fs.truncate(self.ino, &mut self.inode, size)
+    }
+}
+
+impl<IO: Read + Write + Seek> IoBase for File<'_, IO> {
+    type Error = Error<IO::Error>;
+}
+
+impl<IO: Read + Write + Seek> Read for File<'_, IO> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let size = self.inode.size();
+        if self.offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Read at most up to the end of the current block
+        let block_size = self.fs.block_size() as u64;
+        let index = self.offset / block_size;
+        let in_block = self.offset % block_size;
+        let n = (buf.len() as u64)
+            .min(block_size - in_block)
+            .min(size - self.offset) as usize;
+
+        match self.fs.map_block(&self.inode, index)? {
+            0 => buf[..n].fill(0),
+            block => self
+                .fs
+                .read_at(block as u64 * block_size + in_block, &mut buf[..n])?,
+        }
+
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<IO: Read + Write + Seek> Write for File<'_, IO> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.inode.file_type() == FileType::Directory {
+            return Err(Error::IsADirectory);
+        }
+        self.fs.check_writable()?;
+
+        let max_size = self.fs.max_file_size();
+        if buf.is_empty() {
+            return Ok(0);
+        } else if self.offset >= max_size {
+            return Err(Error::FileTooLarge);
+        }
+
+        // Write at most up to the end of the current block
+        let block_size = self.fs.block_size() as u64;
+        let index = self.offset / block_size;
+        let in_block = self.offset % block_size;
+        let n = (buf.len() as u64)
+            .min(block_size - in_block)
+            .min(max_size - self.offset) as usize;
+
+        let block = self.fs.bmap(self.ino, &mut self.inode, index, true)?;
+        self.fs
+            .write_at(block as u64 * block_size + in_block, &buf[..n])?;
+
+        self.offset += n as u64;
+        if self.offset > self.inode.size() {
+            self.inode.set_size(self.offset);
+        }
+
+        self.inode.touch(self.fs.now());
+        self.fs.write_inode(self.ino, &self.inode)?;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.fs.flush()
+    }
+}
+
+impl<IO: Read + Write + Seek> Seek for File<'_, IO> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let offset = match pos {
+            SeekFrom::Start(off) => Some(off),
+            SeekFrom::End(off) => self.inode.size().checked_add_signed(off),
+            SeekFrom::Current(off) => self.offset.checked_add_signed(off),
+        };
+
+        self.offset = offset.ok_or(Error::InvalidInput)?;
+        Ok(self.offset)
+    }
+}
@@ -0,0 +1,347 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    blocks::{BlockGroupDesc, OnDisk, SuperBlock},
+    dir::{DirEntry, write_record},
+    error::Error,
+    inode::{FileType, Inode},
+    io::{Read, Seek, SeekFrom, Write},
+    time::{DefaultTimeProvider, TimeProvider},
+};
+
+/// Inode number of the `lost+found` directory, the first non-reserved inode.
+const LOST_FOUND_INO: u32 = SuperBlock::GOOD_OLD_FIRST_INO;
+
+/// Size of `lost+found`, preallocated so that a checker can reconnect files without allocating.
+const LOST_FOUND_SIZE: u32 = 12 * 1024;
+
+/// Largest number of blocks or inodes in a group, so that group descriptor counters fit in 16 bits.
+const MAX_PER_GROUP: u32 = 65528;
+
+/// Groups smaller than their metadata plus this many blocks are left out of the file system.
+const MIN_GROUP_DATA_BLOCKS: u32 = 50;
+
+/// Directory entry file type of directories, with the `filetype` feature.
+const DIR_FILE_TYPE: u8 = 2;
+
+/// `state` of a cleanly unmounted file system.
+const STATE_VALID: u16 = 1;
+
+/// `errors` behavior: continue as if nothing happened.
+const ERRORS_CONTINUE: u16 = 1;
+
+/// Parameters of a new file system created by [`format`].
+///
+/// Fields left to `None` are chosen from the size of the device, like `mke2fs` does.
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions<'a> {
+    /// Size of a block in bytes: 1024, 2048, 4096, ... up to 64 KiB.
+    ///
+    /// Defaults to 1 KiB for devices smaller than 512 MiB and to 4 KiB otherwise.
+    pub block_size: Option<u32>,
+    /// Number of blocks of the file system, which defaults to the whole device.
+    pub blocks_count: Option<u32>,
+    /// Number of blocks in each group, a multiple of 8 up to `8 * block_size`, the default.
+    ///
+    /// Groups are limited to 65528 blocks and inodes, as their descriptors count them on 16 bits.
+    pub blocks_per_group: Option<u32>,
+    /// Number of inodes in each group, rounded up to fill whole inode table blocks.
+    ///
+    /// Defaults to one inode for every 8 KiB of storage.
+    pub inodes_per_group: Option<u32>,
+    /// Size of an inode on disk, a power of two from 128 to the block size.
+    pub inode_size: u16,
+    /// Percentage of the blocks reserved for the super-user.
+    pub reserved_percent: u8,
+    /// Volume name, at most 16 bytes long.
+    pub label: &'a str,
+    /// Volume id.
+    pub uuid: [u8; 16],
+    /// Only keep superblock and GDT backups in groups 0, 1 and powers of 3, 5 and 7.
+    pub sparse_super: bool,
+    /// Source of the creation time of the file system, the root directory and `lost+found`.
+    pub time_provider: &'static (dyn TimeProvider + Sync),
+}
+
+impl Default for FormatOptions<'_> {
+    fn default() -> Self {
+        Self {
+            block_size: None,
+            blocks_count: None,
+            blocks_per_group: None,
+            inodes_per_group: None,
+            inode_size: 256,
+            reserved_percent: 5,
+            label: "",
+            uuid: [0; 16],
+            sparse_super: true,
+            time_provider: &DefaultTimeProvider,
+        }
+    }
+}
+
+/// Creates an empty ext2 file system on `disk`, overwriting its previous contents.
+///
+/// The new file system uses the dynamic revision with the `filetype`, `large_file` and
+/// optionally `sparse_super` features. Its root directory only contains `lost+found`.
+///
+/// Returns [`Error::InvalidInput`] if the options are invalid or if the device is too small.
+pub fn format<IO: Read + Write + Seek>(
+    disk: &mut IO,
+    options: FormatOptions<'_>,
+) -> Result<(), Error<IO::Error>> {
+    let device_size = disk.seek(SeekFrom::End(0))?;
+    let now = options.time_provider.now();
+
+    let default_block_size = if device_size < 512 << 20 { 1024 } else { 4096 };
+    let block_size = options.block_size.unwrap_or(default_block_size);
+    if !block_size.is_power_of_two()
+        || !(1024..=1024 << SuperBlock::MAX_LOG_BLOCK_SIZE).contains(&block_size)
+    {
+        return Err(Error::InvalidInput);
+    }
+
+    let max_blocks = (device_size / block_size as u64).min(u32::MAX as u64) as u32;
+    let blocks_count = options.blocks_count.unwrap_or(max_blocks);
+    let max_per_group = (8 * block_size).min(MAX_PER_GROUP);
+    let blocks_per_group = options.blocks_per_group.unwrap_or(max_per_group);
+    let inode_size = options.inode_size as u32;
+    let label = options.label.as_bytes();
+
+    if blocks_count > max_blocks
+        || !blocks_per_group.is_multiple_of(8)
+        || !(256..=max_per_group).contains(&blocks_per_group)
+        || !inode_size.is_power_of_two()
+        || !(SuperBlock::GOOD_OLD_INODE_SIZE..=block_size).contains(&inode_size)
+        || options.reserved_percent > 50
+        || label.len() > 16
+    {
+        return Err(Error::InvalidInput);
+    }
+
+    let mut sb = SuperBlock::from_bytes(&[0; SuperBlock::SIZE]);
+    sb.blocks_count = blocks_count;
+    sb.first_data_block = (block_size == 1024) as u32;
+    sb.log_block_size = block_size.trailing_zeros() - 10;
+    sb.log_frag_size = sb.log_block_size;
+    sb.blocks_per_group = blocks_per_group;
+    sb.frags_per_group = blocks_per_group;
+    sb.magic = SuperBlock::MAGIC;
+    sb.state = STATE_VALID;
+    sb.errors = ERRORS_CONTINUE;
+    sb.max_mnt_count = u16::MAX;
+    sb.wtime = now;
+    sb.lastcheck = now;
+    sb.mkfs_time = now;
+    sb.rev_level = SuperBlock::DYNAMIC_REV;
+    sb.first_ino = SuperBlock::GOOD_OLD_FIRST_INO;
+    sb.inode_size = options.inode_size;
+    sb.feature_incompat = SuperBlock::FEATURE_INCOMPAT_FILETYPE;
+    sb.feature_ro_compat = SuperBlock::FEATURE_RO_COMPAT_LARGE_FILE;
+    if options.sparse_super {
+        sb.feature_ro_compat |= SuperBlock::FEATURE_RO_COMPAT_SPARSE_SUPER;
+    }
+    sb.uuid = options.uuid;
+    sb.volume_name[..label.len()].copy_from_slice(label);
+
+    if blocks_count <= sb.first_data_block {
+        return Err(Error::InvalidInput);
+    }
+
+    // Inodes are allocated in whole inode table blocks and whole bitmap bytes
+    let inodes_per_block = block_size / inode_size;
+    let inodes_per_group = options
+        .inodes_per_group
+        .unwrap_or_else(|| {
+            let inodes = (blocks_count as u64 * block_size as u64 / 8192) as u32;
+            inodes.div_ceil(sb.group_count())
+        })
+        .max(16)
+        .next_multiple_of(inodes_per_block.max(8));
+    if inodes_per_group > max_per_group {
+        return Err(Error::InvalidInput);
+    }
+    sb.inodes_per_group = inodes_per_group;
+    let inode_table_blocks = inodes_per_group / inodes_per_block;
+
+    // Drop the last group if it's too small to hold anything besides its own metadata
+    let overhead = |sb: &SuperBlock, group: u32| {
+        let super_blocks = if sb.group_has_super(group) {
+            1 + sb.gdt_blocks()
+        } else {
+            0
+        };
+        super_blocks + 2 + inode_table_blocks
+    };
+    let last = sb.group_count() - 1;
+    let last_blocks = blocks_count - sb.first_data_block - last * blocks_per_group;
+    if last_blocks < overhead(&sb, last) + MIN_GROUP_DATA_BLOCKS {
+        if last == 0 {
+            return Err(Error::InvalidInput);
+        }
+        sb.blocks_count -= last_blocks;
+    }
+
+    let group_count = sb.group_count();
+    sb.inodes_count = group_count * inodes_per_group;
+    sb.r_blocks_count = (sb.blocks_count as u64 * options.reserved_percent as u64 / 100) as u32;
+
+    let (first_data_block, blocks_count) = (sb.first_data_block, sb.blocks_count);
+    let group_start = |group: u32| first_data_block + group * blocks_per_group;
+    let group_blocks = |group: u32| (blocks_count - group_start(group)).min(blocks_per_group);
+
+    // The root directory and `lost+found` take the first data blocks of group 0
+    let lost_found_blocks = (LOST_FOUND_SIZE / block_size).clamp(1, Inode::DIRECT_BLOCKS as u32);
+
+    let mut groups = Vec::with_capacity(group_count as usize);
+    let mut used_blocks = Vec::with_capacity(group_count as usize);
+    for group in 0..group_count {
+        let data_blocks = if group == 0 { 1 + lost_found_blocks } else { 0 };
+        let used = overhead(&sb, group) + data_blocks;
+        let free_blocks = group_blocks(group)
+            .checked_sub(used)
+            .ok_or(Error::InvalidInput)?;
+        used_blocks.push(used);
+
+        let mut gd = BlockGroupDesc::from_bytes(&[0; BlockGroupDesc::SIZE]);
+        gd.block_bitmap = group_start(group) + overhead(&sb, group) - 2 - inode_table_blocks;
+        gd.inode_bitmap = gd.block_bitmap + 1;
+        gd.inode_table = gd.inode_bitmap + 1;
+        gd.free_blocks_count = free_blocks as u16;
+        gd.free_inodes_count = inodes_per_group as u16;
+        groups.push(gd);
+    }
+
+    groups[0].free_inodes_count -= LOST_FOUND_INO as u16;
+    groups[0].used_dirs_count = 2;
+
+    sb.free_blocks_count = groups.iter().map(|gd| gd.free_blocks_count as u32).sum();
+    sb.free_inodes_count = groups.iter().map(|gd| gd.free_inodes_count as u32).sum();
+
+    let block_size = block_size as usize;
+    let write_block = |disk: &mut IO, block: u32, buf: &[u8]| -> Result<(), Error<IO::Error>> {
+        disk.seek(SeekFrom::Start(block as u64 * block_size as u64))?;
+        disk.write_all(buf)?;
+        Ok(())
+    };
+
+    // Bitmaps, with the bits past the end of the group set as padding, and empty inode tables
+    let zeroes = vec![0; block_size];
+    for (group, (gd, &used_blocks)) in (0..).zip(groups.iter().zip(&used_blocks)) {
+        let mut block_bitmap = vec![0; block_size];
+        for bit in (0..used_blocks).chain(group_blocks(group)..8 * block_size as u32) {
+            block_bitmap[bit as usize / 8] |= 1 << (bit % 8);
+        }
+        write_block(disk, gd.block_bitmap, &block_bitmap)?;
+
+        let mut inode_bitmap = vec![0; block_size];
+        let used_inodes = if group == 0 { LOST_FOUND_INO } else { 0 };
+        for bit in (0..used_inodes).chain(inodes_per_group..8 * block_size as u32) {
+            inode_bitmap[bit as usize / 8] |= 1 << (bit % 8);
+        }
+        write_block(disk, gd.inode_bitmap, &inode_bitmap)?;
+
+        for block in gd.inode_table..gd.inode_table + inode_table_blocks {
+            write_block(disk, block, &zeroes)?;
+        }
+    }
+
+    // Root directory, holding `lost+found`
+    let root_block = groups[0].inode_table + inode_table_blocks;
+    let dot_len = DirEntry::record_size(1);
+    let dotdot_len = DirEntry::record_size(2);
+
+    let mut buf = vec![0; block_size];
+    write_record(&mut buf, 0, Inode::ROOT_INO, dot_len, b".", DIR_FILE_TYPE);
+    write_record(
+        &mut buf,
+        dot_len,
+        Inode::ROOT_INO,
+        dotdot_len,
+        b"..",
+        DIR_FILE_TYPE,
+    );
+    write_record(
+        &mut buf,
+        dot_len + dotdot_len,
+        LOST_FOUND_INO,
+        block_size - dot_len - dotdot_len,
+        b"lost+found",
+        DIR_FILE_TYPE,
+    );
+    write_block(disk, root_block, &buf)?;
+
+    // `lost+found`, with empty records filling its additional blocks
+    let mut buf = vec![0; block_size];
+    write_record(&mut buf, 0, LOST_FOUND_INO, dot_len, b".", DIR_FILE_TYPE);
+    write_record(
+        &mut buf,
+        dot_len,
+        Inode::ROOT_INO,
+        block_size - dot_len,
+        b"..",
+        DIR_FILE_TYPE,
+    );
+    write_block(disk, root_block + 1, &buf)?;
+
+    let mut buf = vec![0; block_size];
+    write_record(&mut buf, 0, 0, block_size, b"", 0);
+    for block in root_block + 2..root_block + 1 + lost_found_blocks {
+        write_block(disk, block, &buf)?;
+    }
+
+    let sectors_per_block = block_size as u32 / 512;
+    let inode_offset = |ino: u32| {
+        groups[0].inode_table as u64 * block_size as u64 + (ino - 1) as u64 * inode_size as u64
+    };
+
+    let mut root = Inode::new(FileType::Directory, 0o755, now);
+    root.set_links_count(3);
+    root.set_block(0, root_block);
+    root.set_size(block_size as u64);
+    root.set_sectors(sectors_per_block);
+
+    let mut lost_found = Inode::new(FileType::Directory, 0o700, now);
+    lost_found.set_links_count(2);
+    for i in 0..lost_found_blocks {
+        lost_found.set_block(i as usize, root_block + 1 + i);
+    }
+    lost_found.set_size((lost_found_blocks as usize * block_size) as u64);
+    lost_found.set_sectors(lost_found_blocks * sectors_per_block);
+
+    let mut buf = [0; SuperBlock::GOOD_OLD_INODE_SIZE as usize];
+    for (ino, inode) in [(Inode::ROOT_INO, root), (LOST_FOUND_INO, lost_found)] {
+        inode.to_bytes(&mut buf);
+        disk.seek(SeekFrom::Start(inode_offset(ino)))?;
+        disk.write_all(&buf)?;
+    }
+
+    // Superblock and group descriptor table, in every group holding a copy of them
+    let mut gdt = vec![0; sb.gdt_blocks() as usize * block_size];
+    for (gd, buf) in groups.iter().zip(gdt.chunks_mut(BlockGroupDesc::SIZE)) {
+        gd.to_bytes(buf);
+    }
+
+    let mut buf = [0; SuperBlock::SIZE];
+    for group in 0..group_count {
+        if !sb.group_has_super(group) {
+            continue;
+        }
+
+        let start = group_start(group);
+        write_block(disk, start + 1, &gdt)?;
+
+        sb.block_group_nr = group as u16;
+        sb.to_bytes(&mut buf);
+        let offset = if group == 0 {
+            SuperBlock::OFFSET
+        } else {
+            start as u64 * block_size as u64
+        };
+        disk.seek(SeekFrom::Start(offset))?;
+        disk.write_all(&buf)?;
+    }
+
+    disk.flush()?;
+    Ok(())
+}
@@ -1,7 +1,30 @@
-use crate::io::{Read, Seek, Write};
+use core::{cell::RefCell, mem::size_of};
 
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    blocks::{BlockGroupDesc, OnDisk, SuperBlock},
+    dir::Dir,
+    error::Error,
+    file::File,
+    inode::{FileType, Inode},
+    io::{Read, Seek, SeekFrom, Write},
+    time::{DefaultTimeProvider, TimeProvider},
+};
+
+/// Maximum number of symbolic links followed while resolving a path.
+pub const MAX_SYMLINK_DEPTH: u32 = 8;
+
+/// Symbolic links shorter than this are stored in the inode itself.
+const FAST_SYMLINK_MAX_LEN: usize = 60;
+
+/// An ext2 file system.
 pub struct FileSystem<IO: Read + Write + Seek> {
-    disk: IO,
+    disk: RefCell<IO>,
+    pub(crate) sb: RefCell<SuperBlock>,
+    pub(crate) groups: RefCell<Vec<BlockGroupDesc>>,
+    read_only: bool,
+    time_provider: &'static (dyn TimeProvider + Sync),
 }
 
 pub trait IntoStorage<T: Read + Write + Seek> {
@@ -24,9 +47,561 @@ impl<T: std::io::Read + std::io::Write + std::io::Seek> IntoStorage<crate::io::S
 }
 
 impl<IO: Read + Write + Seek> FileSystem<IO> {
-    pub fn new<T: IntoStorage<IO>>(disk: T) -> Self {
-        Self {
-            disk: disk.into_storage(),
+    /// Mounts the file system contained in `disk`.
+    ///
+    /// The superblock and the block group descriptor table are read and validated, and an error
+    /// is returned if they don't describe a supported ext2 file system.
+    ///
+    /// Mounting fails if the file system uses unknown incompatible features. If it uses unknown
+    /// read-only compatible features instead, it is mounted read-only.
+    ///
+    /// If an ext3 file system was not cleanly unmounted, the committed transactions of its
+    /// journal are replayed first, see [`FileSystem::replay_journal`].
+    pub fn new<T: IntoStorage<IO>>(disk: T) -> Result<Self, Error<IO::Error>> {
+        let fs = Self::mount(disk.into_storage(), false)?;
+
+        // Like ext3, bring the file system back to a consistent state after a crash
+        if fs.needs_recovery() {
+            fs.replay_journal()?;
+        }
+
+        Ok(fs)
+    }
+
+    /// Mounts the file system contained in `disk` read-only, so that it's never written to.
+    ///
+    /// Unlike [`FileSystem::new`], the journal of an ext3 file system which needs recovery is
+    /// not replayed, so the changes of its last transactions are missing.
+    pub fn new_read_only<T: IntoStorage<IO>>(disk: T) -> Result<Self, Error<IO::Error>> {
+        Self::mount(disk.into_storage(), true)
+    }
+
+    /// Reads the metadata of the file system contained in `disk`, which is also mounted
+    /// read-only if it uses unsupported read-only compatible features.
+    fn mount(mut disk: IO, read_only: bool) -> Result<Self, Error<IO::Error>> {
+        let (sb, groups) = read_metadata(&mut disk)?;
+        let read_only =
+            read_only || sb.feature_ro_compat & !SuperBlock::FEATURE_RO_COMPAT_SUPP != 0;
+
+        Ok(Self {
+            disk: RefCell::new(disk),
+            sb: RefCell::new(sb),
+            groups: RefCell::new(groups),
+            read_only,
+            time_provider: &DefaultTimeProvider,
+        })
+    }
+
+    /// Returns the size of a block in bytes.
+    pub fn block_size(&self) -> u32 {
+        self.sb.borrow().block_size()
+    }
+
+    /// Returns the total number of blocks, both used and free.
+    pub fn blocks_count(&self) -> u32 {
+        self.sb.borrow().blocks_count
+    }
+
+    /// Returns the number of free blocks, including the ones reserved for the superuser.
+    pub fn free_blocks_count(&self) -> u32 {
+        self.sb.borrow().free_blocks_count
+    }
+
+    /// Returns the number of blocks reserved for the superuser.
+    pub fn reserved_blocks_count(&self) -> u32 {
+        self.sb.borrow().r_blocks_count
+    }
+
+    /// Returns the total number of inodes, both used and free.
+    pub fn inodes_count(&self) -> u32 {
+        self.sb.borrow().inodes_count
+    }
+
+    /// Returns the number of free inodes.
+    pub fn free_inodes_count(&self) -> u32 {
+        self.sb.borrow().free_inodes_count
+    }
+
+    /// Returns the id of the block containing the superblock.
+    pub fn first_data_block(&self) -> u32 {
+        self.sb.borrow().first_data_block
+    }
+
+    /// Returns the number of blocks in each block group.
+    pub fn blocks_per_group(&self) -> u32 {
+        self.sb.borrow().blocks_per_group
+    }
+
+    /// Returns the number of inodes in each block group.
+    pub fn inodes_per_group(&self) -> u32 {
+        self.sb.borrow().inodes_per_group
+    }
+
+    /// Returns the number of block groups.
+    pub fn group_count(&self) -> u32 {
+        self.groups.borrow().len() as u32
+    }
+
+    /// Sets the source of timestamps for modified inodes.
+    pub fn set_time_provider(&mut self, time_provider: &'static (dyn TimeProvider + Sync)) {
+        self.time_provider = time_provider;
+    }
+
+    /// Returns the revision level of the file system.
+    pub fn revision(&self) -> u32 {
+        self.sb.borrow().rev_level
+    }
+
+    /// Returns the size of an inode on disk.
+    pub fn inode_size(&self) -> u32 {
+        self.sb.borrow().inode_size()
+    }
+
+    /// Returns the first inode number available for regular files, the ones below being
+    /// reserved.
+    pub fn first_ino(&self) -> u32 {
+        self.sb.borrow().first_ino()
+    }
+
+    /// Returns the compatible feature flags.
+    pub fn feature_compat(&self) -> u32 {
+        self.sb.borrow().feature_compat
+    }
+
+    /// Returns the incompatible feature flags.
+    pub fn feature_incompat(&self) -> u32 {
+        self.sb.borrow().feature_incompat
+    }
+
+    /// Returns the read-only compatible feature flags.
+    pub fn feature_ro_compat(&self) -> u32 {
+        self.sb.borrow().feature_ro_compat
+    }
+
+    /// Returns the volume id, which is all zeroes in revision 0 file systems.
+    pub fn uuid(&self) -> [u8; 16] {
+        self.sb.borrow().uuid
+    }
+
+    /// Returns the volume name, without its zero padding.
+    pub fn volume_name(&self) -> Vec<u8> {
+        let name = self.sb.borrow().volume_name;
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        name[..len].to_vec()
+    }
+
+    /// Returns `true` if the file system was mounted read-only, either explicitly or because it
+    /// uses read-only compatible features which are not supported.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns `true` if the journal of the file system holds transactions which still have to
+    /// be replayed, which is only the case after mounting it with
+    /// [`FileSystem::new_read_only`].
+    pub fn needs_recovery(&self) -> bool {
+        self.sb
+            .borrow()
+            .has_incompat(SuperBlock::FEATURE_INCOMPAT_RECOVER)
+    }
+
+    /// Reads inode number `ino` from its group's inode table.
+    ///
+    /// Inode numbers start from 1, and the root directory is always [`Inode::ROOT_INO`].
+    pub fn inode(&self, ino: u32) -> Result<Inode, Error<IO::Error>> {
+        let mut buf = [0; size_of::<Inode>()];
+        self.read_at(self.inode_offset(ino)?, &mut buf)?;
+        Ok(Inode::from_bytes(&buf))
+    }
+
+    /// Writes back inode number `ino`.
+    ///
+    /// Only the first 128 bytes are written, leaving the extra space of larger inodes as is.
+    pub(crate) fn write_inode(&self, ino: u32, inode: &Inode) -> Result<(), Error<IO::Error>> {
+        if inode.size() > i32::MAX as u64 {
+            self.enable_large_file()?;
+        }
+
+        let mut buf = [0; size_of::<Inode>()];
+        inode.to_bytes(&mut buf);
+        self.write_at(self.inode_offset(ino)?, &buf)
+    }
+
+    /// Zeroes the whole on-disk record of inode number `ino`, including the extra space of
+    /// larger inodes.
+    pub(crate) fn clear_inode(&self, ino: u32) -> Result<(), Error<IO::Error>> {
+        let zeroes = vec![0; self.inode_size() as usize];
+        self.write_at(self.inode_offset(ino)?, &zeroes)
+    }
+
+    /// Returns the byte offset of inode number `ino` on disk.
+    pub(crate) fn inode_offset(&self, ino: u32) -> Result<u64, Error<IO::Error>> {
+        if ino == 0 || ino > self.sb.borrow().inodes_count {
+            return Err(Error::InvalidInput);
+        }
+
+        let group = (ino - 1) / self.sb.borrow().inodes_per_group;
+        let index = (ino - 1) % self.sb.borrow().inodes_per_group;
+        let inode_size = self.sb.borrow().inode_size() as u64;
+
+        Ok(
+            self.groups.borrow()[group as usize].inode_table as u64 * self.block_size() as u64
+                + index as u64 * inode_size,
+        )
+    }
+
+    /// Opens the contents of inode number `ino` for reading and writing.
+    ///
+    /// Only regular files and directories can be opened: the block pointers of symlinks and
+    /// device nodes may hold a target or device numbers instead. Symlinks are read with
+    /// [`FileSystem::read_link`].
+    pub fn file(&self, ino: u32) -> Result<File<'_, IO>, Error<IO::Error>> {
+        let inode = self.inode(ino)?;
+        if !matches!(
+            inode.file_type(),
+            FileType::RegularFile | FileType::Directory
+        ) {
+            return Err(Error::InvalidInput);
+        }
+        Ok(File::new(self, ino, inode))
+    }
+
+    /// Opens the directory with inode number `ino`.
+    pub fn dir(&self, ino: u32) -> Result<Dir<'_, IO>, Error<IO::Error>> {
+        Dir::new(self, ino, self.inode(ino)?)
+    }
+
+    /// Opens the root directory.
+    pub fn root_dir(&self) -> Result<Dir<'_, IO>, Error<IO::Error>> {
+        self.dir(Inode::ROOT_INO)
+    }
+
+    /// Resolves an absolute path to an inode number.
+    ///
+    /// Symbolic links are followed, including the last component of the path, up to a nesting
+    /// depth of [`MAX_SYMLINK_DEPTH`].
+    pub fn lookup(&self, path: &str) -> Result<u32, Error<IO::Error>> {
+        if !path.starts_with('/') {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut depth = 0;
+        self.resolve(Inode::ROOT_INO, path.as_bytes(), true, &mut depth)
+    }
+
+    /// Resolves an absolute path and opens the corresponding file.
+    pub fn open(&self, path: &str) -> Result<File<'_, IO>, Error<IO::Error>> {
+        self.file(self.lookup(path)?)
+    }
+
+    /// Returns the target of the symbolic link with inode number `ino`.
+    pub fn read_link(&self, ino: u32) -> Result<Vec<u8>, Error<IO::Error>> {
+        let inode = self.inode(ino)?;
+        if inode.file_type() != FileType::Symlink {
+            return Err(Error::InvalidInput);
         }
+
+        let size = inode.size();
+        if size > self.block_size() as u64 {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        // Fast symlinks store the target in place of the block pointers
+        if inode.is_fast_symlink(self.block_size()) {
+            if size > FAST_SYMLINK_MAX_LEN as u64 {
+                return Err(Error::CorruptedFileSystem);
+            }
+
+            let target = inode
+                .block()
+                .iter()
+                .flat_map(|b| b.to_le_bytes())
+                .take(size as usize)
+                .collect();
+
+            return Ok(target);
+        }
+
+        let mut target = vec![0; size as usize];
+        File::new(self, ino, inode).read_exact(&mut target)?;
+        Ok(target)
+    }
+
+    /// Flushes any pending write to the underlying storage.
+    pub fn flush(&self) -> Result<(), Error<IO::Error>> {
+        Ok(self.disk.borrow_mut().flush()?)
+    }
+
+    /// Consumes the file system, returning the underlying storage.
+    pub fn into_inner(self) -> IO {
+        self.disk.into_inner()
+    }
+
+    /// Resolves `path` starting from directory `dir`.
+    ///
+    /// If `follow` is false and the last component is a symbolic link, the link itself is
+    /// returned. `depth` counts the symbolic links followed so far.
+    fn resolve(
+        &self,
+        dir: u32,
+        path: &[u8],
+        follow: bool,
+        depth: &mut u32,
+    ) -> Result<u32, Error<IO::Error>> {
+        let mut cur = if path.starts_with(b"/") {
+            Inode::ROOT_INO
+        } else {
+            dir
+        };
+
+        let mut components = path
+            .split(|&c| c == b'/')
+            .filter(|c| !c.is_empty() && *c != b".")
+            .peekable();
+
+        while let Some(name) = components.next() {
+            let entry = self.dir(cur)?.find(name)?.ok_or(Error::NotFound)?;
+            let is_last = components.peek().is_none();
+
+            let inode = self.inode(entry.ino())?;
+            if inode.file_type() == FileType::Symlink && (follow || !is_last) {
+                *depth += 1;
+                if *depth > MAX_SYMLINK_DEPTH {
+                    return Err(Error::TooManySymlinks);
+                }
+
+                // Relative targets are resolved from the directory containing the link
+                let target = self.read_link(entry.ino())?;
+                cur = self.resolve(cur, &target, true, depth)?;
+            } else {
+                cur = entry.ino();
+            }
+        }
+
+        Ok(cur)
+    }
+
+    /// Reads `buf.len()` bytes starting at byte `offset` of the storage.
+    pub(crate) fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Error<IO::Error>> {
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(offset))?;
+        disk.read_exact(buf)?;
+        Ok(())
+    }
+
+    /// Writes `buf` starting at byte `offset` of the storage.
+    pub(crate) fn write_at(&self, offset: u64, buf: &[u8]) -> Result<(), Error<IO::Error>> {
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(offset))?;
+        disk.write_all(buf)?;
+        Ok(())
+    }
+
+    /// Reads a whole block into `buf`, which must be `block_size()` bytes long.
+    pub(crate) fn read_block(&self, block: u32, buf: &mut [u8]) -> Result<(), Error<IO::Error>> {
+        if block >= self.blocks_count() {
+            return Err(Error::CorruptedFileSystem);
+        }
+        self.read_at(block as u64 * self.block_size() as u64, buf)
+    }
+
+    /// Writes a whole block from `buf`, which must be `block_size()` bytes long.
+    pub(crate) fn write_block(&self, block: u32, buf: &[u8]) -> Result<(), Error<IO::Error>> {
+        if block >= self.blocks_count() {
+            return Err(Error::CorruptedFileSystem);
+        }
+        self.write_at(block as u64 * self.block_size() as u64, buf)
+    }
+
+    /// Returns an error if the file system can't be modified.
+    pub(crate) fn check_writable(&self) -> Result<(), Error<IO::Error>> {
+        if self.read_only {
+            return Err(Error::ReadOnlyFileSystem);
+        }
+        Ok(())
+    }
+
+    /// Sets the `large_file` feature, after which regular files may grow past 2 GiB.
+    ///
+    /// Revision 0 file systems have no feature flags, so they are upgraded to revision 1 first.
+    fn enable_large_file(&self) -> Result<(), Error<IO::Error>> {
+        {
+            let mut sb = self.sb.borrow_mut();
+            if sb.has_ro_compat(SuperBlock::FEATURE_RO_COMPAT_LARGE_FILE) {
+                return Ok(());
+            }
+
+            if sb.rev_level == SuperBlock::GOOD_OLD_REV {
+                sb.rev_level = SuperBlock::DYNAMIC_REV;
+                sb.first_ino = SuperBlock::GOOD_OLD_FIRST_INO;
+                sb.inode_size = SuperBlock::GOOD_OLD_INODE_SIZE as u16;
+            }
+            sb.feature_ro_compat |= SuperBlock::FEATURE_RO_COMPAT_LARGE_FILE;
+        }
+        self.write_superblock()
+    }
+
+    /// Reads the superblock and the group descriptor table again, after they have been changed
+    /// behind our back.
+    pub(crate) fn reload_metadata(&self) -> Result<(), Error<IO::Error>> {
+        let (sb, groups) = read_metadata(&mut *self.disk.borrow_mut())?;
+        *self.sb.borrow_mut() = sb;
+        *self.groups.borrow_mut() = groups;
+        Ok(())
+    }
+
+    /// Writes back the primary superblock.
+    pub(crate) fn write_superblock(&self) -> Result<(), Error<IO::Error>> {
+        let mut buf = [0; size_of::<SuperBlock>()];
+        {
+            let mut sb = self.sb.borrow_mut();
+            sb.wtime = self.now();
+            sb.to_bytes(&mut buf);
+        }
+        self.write_at(SuperBlock::OFFSET, &buf)
     }
+
+    /// Writes back the primary descriptor of `group`.
+    pub(crate) fn write_group_desc(&self, group: u32) -> Result<(), Error<IO::Error>> {
+        let mut buf = [0; BlockGroupDesc::SIZE];
+        self.groups.borrow()[group as usize].to_bytes(&mut buf);
+
+        let table = (self.sb.borrow().first_data_block as u64 + 1) * self.block_size() as u64;
+        self.write_at(table + group as u64 * BlockGroupDesc::SIZE as u64, &buf)
+    }
+
+    /// Returns the current time, in seconds since the epoch.
+    pub(crate) fn now(&self) -> u32 {
+        self.time_provider.now()
+    }
+}
+
+/// Reads and validates the primary superblock and group descriptor table of `disk`.
+fn read_metadata<IO: Read + Seek>(
+    disk: &mut IO,
+) -> Result<(SuperBlock, Vec<BlockGroupDesc>), Error<IO::Error>> {
+    let mut buf = [0; SuperBlock::SIZE];
+    disk.seek(SeekFrom::Start(SuperBlock::OFFSET))?;
+    disk.read_exact(&mut buf)?;
+
+    let sb = SuperBlock::from_bytes(&buf);
+    validate_superblock(&sb)?;
+
+    // The group descriptor table starts in the block following the superblock
+    let block_size = sb.block_size() as u64;
+    let group_count = sb.group_count() as usize;
+
+    let mut buf = vec![0; group_count * BlockGroupDesc::SIZE];
+    disk.seek(SeekFrom::Start(
+        (sb.first_data_block as u64 + 1) * block_size,
+    ))?;
+    disk.read_exact(&mut buf)?;
+
+    let groups = buf
+        .as_chunks::<{ BlockGroupDesc::SIZE }>()
+        .0
+        .iter()
+        .map(|gd| BlockGroupDesc::from_bytes(gd))
+        .collect::<Vec<_>>();
+
+    for (i, gd) in groups.iter().enumerate() {
+        validate_group_desc(&sb, i as u32, gd)?;
+    }
+
+    Ok((sb, groups))
+}
+
+fn validate_superblock<E>(sb: &SuperBlock) -> Result<(), Error<E>> {
+    if sb.magic != SuperBlock::MAGIC {
+        return Err(Error::InvalidMagic);
+    }
+
+    if sb.rev_level > SuperBlock::DYNAMIC_REV {
+        return Err(Error::UnsupportedRevision(sb.rev_level));
+    }
+
+    let unsupported = sb.feature_incompat & !SuperBlock::FEATURE_INCOMPAT_SUPP;
+    if unsupported != 0 {
+        return Err(Error::UnsupportedFeature(unsupported));
+    }
+
+    if sb.log_block_size > SuperBlock::MAX_LOG_BLOCK_SIZE {
+        return Err(Error::CorruptedFileSystem);
+    }
+
+    // The superblock always lives at byte 1024, which is block 1 only for 1 KiB blocks
+    let expected_first_data_block = if sb.block_size() == 1024 { 1 } else { 0 };
+
+    if sb.first_data_block != expected_first_data_block
+        || sb.blocks_count <= sb.first_data_block
+        || sb.blocks_per_group == 0
+        || sb.blocks_per_group > sb.block_size() * 8
+        || sb.inodes_per_group == 0
+        || sb.inodes_per_group > sb.block_size() * 8
+        || sb.free_blocks_count > sb.blocks_count
+        || sb.r_blocks_count > sb.blocks_count
+        || sb.free_inodes_count > sb.inodes_count
+    {
+        return Err(Error::CorruptedFileSystem);
+    }
+
+    // Inodes are a power of two no smaller than the original 128 bytes, and never span blocks
+    let inode_size = sb.inode_size();
+    if !inode_size.is_power_of_two()
+        || inode_size < SuperBlock::GOOD_OLD_INODE_SIZE
+        || inode_size > sb.block_size()
+        || sb.first_ino() < SuperBlock::GOOD_OLD_FIRST_INO
+        || sb.first_ino() > sb.inodes_count
+    {
+        return Err(Error::CorruptedFileSystem);
+    }
+
+    if sb.group_count() as u64 * sb.inodes_per_group as u64 != sb.inodes_count as u64 {
+        return Err(Error::CorruptedFileSystem);
+    }
+
+    Ok(())
+}
+
+fn validate_group_desc<E>(
+    sb: &SuperBlock,
+    group: u32,
+    gd: &BlockGroupDesc,
+) -> Result<(), Error<E>> {
+    let inode_table_blocks =
+        (sb.inodes_per_group as u64 * sb.inode_size() as u64).div_ceil(sb.block_size() as u64);
+
+    // Groups holding a copy of the superblock start with it, followed by the GDT and the blocks
+    // reserved for growing it
+    let group_start = sb.first_data_block + group * sb.blocks_per_group;
+    let meta_end = if sb.group_has_super(group) {
+        group_start as u64 + 1 + sb.gdt_blocks() as u64 + sb.reserved_gdt_blocks as u64
+    } else {
+        group_start as u64
+    };
+
+    let in_fs = |block: u32, len: u64| {
+        block >= sb.first_data_block
+            && block as u64 + len <= sb.blocks_count as u64
+            && (block < group_start || block as u64 >= meta_end)
+    };
+
+    if !in_fs(gd.block_bitmap, 1)
+        || !in_fs(gd.inode_bitmap, 1)
+        || !in_fs(gd.inode_table, inode_table_blocks)
+    {
+        return Err(Error::CorruptedFileSystem);
+    }
+
+    let group_blocks = sb
+        .blocks_per_group
+        .min(sb.blocks_count - sb.first_data_block - group * sb.blocks_per_group);
+
+    if gd.free_blocks_count as u32 > group_blocks
+        || gd.free_inodes_count as u32 > sb.inodes_per_group
+    {
+        return Err(Error::CorruptedFileSystem);
+    }
+
+    Ok(())
 }
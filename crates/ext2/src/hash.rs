@@ -0,0 +1,197 @@
+//! Hashes of file names used by the directory index, see `fs/ext4/hash.c` in Linux.
+
+/// Hash algorithm of indexed directories, as stored in their root block and in the superblock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HashVersion {
+    Legacy,
+    HalfMd4,
+    Tea,
+}
+
+impl HashVersion {
+    /// Returns the algorithm identified by `version`, if it's supported.
+    pub(crate) fn from_raw(version: u8) -> Option<Self> {
+        match version {
+            0 => Some(Self::Legacy),
+            1 => Some(Self::HalfMd4),
+            2 => Some(Self::Tea),
+            _ => None,
+        }
+    }
+}
+
+/// Initial state of the MD4 and TEA hashes when the superblock has no seed.
+const DEFAULT_SEED: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+/// Largest 32-bit hash, used by Linux as an end of directory marker.
+const HTREE_EOF_32BIT: u32 = 0x7fffffff;
+
+/// Returns the hash of `name`, whose lowest bit is always clear.
+///
+/// Bytes of the name are either sign-extended, as when Linux is built with a signed `char`, or
+/// zero-extended when `unsigned` is set.
+pub(crate) fn name_hash(version: HashVersion, seed: [u32; 4], unsigned: bool, name: &[u8]) -> u32 {
+    let mut buf = if seed == [0; 4] { DEFAULT_SEED } else { seed };
+
+    let hash = match version {
+        HashVersion::Legacy => legacy_hash(name, unsigned),
+        HashVersion::HalfMd4 => {
+            for rest in tails(name, 32) {
+                let mut input = [0; 8];
+                str2hashbuf(rest, &mut input, unsigned);
+                half_md4_transform(&mut buf, &input);
+            }
+            buf[1]
+        }
+        HashVersion::Tea => {
+            for rest in tails(name, 16) {
+                let mut input = [0; 4];
+                str2hashbuf(rest, &mut input, unsigned);
+                tea_transform(&mut buf, &input);
+            }
+            buf[0]
+        }
+    };
+
+    let hash = hash & !1;
+    if hash == HTREE_EOF_32BIT << 1 {
+        (HTREE_EOF_32BIT - 1) << 1
+    } else {
+        hash
+    }
+}
+
+/// Returns the suffixes of `name` starting every `step` bytes.
+fn tails(name: &[u8], step: usize) -> impl Iterator<Item = &[u8]> {
+    (0..name.len())
+        .step_by(step)
+        .map(move |start| &name[start..])
+}
+
+/// Converts a byte of a name to an integer, the way C would convert a `char`.
+fn char_value(c: u8, unsigned: bool) -> u32 {
+    if unsigned { c as u32 } else { c as i8 as u32 }
+}
+
+/// Packs the first bytes of `msg` in `buf`, padding it with the length of the whole message.
+fn str2hashbuf(msg: &[u8], buf: &mut [u32], unsigned: bool) {
+    let len = msg.len();
+    let mut pad = len as u32 | ((len as u32) << 8);
+    pad |= pad << 16;
+
+    let msg = &msg[..len.min(buf.len() * 4)];
+    let mut words = buf.iter_mut();
+    let mut val = pad;
+
+    for (i, &c) in msg.iter().enumerate() {
+        val = char_value(c, unsigned).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            *words.next().unwrap() = val;
+            val = pad;
+        }
+    }
+
+    if !msg.len().is_multiple_of(4) {
+        *words.next().unwrap() = val;
+    }
+    for word in words {
+        *word = pad;
+    }
+}
+
+/// The original hash of the directory index, from before the seeded hashes were introduced.
+fn legacy_hash(name: &[u8], unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3fe2d_u32, 0x37abe8f9_u32);
+
+    for &c in name {
+        let mut hash = hash1.wrapping_add(hash0 ^ char_value(c, unsigned).wrapping_mul(7152373));
+        if hash & 0x80000000 != 0 {
+            hash = hash.wrapping_sub(0x7fffffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+
+    hash0 << 1
+}
+
+/// Mixes 8 words of input into `buf`, using the first rounds of MD4.
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const K2: u32 = 0o13240474631;
+    const K3: u32 = 0o15666365641;
+
+    fn f(x: u32, y: u32, z: u32) -> u32 {
+        z ^ (x & (y ^ z))
+    }
+    fn g(x: u32, y: u32, z: u32) -> u32 {
+        (x & y).wrapping_add((x ^ y) & z)
+    }
+    fn h(x: u32, y: u32, z: u32) -> u32 {
+        x ^ y ^ z
+    }
+
+    let [mut a, mut b, mut c, mut d] = *buf;
+
+    macro_rules! round {
+        ($f:ident, $a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $s:expr) => {
+            $a = $a
+                .wrapping_add($f($b, $c, $d))
+                .wrapping_add($x)
+                .rotate_left($s);
+        };
+    }
+
+    round!(f, a, b, c, d, input[0], 3);
+    round!(f, d, a, b, c, input[1], 7);
+    round!(f, c, d, a, b, input[2], 11);
+    round!(f, b, c, d, a, input[3], 19);
+    round!(f, a, b, c, d, input[4], 3);
+    round!(f, d, a, b, c, input[5], 7);
+    round!(f, c, d, a, b, input[6], 11);
+    round!(f, b, c, d, a, input[7], 19);
+
+    round!(g, a, b, c, d, input[1].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[3].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[5].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[7].wrapping_add(K2), 13);
+    round!(g, a, b, c, d, input[0].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[2].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[4].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round!(h, a, b, c, d, input[3].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[7].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[2].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[6].wrapping_add(K3), 15);
+    round!(h, a, b, c, d, input[1].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[5].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[0].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+/// Mixes 4 words of input into the first two words of `buf`, with the TEA block cipher.
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 4]) {
+    const DELTA: u32 = 0x9e3779b9;
+
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let [a, b, c, d] = *input;
+    let mut sum = 0_u32;
+
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
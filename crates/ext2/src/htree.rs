@@ -0,0 +1,199 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    blocks::SuperBlock,
+    error::Error,
+    fs::FileSystem,
+    hash::{HashVersion, name_hash},
+    inode::Inode,
+    io::{Read, Seek, Write},
+};
+
+/// Offset of the root information in the first block of an indexed directory, after the `.` and
+/// `..` entries.
+const ROOT_INFO_OFFSET: usize = 24;
+/// Size of the root information.
+const ROOT_INFO_LEN: u8 = 8;
+/// Offset of the entries of interior nodes, after an empty record spanning the whole block.
+const NODE_ENTRIES_OFFSET: usize = 8;
+/// Size of an index entry, and of the count and limit header which replaces the first hash.
+const ENTRY_SIZE: usize = 8;
+/// Maximum number of levels of interior nodes below the root, without the `largedir` feature.
+const MAX_INDIRECT_LEVELS: u8 = 1;
+/// Only the lower 28 bits of the block of an entry are used.
+const BLOCK_MASK: u32 = 0x0fffffff;
+
+/// An entry of an index node: the first directory block whose names hash to `hash` or more.
+#[derive(Clone, Copy)]
+struct DxEntry {
+    hash: u32,
+    block: u32,
+}
+
+/// A node of the index, and the entry followed in it.
+struct Frame {
+    entries: Vec<DxEntry>,
+    at: usize,
+}
+
+impl Frame {
+    fn block(&self) -> u32 {
+        self.entries[self.at].block
+    }
+}
+
+/// Parses the entries of an index node starting at `offset` in `buf`.
+///
+/// The first entry holds the count and limit of entries instead of a hash, as it covers all the
+/// hashes below the second one.
+fn parse_entries<E>(buf: &[u8], offset: usize, dir_blocks: u64) -> Result<Vec<DxEntry>, Error<E>> {
+    let header = buf
+        .get(offset..offset + 4)
+        .ok_or(Error::CorruptedFileSystem)?;
+    let limit = u16::from_le_bytes([header[0], header[1]]) as usize;
+    let count = u16::from_le_bytes([header[2], header[3]]) as usize;
+
+    if count == 0 || count > limit || offset + limit * ENTRY_SIZE > buf.len() {
+        return Err(Error::CorruptedFileSystem);
+    }
+
+    let raw = &buf[offset..offset + count * ENTRY_SIZE];
+    let mut entries = Vec::with_capacity(count);
+    for (i, raw) in raw.as_chunks::<ENTRY_SIZE>().0.iter().enumerate() {
+        let hash = if i == 0 {
+            0
+        } else {
+            u32::from_le_bytes(raw[0..4].try_into().unwrap())
+        };
+        let block = u32::from_le_bytes(raw[4..8].try_into().unwrap()) & BLOCK_MASK;
+
+        if block as u64 >= dir_blocks {
+            return Err(Error::CorruptedFileSystem);
+        }
+        entries.push(DxEntry { hash, block });
+    }
+
+    Ok(entries)
+}
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Returns `true` if directory `dir` is indexed with a hash tree.
+    pub(crate) fn is_dx_dir(&self, dir: &Inode) -> bool {
+        dir.flags() & Inode::INDEX_FL != 0
+            && self
+                .sb
+                .borrow()
+                .has_compat(SuperBlock::FEATURE_COMPAT_DIR_INDEX)
+    }
+
+    /// Returns the logical blocks of directory `dir` which may hold the entry named `name`.
+    ///
+    /// This is the leaf of the index covering the hash of `name`, followed by the leaves which
+    /// continue it when several names have the same hash. Returns `None` if the directory is not
+    /// indexed or if its index can't be used, in which case all its blocks must be searched.
+    pub(crate) fn dx_leaves(
+        &self,
+        dir: &Inode,
+        name: &[u8],
+    ) -> Result<Option<Vec<u64>>, Error<IO::Error>> {
+        // `.` and `..` are always in the first block, outside of the index
+        if !self.is_dx_dir(dir) || name == b"." || name == b".." {
+            return Ok(None);
+        }
+
+        // Like Linux, fall back to a linear search if the index is damaged
+        match self.dx_probe(dir, name) {
+            Err(Error::CorruptedFileSystem) => Ok(None),
+            res => res.map(Some),
+        }
+    }
+
+    /// Walks the index of directory `dir` down to the leaves which may hold `name`.
+    fn dx_probe(&self, dir: &Inode, name: &[u8]) -> Result<Vec<u64>, Error<IO::Error>> {
+        let block_size = self.block_size() as usize;
+        let dir_blocks = dir.size() / block_size as u64;
+
+        let mut buf = vec![0; block_size];
+        self.read_dir_block(dir, 0, &mut buf)?;
+
+        let info = &buf[ROOT_INFO_OFFSET..ROOT_INFO_OFFSET + ROOT_INFO_LEN as usize];
+        let (hash_version, info_len, levels) = (info[4], info[5], info[6]);
+        if info[0..4] != [0; 4] || info_len != ROOT_INFO_LEN || levels > MAX_INDIRECT_LEVELS {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        let version = HashVersion::from_raw(hash_version).ok_or(Error::CorruptedFileSystem)?;
+        let hash = {
+            let sb = self.sb.borrow();
+            let unsigned = sb.flags & SuperBlock::FLAGS_UNSIGNED_HASH != 0;
+            name_hash(version, sb.hash_seed, unsigned, name)
+        };
+
+        // At each level, follow the last entry whose hash isn't above the hash of the name
+        let mut path: Vec<Frame> = Vec::with_capacity(levels as usize + 1);
+        let mut entries = parse_entries(&buf, ROOT_INFO_OFFSET + info_len as usize, dir_blocks)?;
+        loop {
+            let at = entries[1..].partition_point(|e| e.hash <= hash);
+            path.push(Frame { entries, at });
+            if path.len() > levels as usize {
+                break;
+            }
+
+            let block = path.last().unwrap().block();
+            self.read_dir_block(dir, block as u64, &mut buf)?;
+            entries = self.parse_node(&buf, dir_blocks)?;
+        }
+
+        let mut leaves = vec![path.last().unwrap().block() as u64];
+
+        // Names with the same hash may continue in the next leaves, whose hash then has its
+        // lowest bit set
+        while let Some(level) = path.iter().rposition(|f| f.at + 1 < f.entries.len()) {
+            path[level].at += 1;
+            if path[level].entries[path[level].at].hash & !1 != hash {
+                break;
+            }
+
+            // Go back down to the first leaf below the next entry
+            for level in level + 1..path.len() {
+                let block = path[level - 1].block();
+                self.read_dir_block(dir, block as u64, &mut buf)?;
+                path[level] = Frame {
+                    entries: self.parse_node(&buf, dir_blocks)?,
+                    at: 0,
+                };
+            }
+
+            leaves.push(path.last().unwrap().block() as u64);
+        }
+
+        Ok(leaves)
+    }
+
+    /// Parses the entries of an interior node of the index.
+    fn parse_node(&self, buf: &[u8], dir_blocks: u64) -> Result<Vec<DxEntry>, Error<IO::Error>> {
+        // Interior nodes look like empty directory blocks to implementations without htree
+        let ino = u32::from_le_bytes(buf[0..4].try_into().unwrap());
+        let rec_len = u16::from_le_bytes(buf[4..6].try_into().unwrap()) as usize;
+        if ino != 0 || rec_len != buf.len() {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        parse_entries(buf, NODE_ENTRIES_OFFSET, dir_blocks)
+    }
+
+    /// Reads logical block `index` of directory `dir` into `buf`, returning its block number.
+    pub(crate) fn read_dir_block(
+        &self,
+        dir: &Inode,
+        index: u64,
+        buf: &mut [u8],
+    ) -> Result<u32, Error<IO::Error>> {
+        let block = self.map_block(dir, index)?;
+        if block == 0 {
+            return Err(Error::CorruptedFileSystem);
+        }
+        self.read_block(block, buf)?;
+        Ok(block)
+    }
+}
@@ -1,5 +1,15 @@
+use alloc::vec;
+
+use crate::{
+    blocks::{OnDisk, SuperBlock},
+    error::Error,
+    fs::FileSystem,
+    io::{Read, Seek, Write},
+};
+
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
 pub struct Inode {
     mode: u16,        // format of the file and access rights
     uid: u16,         // owner's user id
@@ -15,8 +25,455 @@ pub struct Inode {
     osd1: u32,        // OS-dependent 1
     block: [u32; 15], // block numbers pointing to data blocks
     generation: u32,  // generation number (used in NFS)
-    file_acl: u32,    // always 0
-    dir_acl: u32,     // always 0
+    file_acl: u32,    // block holding extended attributes
+    size_high: u32,   // upper 32 bits of the size of regular files (dir_acl for others)
     faddr: u32,       // fragment address
     osd2: [u8; 12],   // OS-dependent 2
 }
+
+// SAFETY: packed struct of integers
+unsafe impl OnDisk for Inode {}
+
+impl Inode {
+    /// Inode number of the root directory.
+    pub const ROOT_INO: u32 = 2;
+
+    /// Directory is indexed with a hash tree.
+    pub const INDEX_FL: u32 = 0x1000;
+
+    /// Number of direct block pointers.
+    pub(crate) const DIRECT_BLOCKS: usize = 12;
+    /// Index of the singly indirect block pointer.
+    pub(crate) const IND_BLOCK: usize = 12;
+    /// Index of the doubly indirect block pointer.
+    pub(crate) const DIND_BLOCK: usize = 13;
+    /// Index of the triply indirect block pointer.
+    pub(crate) const TIND_BLOCK: usize = 14;
+
+    /// Returns a new inode of the given type and permissions, with no blocks and no links.
+    pub(crate) fn new(file_type: FileType, permissions: u16, now: u32) -> Self {
+        let mut inode = Self::from_bytes(&[0; SuperBlock::GOOD_OLD_INODE_SIZE as usize]);
+        inode.mode = file_type.to_mode() | (permissions & 0o7777);
+        inode.atime = now;
+        inode.ctime = now;
+        inode.mtime = now;
+        inode
+    }
+
+    /// Returns the type of this file.
+    pub fn file_type(&self) -> FileType {
+        FileType::from_mode(self.mode)
+    }
+
+    /// Returns the access rights of this file, including the setuid, setgid and sticky bits.
+    pub fn permissions(&self) -> u16 {
+        self.mode & 0o7777
+    }
+
+    /// Returns the raw `mode` field, ie. file type and access rights.
+    pub fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    /// Returns the size of this file in bytes.
+    pub fn size(&self) -> u64 {
+        if self.file_type() == FileType::RegularFile {
+            (self.size_high as u64) << 32 | self.size as u64
+        } else {
+            self.size as u64
+        }
+    }
+
+    /// Returns the owner's user id.
+    pub fn uid(&self) -> u16 {
+        self.uid
+    }
+
+    /// Returns the owner's group id.
+    pub fn gid(&self) -> u16 {
+        self.gid
+    }
+
+    /// Returns the time of last access, in seconds since the epoch.
+    pub fn atime(&self) -> u32 {
+        self.atime
+    }
+
+    /// Returns the time of creation, in seconds since the epoch.
+    pub fn ctime(&self) -> u32 {
+        self.ctime
+    }
+
+    /// Returns the time of last modification, in seconds since the epoch.
+    pub fn mtime(&self) -> u32 {
+        self.mtime
+    }
+
+    /// Returns the time of deletion, in seconds since the epoch.
+    pub fn dtime(&self) -> u32 {
+        self.dtime
+    }
+
+    /// Returns the number of hard links to this file.
+    pub fn links_count(&self) -> u16 {
+        self.links_count
+    }
+
+    /// Returns the number of 512-byte sectors allocated to this file.
+    pub fn sectors(&self) -> u32 {
+        self.blocks
+    }
+
+    /// Returns the inode flags.
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Returns the block holding the extended attributes of this file, or 0 if there is none.
+    pub(crate) fn file_acl(&self) -> u32 {
+        self.file_acl
+    }
+
+    /// Returns the raw block pointers.
+    pub(crate) fn block(&self) -> [u32; 15] {
+        self.block
+    }
+
+    pub(crate) fn set_block(&mut self, index: usize, block: u32) {
+        let mut blocks = self.block;
+        blocks[index] = block;
+        self.block = blocks;
+    }
+
+    pub(crate) fn set_size(&mut self, size: u64) {
+        self.size = size as u32;
+        if self.file_type() == FileType::RegularFile {
+            self.size_high = (size >> 32) as u32;
+        }
+    }
+
+    pub(crate) fn set_links_count(&mut self, links_count: u16) {
+        self.links_count = links_count;
+    }
+
+    pub(crate) fn set_sectors(&mut self, sectors: u32) {
+        self.blocks = sectors;
+    }
+
+    pub(crate) fn set_flags(&mut self, flags: u32) {
+        self.flags = flags;
+    }
+
+    pub(crate) fn set_file_acl(&mut self, block: u32) {
+        self.file_acl = block;
+    }
+
+    pub(crate) fn set_dtime(&mut self, dtime: u32) {
+        self.dtime = dtime;
+    }
+
+    /// Marks the contents of this inode as modified at time `now`.
+    pub(crate) fn touch(&mut self, now: u32) {
+        self.mtime = now;
+        self.ctime = now;
+    }
+
+    /// Marks the metadata of this inode as modified at time `now`.
+    pub(crate) fn touch_metadata(&mut self, now: u32) {
+        self.ctime = now;
+    }
+
+    /// Returns `true` if the contents of this inode are stored in its block pointers.
+    ///
+    /// Fast symlinks have no data block, but may still own an attribute block, which is counted
+    /// in their sectors, on a file system with blocks of `block_size` bytes.
+    pub(crate) fn is_fast_symlink(&self, block_size: u32) -> bool {
+        let xattr_sectors = if self.file_acl != 0 {
+            block_size / 512
+        } else {
+            0
+        };
+        self.file_type() == FileType::Symlink && self.blocks == xattr_sectors
+    }
+}
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Translates the `index`-th block of a file into a block number on disk.
+    ///
+    /// Returns 0 if the block falls into a hole.
+    pub(crate) fn map_block(&self, inode: &Inode, index: u64) -> Result<u32, Error<IO::Error>> {
+        let mut inode = *inode;
+        self.bmap(0, &mut inode, index, false)
+    }
+
+    /// Translates the `index`-th block of inode `ino` into a block number on disk.
+    ///
+    /// If `create` is true, missing data and indirect blocks are allocated and zeroed, and
+    /// `inode` is updated accordingly. It is up to the caller to write `inode` back to disk.
+    /// Otherwise, 0 is returned for blocks falling into holes.
+    pub(crate) fn bmap(
+        &self,
+        ino: u32,
+        inode: &mut Inode,
+        index: u64,
+        create: bool,
+    ) -> Result<u32, Error<IO::Error>> {
+        let ptrs = self.block_size() as u64 / 4;
+        let sectors_per_block = self.block_size() / 512;
+        let goal = ino.saturating_sub(1) / self.inodes_per_group();
+
+        // Find the block pointer in the inode covering `index`, and how many data blocks are
+        // reachable from it
+        let (slot, mut index, mut span) = if index < Inode::DIRECT_BLOCKS as u64 {
+            (index as usize, 0, 1)
+        } else {
+            let mut index = index - Inode::DIRECT_BLOCKS as u64;
+            let mut span = ptrs;
+            let mut slot = Inode::IND_BLOCK;
+            while index >= span {
+                if slot == Inode::TIND_BLOCK {
+                    return Err(Error::FileTooLarge);
+                }
+                index -= span;
+                span *= ptrs;
+                slot += 1;
+            }
+            (slot, index, span)
+        };
+
+        let mut block = inode.block()[slot];
+        if block == 0 {
+            if !create {
+                return Ok(0);
+            }
+            block = self.alloc_block(goal)?;
+            inode.set_block(slot, block);
+            inode.set_sectors(inode.sectors() + sectors_per_block);
+        }
+
+        // Walk down the indirect blocks
+        while span > 1 {
+            if block >= self.blocks_count() {
+                return Err(Error::CorruptedFileSystem);
+            }
+
+            span /= ptrs;
+
+            let mut buf = [0; 4];
+            let ptr_offset = block as u64 * self.block_size() as u64 + (index / span) * 4;
+            self.read_at(ptr_offset, &mut buf)?;
+            index %= span;
+
+            let mut child = u32::from_le_bytes(buf);
+            if child == 0 {
+                if !create {
+                    return Ok(0);
+                }
+                child = self.alloc_block(goal)?;
+                self.write_at(ptr_offset, &child.to_le_bytes())?;
+                inode.set_sectors(inode.sectors() + sectors_per_block);
+            }
+
+            block = child;
+        }
+
+        if block >= self.blocks_count() {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        Ok(block)
+    }
+
+    /// Returns the largest file size supported by the file system.
+    ///
+    /// Files are limited both by the number of blocks reachable from the inode and by the number
+    /// of sectors, indirect blocks included, which fits into `Inode::sectors`.
+    pub(crate) fn max_file_size(&self) -> u64 {
+        let block_size = self.block_size() as u64;
+        let ptrs = block_size / 4;
+
+        let blocks = Inode::DIRECT_BLOCKS as u64 + ptrs + ptrs * ptrs + ptrs * ptrs * ptrs;
+        let indirect_blocks = 1 + (1 + ptrs) + (1 + ptrs + ptrs * ptrs);
+        let sector_limit = u32::MAX as u64 / (block_size / 512) - indirect_blocks;
+
+        blocks.min(sector_limit) * block_size
+    }
+
+    /// Changes the size of inode `ino` to `size` bytes.
+    ///
+    /// Shrinking frees all the blocks past the new end of file, while growing only updates the
+    /// size, leaving a hole. `inode` is written back to disk.
+    pub(crate) fn truncate(
+        &self,
+        ino: u32,
+        inode: &mut Inode,
+        size: u64,
+    ) -> Result<(), Error<IO::Error>> {
+        if size > self.max_file_size() {
+            return Err(Error::FileTooLarge);
+        }
+
+        let block_size = self.block_size() as u64;
+
+        if size < inode.size() && !inode.is_fast_symlink(self.block_size()) {
+            self.free_blocks_from(inode, size.div_ceil(block_size))?;
+
+            // Clear the tail of the last block, so that it reads as zeroes if the file grows again
+            let tail = size % block_size;
+            if tail != 0 {
+                let block = self.map_block(inode, size / block_size)?;
+                if block != 0 {
+                    let zeroes = vec![0; (block_size - tail) as usize];
+                    self.write_at(block as u64 * block_size + tail, &zeroes)?;
+                }
+            }
+        }
+
+        inode.set_size(size);
+        inode.touch(self.now());
+        self.write_inode(ino, inode)
+    }
+
+    /// Frees all the data blocks of `inode` starting from the `first`-th one, together with the
+    /// indirect blocks which become empty.
+    fn free_blocks_from(&self, inode: &mut Inode, first: u64) -> Result<(), Error<IO::Error>> {
+        let ptrs = self.block_size() as u64 / 4;
+        let sectors_per_block = self.block_size() / 512;
+        let blocks = inode.block();
+
+        for (i, &block) in blocks
+            .iter()
+            .enumerate()
+            .take(Inode::DIRECT_BLOCKS)
+            .skip(first as usize)
+        {
+            if block != 0 {
+                self.free_block(block)?;
+                inode.set_block(i, 0);
+                inode.set_sectors(inode.sectors() - sectors_per_block);
+            }
+        }
+
+        let mut base = Inode::DIRECT_BLOCKS as u64;
+        let mut span = ptrs;
+
+        for (slot, depth) in [
+            (Inode::IND_BLOCK, 1),
+            (Inode::DIND_BLOCK, 2),
+            (Inode::TIND_BLOCK, 3),
+        ] {
+            if first < base + span
+                && blocks[slot] != 0
+                && self.free_tree(inode, blocks[slot], depth, first.saturating_sub(base))?
+            {
+                inode.set_block(slot, 0);
+            }
+
+            base += span;
+            span *= ptrs;
+        }
+
+        Ok(())
+    }
+
+    /// Frees the data blocks starting from the `start`-th one in the tree of indirect blocks
+    /// rooted at `block`, which has `depth` levels.
+    ///
+    /// Returns `true` if the whole tree, including `block`, has been freed.
+    fn free_tree(
+        &self,
+        inode: &mut Inode,
+        block: u32,
+        depth: u32,
+        start: u64,
+    ) -> Result<bool, Error<IO::Error>> {
+        let ptrs = self.block_size() as u64 / 4;
+        let span = ptrs.pow(depth - 1);
+        let sectors_per_block = self.block_size() / 512;
+
+        let mut buf = vec![0; self.block_size() as usize];
+        self.read_block(block, &mut buf)?;
+
+        let first_slot = start / span;
+
+        for (slot, ptr) in buf
+            .as_chunks_mut::<4>()
+            .0
+            .iter_mut()
+            .enumerate()
+            .skip(first_slot as usize)
+        {
+            let child = u32::from_le_bytes(*ptr);
+            if child == 0 {
+                continue;
+            }
+
+            let freed = if depth == 1 {
+                self.free_block(child)?;
+                inode.set_sectors(inode.sectors() - sectors_per_block);
+                true
+            } else {
+                let child_start = if slot as u64 == first_slot {
+                    start % span
+                } else {
+                    0
+                };
+                self.free_tree(inode, child, depth - 1, child_start)?
+            };
+
+            if freed {
+                *ptr = [0; 4];
+            }
+        }
+
+        if start == 0 {
+            self.free_block(block)?;
+            inode.set_sectors(inode.sectors() - sectors_per_block);
+            Ok(true)
+        } else {
+            self.write_block(block, &buf)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Type of a file, as encoded in the upper bits of the inode `mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Fifo,
+    CharDevice,
+    Directory,
+    BlockDevice,
+    RegularFile,
+    Symlink,
+    Socket,
+    Unknown,
+}
+
+impl FileType {
+    fn to_mode(self) -> u16 {
+        match self {
+            FileType::Fifo => 0x1000,
+            FileType::CharDevice => 0x2000,
+            FileType::Directory => 0x4000,
+            FileType::BlockDevice => 0x6000,
+            FileType::RegularFile => 0x8000,
+            FileType::Symlink => 0xa000,
+            FileType::Socket => 0xc000,
+            FileType::Unknown => 0,
+        }
+    }
+
+    fn from_mode(mode: u16) -> Self {
+        match mode & 0xf000 {
+            0x1000 => FileType::Fifo,
+            0x2000 => FileType::CharDevice,
+            0x4000 => FileType::Directory,
+            0x6000 => FileType::BlockDevice,
+            0x8000 => FileType::RegularFile,
+            0xa000 => FileType::Symlink,
+            0xc000 => FileType::Socket,
+            _ => FileType::Unknown,
+        }
+    }
+}
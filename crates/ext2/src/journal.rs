@@ -0,0 +1,376 @@
+//! Recovery of the ext3 journal, see `fs/jbd2/recovery.c` in Linux.
+//!
+//! The journal is a circular log stored in an inode. Each transaction is a series of
+//! descriptor blocks, each followed by the blocks it describes, and ends with a commit block.
+//! Transactions which reached their commit block before a crash are copied to their final
+//! location, unless a later transaction revoked some of their blocks. All the fields of the
+//! journal are big-endian.
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use crate::{
+    blocks::SuperBlock,
+    error::Error,
+    fs::FileSystem,
+    inode::Inode,
+    io::{Read, Seek, Write},
+};
+
+/// Magic number found at the beginning of every metadata block of the journal.
+const JBD2_MAGIC: u32 = 0xC03B3998;
+
+const DESCRIPTOR_BLOCK: u32 = 1;
+const COMMIT_BLOCK: u32 = 2;
+const SUPERBLOCK_V1: u32 = 3;
+const SUPERBLOCK_V2: u32 = 4;
+const REVOKE_BLOCK: u32 = 5;
+
+/// Size of the header shared by all metadata blocks.
+const HEADER_SIZE: usize = 12;
+/// Size of the header of revoke blocks, including the number of bytes used.
+const REVOKE_HEADER_SIZE: usize = 16;
+
+/// Revoke blocks may be used.
+const FEATURE_INCOMPAT_REVOKE: u32 = 0x1;
+/// Block numbers are 64-bit.
+const FEATURE_INCOMPAT_64BIT: u32 = 0x2;
+/// Incompatible features understood by this implementation.
+///
+/// Checksummed journals are not supported, as their superblock couldn't be updated without
+/// computing checksums, and neither are asynchronous commits, whose commit block may be
+/// written before the rest of the transaction.
+const FEATURE_INCOMPAT_SUPP: u32 = FEATURE_INCOMPAT_REVOKE | FEATURE_INCOMPAT_64BIT;
+
+/// The first 4 bytes of the logged block were the magic number, and have been zeroed.
+const FLAG_ESCAPE: u16 = 0x1;
+/// The tag is not followed by a UUID, which is the same as in the previous tag.
+const FLAG_SAME_UUID: u16 = 0x2;
+/// This is the last tag of the descriptor block.
+const FLAG_LAST_TAG: u16 = 0x8;
+
+fn be32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Returns `true` if transaction `a` comes after `b` or is `b`, taking wrapping into account.
+fn tid_geq(a: u32, b: u32) -> bool {
+    a.wrapping_sub(b) as i32 >= 0
+}
+
+/// What a pass over the log does.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    /// Find the end of the log.
+    Scan,
+    /// Collect the revoked blocks.
+    Revoke,
+    /// Copy the logged blocks to their final location.
+    Replay,
+}
+
+/// The journal inode and the contents of its superblock.
+struct Journal {
+    inode: Inode,
+    /// Raw journal superblock, written back once the log has been replayed
+    sb: Vec<u8>,
+    /// First block of the log
+    first: u32,
+    /// Number of blocks of the journal, including its superblock
+    maxlen: u32,
+    /// Block of the log holding the oldest transaction, or 0 if the log is empty
+    start: u32,
+    /// Sequence number of the oldest transaction
+    sequence: u32,
+    /// Incompatible features
+    incompat: u32,
+}
+
+impl Journal {
+    /// Returns the block of the log following `block`.
+    fn next(&self, block: u32) -> u32 {
+        if block + 1 >= self.maxlen {
+            self.first
+        } else {
+            block + 1
+        }
+    }
+
+    /// Returns the size of the tags of descriptor blocks.
+    fn tag_size(&self) -> usize {
+        if self.incompat & FEATURE_INCOMPAT_64BIT != 0 {
+            12
+        } else {
+            8
+        }
+    }
+
+    /// Returns the file system blocks logged by descriptor block `buf`, with their flags.
+    fn tags<E>(&self, buf: &[u8]) -> Result<Vec<(u32, u16)>, Error<E>> {
+        let tag_size = self.tag_size();
+        let mut tags = Vec::new();
+        let mut offset = HEADER_SIZE;
+
+        while offset + tag_size <= buf.len() {
+            let block = be32(buf, offset);
+            let flags = u16::from_be_bytes([buf[offset + 6], buf[offset + 7]]);
+            if tag_size == 12 && be32(buf, offset + 8) != 0 {
+                return Err(Error::CorruptedFileSystem);
+            }
+
+            tags.push((block, flags));
+            offset += tag_size;
+            if flags & FLAG_SAME_UUID == 0 {
+                offset += 16;
+            }
+            if flags & FLAG_LAST_TAG != 0 {
+                break;
+            }
+        }
+
+        Ok(tags)
+    }
+
+    /// Returns the file system blocks revoked by revoke block `buf`.
+    fn revoked<E>(&self, buf: &[u8]) -> Result<Vec<u32>, Error<E>> {
+        let count = be32(buf, HEADER_SIZE) as usize;
+        if count < REVOKE_HEADER_SIZE || count > buf.len() {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        let record_size = if self.incompat & FEATURE_INCOMPAT_64BIT != 0 {
+            8
+        } else {
+            4
+        };
+        let mut blocks = Vec::new();
+        for record in buf[REVOKE_HEADER_SIZE..count].chunks_exact(record_size) {
+            if record_size == 8 && be32(record, 0) != 0 {
+                return Err(Error::CorruptedFileSystem);
+            }
+            blocks.push(be32(record, record_size - 4));
+        }
+
+        Ok(blocks)
+    }
+}
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Replays the committed transactions left in the journal of an ext3 file system, and
+    /// marks the journal as empty.
+    ///
+    /// This is done when mounting a file system which needs recovery, but may also be requested
+    /// explicitly, eg. for a journal which was filled without setting the `needs_recovery`
+    /// feature. Returns the number of committed transactions replayed, or 0 if there is no
+    /// journal or it is empty.
+    ///
+    /// Checksums are not verified: a transaction is trusted as soon as its commit block is
+    /// found. External journals are not supported.
+    pub fn replay_journal(&self) -> Result<u32, Error<IO::Error>> {
+        let (has_journal, needs_recovery, journal_inum) = {
+            let sb = self.sb.borrow();
+            (
+                sb.has_compat(SuperBlock::FEATURE_COMPAT_HAS_JOURNAL),
+                sb.has_incompat(SuperBlock::FEATURE_INCOMPAT_RECOVER),
+                sb.journal_inum,
+            )
+        };
+
+        if !has_journal || journal_inum == 0 {
+            if needs_recovery {
+                return Err(Error::UnsupportedFeature(
+                    SuperBlock::FEATURE_INCOMPAT_RECOVER,
+                ));
+            }
+            return Ok(0);
+        }
+
+        let mut journal = self.load_journal(journal_inum)?;
+        if journal.start == 0 && !needs_recovery {
+            return Ok(0);
+        }
+        self.check_writable()?;
+
+        let mut count = 0;
+        if journal.start != 0 {
+            let mut revoked = BTreeMap::new();
+            let end = self.journal_pass(&journal, Pass::Scan, None, &mut revoked)?;
+            self.journal_pass(&journal, Pass::Revoke, Some(end), &mut revoked)?;
+            self.journal_pass(&journal, Pass::Replay, Some(end), &mut revoked)?;
+            count = end.wrapping_sub(journal.sequence);
+
+            // Skip the transaction which may have been partially written, so that its blocks
+            // are never mistaken for a newer transaction
+            journal.start = 0;
+            journal.sequence = end.wrapping_add(1);
+            journal.sb[0x18..0x1c].copy_from_slice(&journal.sequence.to_be_bytes());
+            journal.sb[0x1c..0x20].copy_from_slice(&journal.start.to_be_bytes());
+            self.write_journal_block(&journal, 0, &journal.sb)?;
+
+            // The superblock and the group descriptors may have been replayed
+            self.reload_metadata()?;
+        }
+
+        self.sb.borrow_mut().feature_incompat &= !SuperBlock::FEATURE_INCOMPAT_RECOVER;
+        self.write_superblock()?;
+        self.flush()?;
+
+        Ok(count)
+    }
+
+    /// Reads and validates the superblock of the journal stored in inode `ino`.
+    fn load_journal(&self, ino: u32) -> Result<Journal, Error<IO::Error>> {
+        let inode = self.inode(ino)?;
+        let block_size = self.block_size();
+
+        let mut sb = vec![0; block_size as usize];
+        let block = self.map_block(&inode, 0)?;
+        if block == 0 {
+            return Err(Error::CorruptedFileSystem);
+        }
+        self.read_block(block, &mut sb)?;
+
+        let blocktype = be32(&sb, 4);
+        if be32(&sb, 0) != JBD2_MAGIC || !matches!(blocktype, SUPERBLOCK_V1 | SUPERBLOCK_V2) {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        let maxlen = be32(&sb, 0x10);
+        let first = be32(&sb, 0x14);
+        let start = be32(&sb, 0x1c);
+        if be32(&sb, 0xc) != block_size
+            || maxlen as u64 > inode.size() / block_size as u64
+            || first == 0
+            || first >= maxlen
+            || (start != 0 && (start < first || start >= maxlen))
+        {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        // Version 1 superblocks have no feature flags
+        let incompat = if blocktype == SUPERBLOCK_V2 {
+            be32(&sb, 0x28)
+        } else {
+            0
+        };
+        let unsupported = incompat & !FEATURE_INCOMPAT_SUPP;
+        if unsupported != 0 {
+            return Err(Error::UnsupportedJournalFeature(unsupported));
+        }
+
+        Ok(Journal {
+            inode,
+            first,
+            maxlen,
+            start,
+            sequence: be32(&sb, 0x18),
+            incompat,
+            sb,
+        })
+    }
+
+    /// Walks the log from its oldest transaction, up to transaction `end` or, when scanning,
+    /// to the first block which doesn't belong to the expected transaction.
+    ///
+    /// Returns the sequence number of the first transaction which was not committed.
+    fn journal_pass(
+        &self,
+        journal: &Journal,
+        pass: Pass,
+        end: Option<u32>,
+        revoked: &mut BTreeMap<u32, u32>,
+    ) -> Result<u32, Error<IO::Error>> {
+        let mut buf = vec![0; self.block_size() as usize];
+        let mut data = vec![0; self.block_size() as usize];
+        let mut sequence = journal.sequence;
+        let mut block = journal.start;
+
+        // A valid log never goes around more than once
+        let mut budget = journal.maxlen - journal.first;
+        let mut next = |block: &mut u32| {
+            let current = *block;
+            *block = journal.next(current);
+            budget = budget.checked_sub(1).ok_or(Error::CorruptedFileSystem)?;
+            Ok::<_, Error<IO::Error>>(current)
+        };
+
+        while end != Some(sequence) {
+            let current = next(&mut block)?;
+            self.read_journal_block(journal, current, &mut buf)?;
+
+            if be32(&buf, 0) != JBD2_MAGIC || be32(&buf, 8) != sequence {
+                // Later passes stop at the end found by the scan
+                if pass != Pass::Scan {
+                    return Err(Error::CorruptedFileSystem);
+                }
+                break;
+            }
+
+            match be32(&buf, 4) {
+                DESCRIPTOR_BLOCK => {
+                    for (fs_block, flags) in journal.tags(&buf)? {
+                        let current = next(&mut block)?;
+                        if pass != Pass::Replay {
+                            continue;
+                        }
+
+                        let is_revoked = revoked
+                            .get(&fs_block)
+                            .is_some_and(|&revoked_in| tid_geq(revoked_in, sequence));
+                        if is_revoked {
+                            continue;
+                        }
+
+                        self.read_journal_block(journal, current, &mut data)?;
+                        if flags & FLAG_ESCAPE != 0 {
+                            data[0..4].copy_from_slice(&JBD2_MAGIC.to_be_bytes());
+                        }
+                        self.write_block(fs_block, &data)?;
+                    }
+                }
+                COMMIT_BLOCK => sequence = sequence.wrapping_add(1),
+                REVOKE_BLOCK => {
+                    if pass == Pass::Revoke {
+                        for fs_block in journal.revoked(&buf)? {
+                            // Only the latest revocation matters
+                            revoked.insert(fs_block, sequence);
+                        }
+                    }
+                }
+                _ => {
+                    if pass != Pass::Scan {
+                        return Err(Error::CorruptedFileSystem);
+                    }
+                    break;
+                }
+            }
+        }
+
+        Ok(sequence)
+    }
+
+    /// Reads block `block` of the journal into `buf`.
+    fn read_journal_block(
+        &self,
+        journal: &Journal,
+        block: u32,
+        buf: &mut [u8],
+    ) -> Result<(), Error<IO::Error>> {
+        match self.map_block(&journal.inode, block as u64)? {
+            0 => Err(Error::CorruptedFileSystem),
+            block => self.read_block(block, buf),
+        }
+    }
+
+    /// Writes block `block` of the journal from `buf`.
+    fn write_journal_block(
+        &self,
+        journal: &Journal,
+        block: u32,
+        buf: &[u8],
+    ) -> Result<(), Error<IO::Error>> {
+        match self.map_block(&journal.inode, block as u64)? {
+            0 => Err(Error::CorruptedFileSystem),
+            block => self.write_block(block, buf),
+        }
+    }
+}
@@ -1,9 +1,34 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+mod acl;
+mod bitmap;
 mod blocks;
+pub mod check;
+mod device;
+mod dir;
 mod error;
+mod file;
+mod format;
 mod fs;
+mod hash;
+mod htree;
 mod inode;
 mod io;
+mod journal;
+mod namei;
+mod time;
+mod xattr;
 
+pub use crate::acl::*;
+pub use crate::device::*;
+pub use crate::dir::*;
+pub use crate::error::*;
+pub use crate::file::*;
+pub use crate::format::*;
 pub use crate::fs::*;
+pub use crate::inode::{FileType, Inode};
+pub use crate::io::*;
+pub use crate::time::*;
+pub use crate::xattr::*;
@@ -0,0 +1,262 @@
+use crate::{
+    dir::MAX_NAME_LEN,
+    error::Error,
+    file::File,
+    fs::FileSystem,
+    inode::{FileType, Inode},
+    io::{Read, Seek, Write},
+};
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Creates an empty regular file at `path` and opens it.
+    ///
+    /// The parent directory must exist, and `path` must not.
+    pub fn create(&self, path: &str, permissions: u16) -> Result<File<'_, IO>, Error<IO::Error>> {
+        self.check_writable()?;
+
+        let (parent, name) = self.resolve_parent(path)?;
+        let mut parent_inode = self.inode(parent)?;
+
+        if self.dir(parent)?.find(name)?.is_some() {
+            return Err(Error::AlreadyExists);
+        }
+
+        let ino = self.alloc_inode(self.group_of(parent), false)?;
+
+        let mut inode = Inode::new(FileType::RegularFile, permissions, self.now());
+        inode.set_links_count(1);
+
+        let res = self.clear_inode(ino).and_then(|()| {
+            self.write_inode(ino, &inode)?;
+            self.add_entry(parent, &mut parent_inode, name, ino, FileType::RegularFile)
+        });
+        self.release_on_error(ino, inode, res)?;
+
+        Ok(File::new(self, ino, inode))
+    }
+
+    /// Creates an empty directory at `path`, returning its inode number.
+    ///
+    /// The parent directory must exist, and `path` must not.
+    pub fn mkdir(&self, path: &str, permissions: u16) -> Result<u32, Error<IO::Error>> {
+        self.check_writable()?;
+
+        let (parent, name) = self.resolve_parent(path)?;
+        let mut parent_inode = self.inode(parent)?;
+
+        if self.dir(parent)?.find(name)?.is_some() {
+            return Err(Error::AlreadyExists);
+        }
+
+        let ino = self.alloc_inode(self.group_of(parent), true)?;
+
+        // Linked from the parent and from its own `.` entry
+        let mut inode = Inode::new(FileType::Directory, permissions, self.now());
+        inode.set_links_count(2);
+
+        let res = (|| {
+            self.clear_inode(ino)?;
+
+            // Account for the block as soon as it's mapped, so that it's released on failure
+            let block = self.bmap(ino, &mut inode, 0, true)?;
+            inode.set_size(self.block_size() as u64);
+
+            self.init_dir_block(block, ino, parent)?;
+            self.write_inode(ino, &inode)?;
+
+            // The `..` entry of the new directory links to the parent
+            parent_inode.set_links_count(parent_inode.links_count() + 1);
+            self.add_entry(parent, &mut parent_inode, name, ino, FileType::Directory)
+        })();
+        self.release_on_error(ino, inode, res)?;
+
+        Ok(ino)
+    }
+
+    /// Removes the file at `path`, which must not be a directory.
+    ///
+    /// The inode and its blocks are released when its last link is removed.
+    pub fn unlink(&self, path: &str) -> Result<(), Error<IO::Error>> {
+        self.check_writable()?;
+
+        let (parent, name) = self.resolve_parent(path)?;
+        let entry = self.dir(parent)?.find(name)?.ok_or(Error::NotFound)?;
+
+        let inode = self.inode(entry.ino())?;
+        if inode.file_type() == FileType::Directory {
+            return Err(Error::IsADirectory);
+        }
+
+        let mut parent_inode = self.inode(parent)?;
+        self.remove_entry(parent, &mut parent_inode, name)?;
+
+        self.drop_link(entry.ino(), inode)
+    }
+
+    /// Removes the empty directory at `path`.
+    pub fn rmdir(&self, path: &str) -> Result<(), Error<IO::Error>> {
+        self.check_writable()?;
+
+        let (parent, name) = self.resolve_parent(path)?;
+        let entry = self.dir(parent)?.find(name)?.ok_or(Error::NotFound)?;
+
+        let inode = self.inode(entry.ino())?;
+        if inode.file_type() != FileType::Directory {
+            return Err(Error::NotADirectory);
+        }
+        if !self.is_dir_empty(entry.ino())? {
+            return Err(Error::DirectoryIsNotEmpty);
+        }
+
+        let mut parent_inode = self.inode(parent)?;
+        parent_inode.set_links_count(parent_inode.links_count() - 1);
+        self.remove_entry(parent, &mut parent_inode, name)?;
+
+        self.release_inode(entry.ino(), inode)
+    }
+
+    /// Moves the file or directory at `from` to `to`.
+    ///
+    /// If `to` already exists it is replaced, provided that it's not a directory or that it is
+    /// an empty directory and `from` is a directory too.
+    pub fn rename(&self, from: &str, to: &str) -> Result<(), Error<IO::Error>> {
+        self.check_writable()?;
+
+        let (src_parent, src_name) = self.resolve_parent(from)?;
+        let (dst_parent, dst_name) = self.resolve_parent(to)?;
+
+        let src = self
+            .dir(src_parent)?
+            .find(src_name)?
+            .ok_or(Error::NotFound)?;
+        let mut inode = self.inode(src.ino())?;
+        let file_type = inode.file_type();
+        let is_dir = file_type == FileType::Directory;
+
+        // A directory can't be moved inside itself
+        if is_dir {
+            let mut cur = dst_parent;
+            while cur != Inode::ROOT_INO {
+                if cur == src.ino() {
+                    return Err(Error::InvalidInput);
+                }
+                cur = self
+                    .dir(cur)?
+                    .find(b"..")?
+                    .ok_or(Error::CorruptedFileSystem)?
+                    .ino();
+            }
+        }
+
+        if let Some(dst) = self.dir(dst_parent)?.find(dst_name)? {
+            if dst.ino() == src.ino() {
+                return Ok(());
+            }
+
+            let dst_inode = self.inode(dst.ino())?;
+            let mut dst_parent_inode = self.inode(dst_parent)?;
+
+            match (is_dir, dst_inode.file_type() == FileType::Directory) {
+                (true, true) => {
+                    if !self.is_dir_empty(dst.ino())? {
+                        return Err(Error::DirectoryIsNotEmpty);
+                    }
+                    dst_parent_inode.set_links_count(dst_parent_inode.links_count() - 1);
+                    self.remove_entry(dst_parent, &mut dst_parent_inode, dst_name)?;
+                    self.release_inode(dst.ino(), dst_inode)?;
+                }
+                (true, false) => return Err(Error::NotADirectory),
+                (false, true) => return Err(Error::IsADirectory),
+                (false, false) => {
+                    self.remove_entry(dst_parent, &mut dst_parent_inode, dst_name)?;
+                    self.drop_link(dst.ino(), dst_inode)?;
+                }
+            }
+        }
+
+        let moves_dir = is_dir && src_parent != dst_parent;
+
+        let mut dst_parent_inode = self.inode(dst_parent)?;
+        if moves_dir {
+            dst_parent_inode.set_links_count(dst_parent_inode.links_count() + 1);
+        }
+        self.add_entry(
+            dst_parent,
+            &mut dst_parent_inode,
+            dst_name,
+            src.ino(),
+            file_type,
+        )?;
+
+        let mut src_parent_inode = self.inode(src_parent)?;
+        if moves_dir {
+            src_parent_inode.set_links_count(src_parent_inode.links_count() - 1);
+        }
+        self.remove_entry(src_parent, &mut src_parent_inode, src_name)?;
+
+        if moves_dir {
+            self.relink_entry(&inode, b"..", dst_parent)?;
+        }
+
+        inode.touch_metadata(self.now());
+        self.write_inode(src.ino(), &inode)
+    }
+
+    /// Splits an absolute path into the inode number of its parent directory and its last
+    /// component, which must be a valid file name.
+    fn resolve_parent<'p>(&self, path: &'p str) -> Result<(u32, &'p [u8]), Error<IO::Error>> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').ok_or(Error::InvalidInput)?;
+
+        if name.is_empty() || name == "." || name == ".." || name.len() > MAX_NAME_LEN {
+            return Err(Error::InvalidInput);
+        }
+
+        let parent = self.lookup(if parent.is_empty() { "/" } else { parent })?;
+        Ok((parent, name.as_bytes()))
+    }
+
+    /// Returns the group containing inode `ino`.
+    fn group_of(&self, ino: u32) -> u32 {
+        (ino - 1) / self.inodes_per_group()
+    }
+
+    /// Removes a link to inode `ino`, releasing it if it was the last one.
+    fn drop_link(&self, ino: u32, mut inode: Inode) -> Result<(), Error<IO::Error>> {
+        match inode.links_count() {
+            0 | 1 => self.release_inode(ino, inode),
+            n => {
+                inode.set_links_count(n - 1);
+                inode.touch_metadata(self.now());
+                self.write_inode(ino, &inode)
+            }
+        }
+    }
+
+    /// Releases inode `ino`, which has just been allocated as `inode`, if `res` is an error, so
+    /// that failing to link it, eg. on a full disk, doesn't leave it orphaned.
+    fn release_on_error(
+        &self,
+        ino: u32,
+        inode: Inode,
+        res: Result<(), Error<IO::Error>>,
+    ) -> Result<(), Error<IO::Error>> {
+        if let Err(err) = res {
+            self.release_inode(ino, inode)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Frees all the blocks of inode `ino`, including its attribute block, and the inode itself.
+    fn release_inode(&self, ino: u32, mut inode: Inode) -> Result<(), Error<IO::Error>> {
+        self.truncate(ino, &mut inode, 0)?;
+        self.release_xattr_block(&mut inode)?;
+
+        inode.set_links_count(0);
+        inode.set_dtime(self.now());
+        self.write_inode(ino, &inode)?;
+
+        self.free_inode(ino, inode.file_type() == FileType::Directory)
+    }
+}
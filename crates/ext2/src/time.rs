@@ -0,0 +1,24 @@
+/// A source of timestamps for inodes and the superblock.
+pub trait TimeProvider: core::fmt::Debug {
+    /// Returns the current time, in seconds since the Unix epoch.
+    fn now(&self) -> u32;
+}
+
+/// Uses the system clock when the `std` feature is enabled, or a constant zero otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTimeProvider;
+
+impl TimeProvider for DefaultTimeProvider {
+    #[cfg(feature = "std")]
+    fn now(&self) -> u32 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or(0)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn now(&self) -> u32 {
+        0
+    }
+}
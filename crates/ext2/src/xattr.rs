@@ -0,0 +1,206 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    error::Error,
+    fs::FileSystem,
+    inode::Inode,
+    io::{Read, Seek, Write},
+};
+
+/// Magic number of attribute blocks, also found at the beginning of the in-inode attributes.
+const XATTR_MAGIC: u32 = 0xEA020000;
+/// Size of the header of an attribute block.
+const BLOCK_HEADER_SIZE: usize = 32;
+/// Size of the fixed part of an attribute entry, ie. without the name.
+const ENTRY_HEADER_SIZE: usize = 16;
+
+/// Returns the prefix of the names of attributes with name index `index`.
+///
+/// Attributes with an unknown index are ignored, like Linux does.
+fn name_prefix(index: u8) -> Option<&'static [u8]> {
+    match index {
+        1 => Some(b"user."),
+        2 => Some(b"system.posix_acl_access"),
+        3 => Some(b"system.posix_acl_default"),
+        4 => Some(b"trusted."),
+        6 => Some(b"security."),
+        7 => Some(b"system."),
+        _ => None,
+    }
+}
+
+/// An extended attribute of a file.
+#[derive(Clone)]
+pub struct Xattr {
+    name: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl Xattr {
+    /// Returns the full name of this attribute, including its namespace (eg. `user.`).
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// Returns the full name of this attribute, or `None` if it is not valid UTF-8.
+    pub fn name(&self) -> Option<&str> {
+        core::str::from_utf8(&self.name).ok()
+    }
+
+    /// Returns the value of this attribute.
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+impl core::fmt::Debug for Xattr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Xattr")
+            .field("name", &self.name())
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// Parses the list of attribute entries starting at `offset` in `buf`, whose values are
+/// located relatively to `value_base`.
+fn parse_entries<E>(
+    buf: &[u8],
+    mut offset: usize,
+    value_base: usize,
+    attrs: &mut Vec<Xattr>,
+) -> Result<(), Error<E>> {
+    loop {
+        // The list ends with 4 zero bytes
+        let hdr = buf
+            .get(offset..offset + 4)
+            .ok_or(Error::CorruptedFileSystem)?;
+        if hdr == [0; 4] {
+            return Ok(());
+        }
+
+        let hdr = buf
+            .get(offset..offset + ENTRY_HEADER_SIZE)
+            .ok_or(Error::CorruptedFileSystem)?;
+        let name_len = hdr[0] as usize;
+        let name_index = hdr[1];
+        let value_offs = u16::from_le_bytes(hdr[2..4].try_into().unwrap()) as usize;
+        let value_inum = u32::from_le_bytes(hdr[4..8].try_into().unwrap());
+        let value_size = u32::from_le_bytes(hdr[8..12].try_into().unwrap()) as usize;
+
+        // Values stored in their own inode need the `ea_inode` feature
+        if value_inum != 0 {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        let name_start = offset + ENTRY_HEADER_SIZE;
+        let name = buf
+            .get(name_start..name_start + name_len)
+            .ok_or(Error::CorruptedFileSystem)?;
+        let value_start = value_base + value_offs;
+        let value = buf
+            .get(value_start..value_start + value_size)
+            .ok_or(Error::CorruptedFileSystem)?;
+
+        if let Some(prefix) = name_prefix(name_index) {
+            attrs.push(Xattr {
+                name: [prefix, name].concat(),
+                value: value.to_vec(),
+            });
+        }
+
+        offset = (name_start + name_len).next_multiple_of(4);
+    }
+}
+
+impl<IO: Read + Write + Seek> FileSystem<IO> {
+    /// Returns the extended attributes of inode `ino`.
+    ///
+    /// Attributes stored in the extra space of large inodes come first, followed by those of
+    /// the attribute block of the inode.
+    pub fn xattrs(&self, ino: u32) -> Result<Vec<Xattr>, Error<IO::Error>> {
+        let mut attrs = Vec::new();
+        self.read_inode_xattrs(ino, &mut attrs)?;
+
+        let block = self.inode(ino)?.file_acl();
+        if block != 0 {
+            let buf = self.read_xattr_block(block)?;
+            parse_entries(&buf, BLOCK_HEADER_SIZE, 0, &mut attrs)?;
+        }
+
+        Ok(attrs)
+    }
+
+    /// Returns the value of the extended attribute of inode `ino` named `name`, including its
+    /// namespace (eg. `user.mime_type`), or `None` if there is no such attribute.
+    pub fn get_xattr(&self, ino: u32, name: &str) -> Result<Option<Vec<u8>>, Error<IO::Error>> {
+        let attr = self
+            .xattrs(ino)?
+            .into_iter()
+            .find(|attr| attr.name_bytes() == name.as_bytes());
+        Ok(attr.map(|attr| attr.value))
+    }
+
+    /// Parses the attributes stored after the fixed part of inode `ino`.
+    fn read_inode_xattrs(&self, ino: u32, attrs: &mut Vec<Xattr>) -> Result<(), Error<IO::Error>> {
+        let inode_size = self.inode_size() as usize;
+        let base_size = size_of::<Inode>();
+        if inode_size <= base_size {
+            return Ok(());
+        }
+
+        let mut extra = vec![0; inode_size - base_size];
+        self.read_at(self.inode_offset(ino)? + base_size as u64, &mut extra)?;
+
+        // The extra space starts with the size of the extra fields, followed by the attributes
+        let extra_isize = u16::from_le_bytes([extra[0], extra[1]]) as usize;
+        let Some(area) = extra.get(extra_isize..) else {
+            return Err(Error::CorruptedFileSystem);
+        };
+        if extra_isize.is_multiple_of(4)
+            && area.len() >= 8
+            && u32::from_le_bytes(area[0..4].try_into().unwrap()) == XATTR_MAGIC
+        {
+            // Values are located relatively to the first entry
+            parse_entries(area, 4, 4, attrs)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads and validates attribute block `block`.
+    fn read_xattr_block(&self, block: u32) -> Result<Vec<u8>, Error<IO::Error>> {
+        let mut buf = vec![0; self.block_size() as usize];
+        self.read_block(block, &mut buf)?;
+
+        let magic = u32::from_le_bytes(buf[0..4].try_into().unwrap());
+        let blocks = u32::from_le_bytes(buf[8..12].try_into().unwrap());
+        if magic != XATTR_MAGIC || blocks != 1 {
+            return Err(Error::CorruptedFileSystem);
+        }
+
+        Ok(buf)
+    }
+
+    /// Drops the reference of `inode` to its attribute block, which is freed when it's not
+    /// shared with other inodes anymore.
+    pub(crate) fn release_xattr_block(&self, inode: &mut Inode) -> Result<(), Error<IO::Error>> {
+        let block = inode.file_acl();
+        if block == 0 {
+            return Ok(());
+        }
+
+        let mut buf = self.read_xattr_block(block)?;
+        let refcount = u32::from_le_bytes(buf[4..8].try_into().unwrap());
+        if refcount <= 1 {
+            self.free_block(block)?;
+        } else {
+            buf[4..8].copy_from_slice(&(refcount - 1).to_le_bytes());
+            self.write_block(block, &buf)?;
+        }
+
+        inode.set_file_acl(0);
+        inode.set_sectors(inode.sectors().saturating_sub(self.block_size() / 512));
+        Ok(())
+    }
+}
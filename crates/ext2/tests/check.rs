@@ -0,0 +1,203 @@
+use std::io::Cursor;
+
+use ext2::{Write, check::Problem};
+
+static EXT2_IMG: &str = "tests/data/ext2.img";
+static EXT2_REV1_IMG: &str = "tests/data/ext2-rev1.img";
+static EXT2_HTREE_IMG: &str = "tests/data/ext2-htree.img";
+
+// Locations in `EXT2_IMG`, as reported by `dumpe2fs` and `debugfs -R "stat /"`
+const BLOCK_SIZE: usize = 1024;
+const BLOCK_BITMAP: usize = 3;
+const INODE_BITMAP: usize = 4;
+const INODE_TABLE: usize = 5;
+const ROOT_DIR_BLOCK: usize = 9;
+const HELLO_INO: u32 = 19;
+
+type FileSystem = ext2::FileSystem<ext2::StdIoWrapper<Cursor<Vec<u8>>>>;
+
+fn mount(data: Vec<u8>) -> FileSystem {
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
+
+fn read_image(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap()
+}
+
+/// Returns the byte offset of the record named `name` in the root directory.
+fn root_entry_offset(data: &[u8], name: &[u8]) -> usize {
+    let block = &data[ROOT_DIR_BLOCK * BLOCK_SIZE..][..BLOCK_SIZE];
+    let mut offset = 0;
+    loop {
+        let rec_len = u16::from_le_bytes([block[offset + 4], block[offset + 5]]) as usize;
+        let name_len = block[offset + 6] as usize;
+        if &block[offset + 8..][..name_len] == name {
+            return ROOT_DIR_BLOCK * BLOCK_SIZE + offset;
+        }
+        offset += rec_len;
+    }
+}
+
+#[test]
+fn clean_images() {
+    for path in [EXT2_IMG, EXT2_REV1_IMG, EXT2_HTREE_IMG] {
+        let report = mount(read_image(path)).check(false).unwrap();
+        assert!(report.is_clean(), "{path}: {:?}", report.problems);
+    }
+}
+
+#[test]
+fn fast_symlink_with_xattr_block() {
+    // `/xlink` owns an attribute block, and its target must not be walked as block pointers
+    let data = read_image(EXT2_REV1_IMG);
+    let fs = mount(data.clone());
+    let report = fs.check(true).unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert!(fs.into_inner().into_inner().into_inner() == data);
+}
+
+#[test]
+fn clean_after_writes() {
+    let fs = mount(read_image(EXT2_IMG));
+    fs.mkdir("/a", 0o755).unwrap();
+    fs.mkdir("/a/b", 0o755).unwrap();
+    fs.create("/a/b/file", 0o644)
+        .unwrap()
+        .write_all(&[1; 50000])
+        .unwrap();
+    fs.rename("/dir/sub", "/a/sub").unwrap();
+    fs.unlink("/big").unwrap();
+
+    let report = fs.check(false).unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+}
+
+#[test]
+fn wrong_counters() {
+    let mut data = read_image(EXT2_IMG);
+    // Superblock free blocks, and free inodes of the first group
+    data[1024 + 12..1024 + 16].copy_from_slice(&1000_u32.to_le_bytes());
+    data[2048 + 14..2048 + 16].copy_from_slice(&3_u16.to_le_bytes());
+
+    let fs = mount(data);
+    let report = fs.check(false).unwrap();
+    assert_eq!(
+        report.problems,
+        [
+            Problem::GroupFreeInodes {
+                group: 0,
+                found: 3,
+                expected: 7,
+            },
+            Problem::FreeBlocks {
+                found: 1000,
+                expected: 1684,
+            },
+        ]
+    );
+    assert_eq!(report.remaining().count(), 2);
+
+    let report = fs.check(true).unwrap();
+    assert_eq!(report.problems.len(), 2);
+    assert_eq!(report.remaining().count(), 0);
+
+    assert!(fs.check(false).unwrap().is_clean());
+    assert_eq!(fs.free_blocks_count(), 1684);
+}
+
+#[test]
+fn stray_bitmap_bits() {
+    let mut data = read_image(EXT2_IMG);
+    // Mark the last block and inode of the first group as used, and free the root directory
+    data[BLOCK_BITMAP * BLOCK_SIZE + 63] |= 0x80;
+    data[INODE_BITMAP * BLOCK_SIZE + 3] |= 0x80;
+    data[INODE_BITMAP * BLOCK_SIZE] &= !0x02;
+
+    let fs = mount(data);
+    let report = fs.check(false).unwrap();
+    assert_eq!(
+        report.problems,
+        [
+            Problem::InodeBitmap {
+                ino: 2,
+                in_use: true,
+            },
+            Problem::InodeBitmap {
+                ino: 32,
+                in_use: false,
+            },
+            Problem::BlockBitmap {
+                block: 512,
+                in_use: false,
+            },
+        ]
+    );
+
+    fs.check(true).unwrap();
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn wrong_link_count() {
+    let mut data = read_image(EXT2_IMG);
+    let links = INODE_TABLE * BLOCK_SIZE + (HELLO_INO as usize - 1) * 128 + 26;
+    data[links..links + 2].copy_from_slice(&5_u16.to_le_bytes());
+
+    let fs = mount(data);
+    assert_eq!(
+        fs.check(false).unwrap().problems,
+        [Problem::LinkCount {
+            ino: HELLO_INO,
+            found: 5,
+            expected: 1,
+        }]
+    );
+
+    fs.check(true).unwrap();
+    assert_eq!(fs.inode(HELLO_INO).unwrap().links_count(), 1);
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn orphaned_inode() {
+    let mut data = read_image(EXT2_IMG);
+    // Clear the inode number of the entry, leaving the inode unreachable
+    let entry = root_entry_offset(&data, b"hello.txt");
+    data[entry..entry + 4].fill(0);
+
+    let fs = mount(data);
+    let report = fs.check(true).unwrap();
+    assert_eq!(report.problems, [Problem::OrphanedInode { ino: HELLO_INO }]);
+    assert_eq!(report.remaining().count(), 1);
+
+    // The orphan keeps its blocks
+    assert_eq!(fs.check(false).unwrap().problems, report.problems);
+}
+
+#[test]
+fn bad_record_length() {
+    let mut data = read_image(EXT2_IMG);
+    let entry = root_entry_offset(&data, b"hello.txt");
+    data[entry + 4..entry + 6].copy_from_slice(&6_u16.to_le_bytes());
+
+    let report = mount(data).check(false).unwrap();
+    assert!(report.problems.contains(&Problem::BadRecordLength {
+        dir: 2,
+        offset: (entry - ROOT_DIR_BLOCK * BLOCK_SIZE) as u64,
+    }));
+    assert!(report.remaining().count() > 0);
+}
+
+#[test]
+fn bad_entry_inode() {
+    let mut data = read_image(EXT2_IMG);
+    let entry = root_entry_offset(&data, b"link");
+    data[entry..entry + 4].copy_from_slice(&100_u32.to_le_bytes());
+
+    let report = mount(data).check(false).unwrap();
+    assert!(report.problems.contains(&Problem::BadEntryInode {
+        dir: 2,
+        offset: (entry - ROOT_DIR_BLOCK * BLOCK_SIZE) as u64,
+        ino: 100,
+    }));
+}
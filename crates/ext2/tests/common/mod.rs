@@ -0,0 +1,36 @@
+//! Helpers shared by the integration tests.
+
+use std::{env, io::Cursor, process::Command};
+
+use ext2::StdIoWrapper;
+
+pub type FileSystem = ext2::FileSystem<StdIoWrapper<Cursor<Vec<u8>>>>;
+
+/// Environment variable skipping the runs of `e2fsck` when set, for machines without e2fsprogs.
+const SKIP_FSCK_VAR: &str = "EXT2_SKIP_FSCK";
+
+/// Runs `e2fsck -fn` on the file system, panicking if it finds any problem, and mounts it again.
+///
+/// e2fsprogs must be installed, unless `EXT2_SKIP_FSCK` is set to skip the check.
+pub fn fsck(fs: FileSystem, name: &str) -> FileSystem {
+    let data = fs.into_inner().into_inner().into_inner();
+
+    if env::var_os(SKIP_FSCK_VAR).is_none() {
+        let path = env::temp_dir().join(format!("ext2-{}-{}.img", name, std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+
+        let res = Command::new("e2fsck").arg("-fn").arg(&path).output();
+        std::fs::remove_file(&path).unwrap();
+
+        let out = res.unwrap_or_else(|e| {
+            panic!("cannot run e2fsck ({e}), install e2fsprogs or set {SKIP_FSCK_VAR} to skip it")
+        });
+        assert!(
+            out.status.success(),
+            "e2fsck failed:\n{}",
+            String::from_utf8_lossy(&out.stdout)
+        );
+    }
+
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
@@ -0,0 +1,175 @@
+#!/bin/bash
+
+# Regenerate the ext2 images used by the integration tests.
+#
+# Requires e2fsprogs (mke2fs) and python3. The images are committed to the repository, so this
+# only needs to be run when their contents have to change.
+
+set -euo pipefail
+
+cd "$(dirname "$0")"
+
+ROOT=$(mktemp -d)
+trap 'rm -rf "$ROOT"' EXIT
+
+# Revision 0 image with 1 KiB blocks split over several groups.
+#
+# - `big` spans direct, single and double indirect blocks
+# - `sparse` has holes and a tail block reachable only through the triple indirect block
+# - a mix of fast, slow, relative, absolute and looping symlinks
+mkdir -p "$ROOT/rev0/bin" "$ROOT/rev0/dir/sub"
+printf 'Hello, ext2!\n' > "$ROOT/rev0/hello.txt"
+printf '\x13\x05\x00\x00\x93\x08\xa0\x02\x73\x00\x00\x00\x6f\x00\x00\x00' > "$ROOT/rev0/bin/init"
+printf 'nested\n' > "$ROOT/rev0/dir/sub/file"
+python3 - "$ROOT/rev0" <<'PY'
+import sys
+root = sys.argv[1]
+with open(f"{root}/big", "wb") as f:
+    f.write(bytes((i * 7 + i // 1024) % 256 for i in range(300 * 1024 + 123)))
+with open(f"{root}/sparse", "wb") as f:
+    f.write(b"head")
+    f.seek(100 * 1024)
+    f.write(b"middle")
+    f.seek(70 * 1024 * 1024)
+    f.write(b"tail")
+PY
+ln -s hello.txt "$ROOT/rev0/link"
+ln -s ../bin/init "$ROOT/rev0/dir/init-link"
+ln -s "/$(printf '%.0s./' {1..30})dir/sub/../../hello.txt" "$ROOT/rev0/slowlink"
+ln -s loop2 "$ROOT/rev0/loop1"
+ln -s loop1 "$ROOT/rev0/loop2"
+ln -s dir/sub "$ROOT/rev0/subdir-link"
+
+rm -f ext2.img
+mke2fs -q -t ext2 -r 0 -b 1024 -g 512 -N 128 -m 5 -U clear -E root_owner=0:0 \
+    -d "$ROOT/rev0" ext2.img 2048
+
+# Revision 1 image with 4 KiB blocks and 256-byte inodes, using the default ext2 features of
+# mke2fs (sparse_super, large_file, filetype, ...).
+#
+# - `large` is a sparse file larger than 4 GiB, whose size needs `size_high`
+mkdir -p "$ROOT/rev1/dir"
+printf 'Hello, rev 1!\n' > "$ROOT/rev1/hello.txt"
+printf 'nested\n' > "$ROOT/rev1/dir/file"
+python3 - "$ROOT/rev1" <<'PY'
+import sys
+root = sys.argv[1]
+with open(f"{root}/large", "wb") as f:
+    f.write(b"head")
+    f.seek(5 * 1024 * 1024 * 1024)
+    f.write(b"tail")
+PY
+ln -s dir/file "$ROOT/rev1/link"
+
+rm -f ext2-rev1.img
+mke2fs -q -t ext2 -b 4096 -g 256 -N 128 -I 256 -m 5 -L rev1-test \
+    -U 01234567-89ab-cdef-0123-456789abcdef -E root_owner=0:0 \
+    -d "$ROOT/rev1" ext2-rev1.img 1024
+
+# Extended attributes of the revision 1 image.
+#
+# Small attributes fit in the extra space of the inode, while the ACLs and `user.big` go to an
+# attribute block. ACLs are given to debugfs in the format of the VFS, which it converts.
+# `xlink` is a fast symlink owning an attribute block, as labelled symlinks often do.
+python3 - "$ROOT" <<'PY'
+import struct, sys
+root = sys.argv[1]
+def acl(path, entries):
+    with open(f"{root}/{path}", "wb") as f:
+        f.write(struct.pack("<I", 2))
+        for tag, perm, id in entries:
+            f.write(struct.pack("<HHI", tag, perm, id))
+acl("access-acl", [(0x01, 6, 0), (0x02, 4, 1000), (0x04, 4, 0), (0x08, 6, 100),
+                   (0x10, 6, 0), (0x20, 4, 0)])
+acl("default-acl", [(0x01, 7, 0), (0x04, 5, 0), (0x20, 5, 0)])
+open(f"{root}/big-value", "wb").write(bytes(i % 251 for i in range(512)))
+PY
+debugfs -w -f - ext2-rev1.img > /dev/null 2>&1 <<EOF
+ea_set /hello.txt user.comment hello
+ea_set /hello.txt security.selinux system_u:object_r:etc_t:s0
+ea_set -f $ROOT/access-acl /hello.txt system.posix_acl_access
+ea_set -f $ROOT/big-value /hello.txt user.big
+ea_set -f $ROOT/default-acl /dir system.posix_acl_default
+symlink /xlink dir/file
+ea_set -f $ROOT/big-value /xlink user.big
+EOF
+
+# Image with large hash-indexed directories, one for each hash algorithm.
+#
+# Each directory holds 2000 long names, some of them not ASCII, all hard links to the same file,
+# so that the index has a level of interior nodes. The indexes are built by `e2fsck -D` with the
+# default hash of the superblock, which is changed in between to rebuild some of them.
+for dir in half_md4 tea legacy; do
+    mkdir -p "$ROOT/htree/$dir"
+done
+python3 - "$ROOT/htree" <<'PY'
+import os, sys
+root = sys.argv[1]
+for d in ["half_md4", "tea", "legacy"]:
+    target = f"{root}/{d}/target"
+    open(target, "w").write("target\n")
+    for i in range(2000):
+        name = f"café-{i:04}" if i % 10 == 0 else f"file-{i:04}-" + "x" * 70
+        os.link(target, f"{root}/{d}/{name}")
+PY
+
+# Makes e2fsck rebuild the index of directory $2 with hash $3, by breaking its hash version
+rehash() {
+    local block
+    block=$(debugfs -R "bmap $2 0" "$1" 2>/dev/null)
+    printf '\xff' | dd of="$1" bs=1 seek=$((block * 1024 + 28)) conv=notrunc status=none
+    debugfs -w -R "ssv def_hash_version $3" "$1" 2>/dev/null
+    e2fsck -fy "$1" > /dev/null 2>&1 || [ $? -eq 1 ]
+}
+
+rm -f ext2-htree.img
+mke2fs -q -t ext2 -b 1024 -N 512 -m 0 -U 76543210-fedc-ba98-7654-3210fedcba98 \
+    -E root_owner=0:0,hash_seed=00112233-4455-6677-8899-aabbccddeeff \
+    -d "$ROOT/htree" ext2-htree.img 1536
+e2fsck -fyD ext2-htree.img > /dev/null 2>&1 || [ $? -eq 1 ]
+rehash ext2-htree.img tea tea
+rehash ext2-htree.img legacy legacy
+debugfs -w -R "ssv def_hash_version half_md4" ext2-htree.img 2>/dev/null
+
+# ext3 image whose journal holds transactions which were never checkpointed, as if the host had
+# crashed right after committing them. debugfs writes them and leaves `needs_recovery` set:
+#
+# 1. new contents for `hello.txt` and `magic`, whose data starts with the journal magic number
+#    and must be escaped, and a superblock with a new label
+# 2. new contents for `revoked.txt`
+# 3. a revocation of the block of `revoked.txt`
+# 4. new contents for `uncommitted.txt`, without a commit block
+mkdir -p "$ROOT/ext3"
+printf 'before crash\n' > "$ROOT/ext3/hello.txt"
+printf 'magic before\n' > "$ROOT/ext3/magic"
+printf 'revoked before\n' > "$ROOT/ext3/revoked.txt"
+printf 'uncommitted before\n' > "$ROOT/ext3/uncommitted.txt"
+
+rm -f ext3-journal.img
+mke2fs -q -t ext3 -b 1024 -J size=1 -N 64 -m 0 -L before -O ^resize_inode \
+    -U 89abcdef-0123-4567-89ab-cdef01234567 -E root_owner=0:0 \
+    -d "$ROOT/ext3" ext3-journal.img 4096
+
+bmap() {
+    debugfs -R "bmap $1 0" ext3-journal.img 2>/dev/null
+}
+python3 - "$ROOT" ext3-journal.img <<'PY'
+import sys
+root, img = sys.argv[1], sys.argv[2]
+def block(data):
+    return data.ljust(1024, b"\0")
+sb = bytearray(open(img, "rb").read()[1024:2048])
+sb[120:136] = b"after".ljust(16, b"\0")
+open(f"{root}/t1", "wb").write(
+    block(b"after replay\n") + block(b"\xc0\x3b\x39\x98magic after\n") + bytes(sb))
+open(f"{root}/t2", "wb").write(block(b"revoked after\n"))
+open(f"{root}/t4", "wb").write(block(b"uncommitted after\n"))
+PY
+debugfs -w -f - ext3-journal.img > /dev/null 2>&1 <<EOF
+jo
+jw -b $(bmap hello.txt),$(bmap magic),1 $ROOT/t1
+jw -b $(bmap revoked.txt) $ROOT/t2
+jw -r $(bmap revoked.txt)
+jw -b $(bmap uncommitted.txt) -c $ROOT/t4
+jc
+EOF
@@ -0,0 +1,207 @@
+use std::process::Command;
+
+use ext2::{BlockDevice, BlockDeviceIo, Read, Seek, SeekFrom, Write};
+
+static EXT2_IMG: &str = "tests/data/ext2.img";
+
+/// A RAM disk which counts the requests it receives.
+struct MemDisk {
+    data: Vec<u8>,
+    sector_size: usize,
+    reads: usize,
+    writes: usize,
+}
+
+impl MemDisk {
+    fn new(data: Vec<u8>, sector_size: usize) -> Self {
+        assert_eq!(data.len() % sector_size, 0);
+        Self {
+            data,
+            sector_size,
+            reads: 0,
+            writes: 0,
+        }
+    }
+
+    fn range(&self, lba: u64, len: usize) -> std::ops::Range<usize> {
+        assert_eq!(len % self.sector_size, 0, "partial sector transfer");
+        let start = lba as usize * self.sector_size;
+        start..start + len
+    }
+}
+
+impl BlockDevice for MemDisk {
+    type Error = std::io::Error;
+
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn sector_count(&self) -> u64 {
+        (self.data.len() / self.sector_size) as u64
+    }
+
+    fn read_sectors(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.reads += 1;
+        buf.copy_from_slice(&self.data[self.range(lba, buf.len())]);
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, lba: u64, buf: &[u8]) -> Result<(), Self::Error> {
+        self.writes += 1;
+        let range = self.range(lba, buf.len());
+        self.data[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Lets an adapter borrow the disk, so that it can be inspected once the adapter is dropped.
+impl BlockDevice for &mut MemDisk {
+    type Error = std::io::Error;
+
+    fn sector_size(&self) -> usize {
+        (**self).sector_size()
+    }
+
+    fn sector_count(&self) -> u64 {
+        (**self).sector_count()
+    }
+
+    fn read_sectors(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Self::Error> {
+        (**self).read_sectors(lba, buf)
+    }
+
+    fn write_sectors(&mut self, lba: u64, buf: &[u8]) -> Result<(), Self::Error> {
+        (**self).write_sectors(lba, buf)
+    }
+}
+
+type FileSystem = ext2::FileSystem<BlockDeviceIo<MemDisk>>;
+
+fn open_ext2_fs(sector_size: usize) -> FileSystem {
+    let data = std::fs::read(EXT2_IMG).unwrap();
+    ext2::FileSystem::new(MemDisk::new(data, sector_size)).unwrap()
+}
+
+fn read_all(fs: &FileSystem, path: &str) -> Vec<u8> {
+    let mut file = fs.open(path).unwrap();
+    let mut data = vec![0; file.len() as usize];
+    file.read_exact(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn read_through_cache() {
+    for sector_size in [512, 1024, 4096, 8192] {
+        let fs = open_ext2_fs(sector_size);
+
+        assert_eq!(read_all(&fs, "/hello.txt"), b"Hello, ext2!\n");
+        assert_eq!(read_all(&fs, "/dir/sub/file"), b"nested\n");
+        assert_eq!(read_all(&fs, "/link"), b"Hello, ext2!\n");
+
+        let big = read_all(&fs, "/big");
+        assert_eq!(big.len(), 300 * 1024 + 123);
+        assert!(
+            big.iter()
+                .enumerate()
+                .all(|(i, &b)| b == ((i * 7 + i / 1024) % 256) as u8)
+        );
+    }
+}
+
+#[test]
+fn cache_hits() {
+    let fs = open_ext2_fs(512);
+    read_all(&fs, "/hello.txt");
+    let reads = fs.into_inner().into_inner().unwrap().reads;
+
+    // Reading the same file again is served from the cache
+    let fs = open_ext2_fs(512);
+    for _ in 0..10 {
+        read_all(&fs, "/hello.txt");
+    }
+    assert_eq!(fs.into_inner().into_inner().unwrap().reads, reads);
+}
+
+#[test]
+fn write_back_on_into_inner() {
+    let fs = open_ext2_fs(512);
+    fs.create("/small.txt", 0o644)
+        .unwrap()
+        .write_all(b"tiny")
+        .unwrap();
+
+    let disk = fs.into_inner().into_inner().unwrap();
+    assert!(disk.writes > 0);
+
+    let fs = ext2::FileSystem::new(MemDisk::new(disk.data, 4096)).unwrap();
+    assert_eq!(read_all(&fs, "/small.txt"), b"tiny");
+}
+
+#[test]
+fn fsck_after_writes() {
+    let fs = open_ext2_fs(512);
+
+    fs.mkdir("/a", 0o755).unwrap();
+    for i in 0..20 {
+        let mut file = fs.create(&format!("/a/file-{i}"), 0o644).unwrap();
+        file.write_all(&vec![i as u8; 3000 * i]).unwrap();
+    }
+    fs.rename("/hello.txt", "/a/hello.txt").unwrap();
+    fs.unlink("/big").unwrap();
+    fs.flush().unwrap();
+
+    let data = fs.into_inner().into_inner().unwrap().data;
+
+    let path = std::env::temp_dir().join(format!("ext2-device-{}.img", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+    let res = Command::new("e2fsck").arg("-fn").arg(&path).output();
+    std::fs::remove_file(&path).unwrap();
+
+    match res {
+        Ok(out) => assert!(
+            out.status.success(),
+            "e2fsck failed:\n{}",
+            String::from_utf8_lossy(&out.stdout)
+        ),
+        Err(e) => eprintln!("skipping e2fsck: {e}"),
+    }
+
+    let fs = ext2::FileSystem::new(MemDisk::new(data, 512)).unwrap();
+    for i in 0..20 {
+        assert_eq!(
+            read_all(&fs, &format!("/a/file-{i}")),
+            vec![i as u8; 3000 * i]
+        );
+    }
+}
+
+#[test]
+fn write_back_on_drop() {
+    let mut disk = MemDisk::new(vec![0; 16 * 512], 512);
+    let mut io = BlockDeviceIo::new(&mut disk);
+    io.seek(SeekFrom::Start(5000)).unwrap();
+    io.write_all(b"dirty").unwrap();
+    drop(io);
+
+    assert_eq!(disk.writes, 1);
+    assert_eq!(&disk.data[5000..5005], b"dirty");
+}
+
+#[test]
+fn seek_and_bounds() {
+    let mut io = BlockDeviceIo::new(MemDisk::new(vec![0xaa; 3 * 512], 512));
+    assert_eq!(io.len(), 1536);
+
+    assert_eq!(io.seek(SeekFrom::End(-2)).unwrap(), 1534);
+    io.write_all(b"xy").unwrap();
+    assert!(io.write_all(b"z").is_err());
+
+    let mut buf = [0; 4];
+    io.seek(SeekFrom::Current(-4)).unwrap();
+    io.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"\xaa\xaaxy");
+    assert_eq!(io.read(&mut buf).unwrap(), 0);
+
+    assert!(io.seek(SeekFrom::Current(-2000)).is_err());
+}
@@ -0,0 +1,164 @@
+use std::io::Cursor;
+
+use ext2::{Error, FileType, FormatOptions, Inode, Read, StdIoWrapper, Write};
+
+mod common;
+
+use common::{FileSystem, fsck};
+
+/// Formats a blank image of `size` bytes and mounts it.
+fn format(size: usize, options: FormatOptions) -> FileSystem {
+    let mut disk = StdIoWrapper::new(Cursor::new(vec![0xa5; size]));
+    ext2::format(&mut disk, options).unwrap();
+    ext2::FileSystem::new(disk).unwrap()
+}
+
+#[test]
+fn format_block_sizes() {
+    for block_size in [1024, 2048, 4096] {
+        let options = FormatOptions {
+            block_size: Some(block_size),
+            ..Default::default()
+        };
+        let fs = fsck(format(8 << 20, options), &format!("bs{block_size}"));
+
+        assert_eq!(fs.block_size(), block_size);
+        assert_eq!(fs.blocks_count(), (8 << 20) / block_size);
+        assert_eq!(fs.first_data_block(), (block_size == 1024) as u32);
+        assert!(fs.check(false).unwrap().is_clean());
+
+        let entries: Vec<_> = fs
+            .dir(Inode::ROOT_INO)
+            .unwrap()
+            .iter()
+            .map(|e| e.unwrap().name_bytes().to_vec())
+            .collect();
+        assert_eq!(entries, [&b"."[..], b"..", b"lost+found"]);
+
+        let lost_found = fs.inode(fs.lookup("/lost+found").unwrap()).unwrap();
+        assert_eq!(lost_found.file_type(), FileType::Directory);
+        assert_eq!(lost_found.permissions(), 0o700);
+        assert_eq!(lost_found.links_count(), 2);
+        assert_eq!(fs.inode(Inode::ROOT_INO).unwrap().links_count(), 3);
+    }
+}
+
+#[test]
+fn format_many_groups() {
+    let options = FormatOptions {
+        blocks_per_group: Some(1024),
+        inodes_per_group: Some(64),
+        inode_size: 128,
+        ..Default::default()
+    };
+    let fs = format(30 << 20, options);
+
+    assert_eq!(fs.group_count(), 30);
+    assert_eq!(fs.inodes_per_group(), 64);
+    assert_eq!(fs.inode_size(), 128);
+    assert_eq!(fs.inodes_count(), 30 * 64);
+    assert_eq!(fs.reserved_blocks_count(), fs.blocks_count() / 20);
+
+    let fs = fsck(fs, "groups");
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn format_without_sparse_super() {
+    let options = FormatOptions {
+        blocks_per_group: Some(2048),
+        sparse_super: false,
+        ..Default::default()
+    };
+    let fs = fsck(format(16 << 20, options), "nosparse");
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn drop_small_last_group() {
+    // The last group would only hold 40 blocks, not enough for its own metadata
+    let options = FormatOptions {
+        blocks_per_group: Some(1024),
+        ..Default::default()
+    };
+    let fs = fsck(format((4 * 1024 + 41) * 1024, options), "last");
+
+    assert_eq!(fs.group_count(), 4);
+    assert_eq!(fs.blocks_count(), 4 * 1024 + 1);
+}
+
+#[test]
+fn label_and_uuid() {
+    let uuid = *b"0123456789abcdef";
+    let options = FormatOptions {
+        label: "rv6",
+        uuid,
+        ..Default::default()
+    };
+    let fs = format(4 << 20, options);
+
+    assert_eq!(fs.volume_name(), b"rv6");
+    assert_eq!(fs.uuid(), uuid);
+}
+
+#[test]
+fn write_after_format() {
+    let fs = format(8 << 20, FormatOptions::default());
+    let (free_blocks, free_inodes) = (fs.free_blocks_count(), fs.free_inodes_count());
+
+    fs.mkdir("/dir", 0o755).unwrap();
+    let mut file = fs.create("/dir/file", 0o644).unwrap();
+    file.write_all(&vec![7; 100_000]).unwrap();
+    fs.flush().unwrap();
+
+    let fs = fsck(fs, "write");
+    assert!(fs.check(false).unwrap().is_clean());
+    assert_eq!(fs.free_inodes_count(), free_inodes - 2);
+    assert!(fs.free_blocks_count() < free_blocks - 98);
+
+    let mut file = fs.open("/dir/file").unwrap();
+    let mut data = vec![0; file.len() as usize];
+    file.read_exact(&mut data).unwrap();
+    assert_eq!(data, vec![7; 100_000]);
+}
+
+#[test]
+fn invalid_options() {
+    let invalid = [
+        FormatOptions {
+            block_size: Some(3000),
+            ..Default::default()
+        },
+        FormatOptions {
+            blocks_count: Some(1 << 20),
+            ..Default::default()
+        },
+        FormatOptions {
+            blocks_per_group: Some(1004),
+            ..Default::default()
+        },
+        FormatOptions {
+            inode_size: 192,
+            ..Default::default()
+        },
+        FormatOptions {
+            label: "a label which is too long",
+            ..Default::default()
+        },
+    ];
+
+    for options in invalid {
+        let mut disk = StdIoWrapper::new(Cursor::new(vec![0; 4 << 20]));
+        assert!(matches!(
+            ext2::format(&mut disk, options),
+            Err(Error::InvalidInput)
+        ));
+    }
+
+    // Too small to hold a single group
+    let mut disk = StdIoWrapper::new(Cursor::new(vec![0; 32 * 1024]));
+    assert!(matches!(
+        ext2::format(&mut disk, FormatOptions::default()),
+        Err(Error::InvalidInput)
+    ));
+}
@@ -0,0 +1,133 @@
+use std::io::Cursor;
+
+use ext2::Read;
+
+mod common;
+
+use common::{FileSystem, fsck};
+
+static EXT3_JOURNAL_IMG: &str = "tests/data/ext3-journal.img";
+
+/// Offset of `feature_incompat` in the superblock.
+const FEATURE_INCOMPAT: usize = 1024 + 96;
+/// Offset of `feature_ro_compat` in the superblock.
+const FEATURE_RO_COMPAT: usize = 1024 + 100;
+/// `needs_recovery` feature.
+const INCOMPAT_RECOVER: u32 = 0x4;
+/// Byte offset of the journal superblock, the first block of inode 8.
+const JOURNAL_SB: usize = 34 * 1024;
+
+fn read_image() -> Vec<u8> {
+    std::fs::read(EXT3_JOURNAL_IMG).unwrap()
+}
+
+fn mount(data: Vec<u8>) -> Result<FileSystem, ext2::Error<std::io::Error>> {
+    ext2::FileSystem::new(Cursor::new(data))
+}
+
+fn update_u32(data: &mut [u8], offset: usize, f: impl FnOnce(u32) -> u32) {
+    let value = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    data[offset..offset + 4].copy_from_slice(&f(value).to_le_bytes());
+}
+
+fn read_all(fs: &FileSystem, path: &str) -> Vec<u8> {
+    let mut file = fs.open(path).unwrap();
+    let mut data = vec![0; file.len() as usize];
+    file.read_exact(&mut data).unwrap();
+    data
+}
+
+/// Checks that the committed transactions of the test image, and only them, were replayed.
+fn assert_replayed(fs: &FileSystem) {
+    assert_eq!(read_all(fs, "/hello.txt"), b"after replay\n");
+    // The block was escaped in the journal
+    assert_eq!(read_all(fs, "/magic"), b"\xc0\x3b\x39\x98magic aft");
+    assert_eq!(read_all(fs, "/revoked.txt"), b"revoked before\n");
+    assert_eq!(read_all(fs, "/uncommitted.txt"), b"uncommitted before\n");
+    // The superblock itself was journaled
+    assert_eq!(fs.volume_name(), b"after");
+    assert_eq!(fs.feature_incompat() & INCOMPAT_RECOVER, 0);
+}
+
+#[test]
+fn replay_on_mount() {
+    let fs = mount(read_image()).unwrap();
+    assert_replayed(&fs);
+    assert!(fs.check(false).unwrap().is_clean());
+
+    // The journal is now empty
+    let fs = fsck(fs, "mount");
+    assert_eq!(fs.replay_journal().unwrap(), 0);
+    assert_replayed(&fs);
+}
+
+#[test]
+fn replay_explicitly() {
+    // Without the `needs_recovery` flag, the journal is left alone when mounting
+    let mut data = read_image();
+    update_u32(&mut data, FEATURE_INCOMPAT, |f| f & !INCOMPAT_RECOVER);
+    let fs = mount(data).unwrap();
+    assert_eq!(read_all(&fs, "/hello.txt"), b"before crash\n");
+    assert_eq!(fs.volume_name(), b"before");
+
+    assert_eq!(fs.replay_journal().unwrap(), 3);
+    assert_replayed(&fs);
+    assert_eq!(fs.replay_journal().unwrap(), 0);
+
+    let fs = fsck(fs, "explicit");
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn write_after_replay() {
+    let fs = mount(read_image()).unwrap();
+    fs.mkdir("/dir", 0o755).unwrap();
+    fs.unlink("/hello.txt").unwrap();
+
+    let fs = fsck(fs, "write");
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn read_only_needs_recovery() {
+    let mut data = read_image();
+    update_u32(&mut data, FEATURE_RO_COMPAT, |f| f | 0x8000);
+    assert!(matches!(mount(data), Err(ext2::Error::ReadOnlyFileSystem)));
+}
+
+#[test]
+fn mount_read_only_without_replay() {
+    let data = read_image();
+    let fs = ext2::FileSystem::new_read_only(Cursor::new(data.clone())).unwrap();
+    assert!(fs.is_read_only());
+    assert!(fs.needs_recovery());
+    assert_eq!(read_all(&fs, "/hello.txt"), b"before crash\n");
+    assert!(matches!(
+        fs.replay_journal(),
+        Err(ext2::Error::ReadOnlyFileSystem)
+    ));
+
+    // The image is left untouched
+    assert_eq!(fs.into_inner().into_inner().into_inner(), data);
+}
+
+#[test]
+fn bad_journal() {
+    let data = read_image();
+
+    // Bad magic number
+    let mut corrupted = data.clone();
+    corrupted[JOURNAL_SB] ^= 0xff;
+    assert!(matches!(
+        mount(corrupted),
+        Err(ext2::Error::CorruptedFileSystem)
+    ));
+
+    // Checksummed journal
+    let mut checksummed = data.clone();
+    checksummed[JOURNAL_SB + 0x2b] |= 0x10;
+    assert!(matches!(
+        mount(checksummed),
+        Err(ext2::Error::UnsupportedJournalFeature(0x10))
+    ));
+}
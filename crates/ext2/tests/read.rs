@@ -1,9 +1,587 @@
 use std::io::Cursor;
 
+use ext2::{AclTag, FileType, Inode, Read, Seek, SeekFrom};
+
 static EXT2_IMG: &str = "tests/data/ext2.img";
 
+// Inode numbers in `EXT2_IMG`, as reported by `debugfs -R "ls -l /"`
+const BIG_INO: u32 = 12;
+const HELLO_INO: u32 = 19;
+const SPARSE_INO: u32 = 24;
+
+fn open_ext2_fs() -> ext2::FileSystem<ext2::StdIoWrapper<Cursor<Vec<u8>>>> {
+    let data = std::fs::read(EXT2_IMG).unwrap();
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
+
+fn read_to_end<R: Read>(r: &mut R) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut buf = [0; 700];
+    loop {
+        match r.read(&mut buf).unwrap() {
+            0 => return data,
+            n => data.extend_from_slice(&buf[..n]),
+        }
+    }
+}
+
 #[test]
 fn read_ext2_fs() {
+    let fs = open_ext2_fs();
+
+    assert_eq!(fs.revision(), 0);
+    assert_eq!(fs.block_size(), 1024);
+    assert_eq!(fs.blocks_count(), 2048);
+    assert_eq!(fs.free_blocks_count(), 1684);
+    assert_eq!(fs.reserved_blocks_count(), 102);
+    assert_eq!(fs.inodes_count(), 128);
+    assert_eq!(fs.free_inodes_count(), 103);
+    assert_eq!(fs.blocks_per_group(), 512);
+    assert_eq!(fs.inodes_per_group(), 32);
+    assert_eq!(fs.group_count(), 4);
+}
+
+#[test]
+fn bad_magic() {
+    let mut data = std::fs::read(EXT2_IMG).unwrap();
+    data[1024 + 56] = 0;
+
+    assert!(matches!(
+        ext2::FileSystem::new(Cursor::new(data)),
+        Err(ext2::Error::InvalidMagic)
+    ));
+}
+
+#[test]
+fn unsupported_revision() {
+    let mut data = std::fs::read(EXT2_IMG).unwrap();
+    data[1024 + 76] = 2;
+
+    assert!(matches!(
+        ext2::FileSystem::new(Cursor::new(data)),
+        Err(ext2::Error::UnsupportedRevision(2))
+    ));
+}
+
+#[test]
+fn corrupted_group_desc() {
+    let mut data = std::fs::read(EXT2_IMG).unwrap();
+    // Point the inode table of the first group past the end of the file system
+    data[2048 + 8..2048 + 12].copy_from_slice(&4096_u32.to_le_bytes());
+
+    assert!(matches!(
+        ext2::FileSystem::new(Cursor::new(data)),
+        Err(ext2::Error::CorruptedFileSystem)
+    ));
+}
+
+#[test]
+fn truncated_image() {
     let data = std::fs::read(EXT2_IMG).unwrap();
-    let _fs = ext2::FileSystem::new(Cursor::new(data));
+
+    assert!(ext2::FileSystem::new(Cursor::new(data[..1500].to_vec())).is_err());
+}
+
+#[test]
+fn read_inodes() {
+    let fs = open_ext2_fs();
+
+    let root = fs.inode(Inode::ROOT_INO).unwrap();
+    assert_eq!(root.file_type(), FileType::Directory);
+    assert_eq!(root.permissions(), 0o755);
+
+    let hello = fs.inode(HELLO_INO).unwrap();
+    assert_eq!(hello.file_type(), FileType::RegularFile);
+    assert_eq!(hello.size(), 13);
+    assert_eq!(hello.links_count(), 1);
+
+    assert!(matches!(fs.inode(0), Err(ext2::Error::InvalidInput)));
+    assert!(matches!(fs.inode(129), Err(ext2::Error::InvalidInput)));
+}
+
+#[test]
+fn read_small_file() {
+    let fs = open_ext2_fs();
+
+    let mut file = fs.file(HELLO_INO).unwrap();
+    assert_eq!(read_to_end(&mut file), b"Hello, ext2!\n");
+
+    file.seek(SeekFrom::Start(7)).unwrap();
+    assert_eq!(read_to_end(&mut file), b"ext2!\n");
+
+    file.seek(SeekFrom::End(-3)).unwrap();
+    assert_eq!(read_to_end(&mut file), b"2!\n");
+
+    assert!(file.seek(SeekFrom::Current(-100)).is_err());
+}
+
+#[test]
+fn read_indirect_blocks() {
+    let fs = open_ext2_fs();
+
+    // Spans direct, singly and doubly indirect blocks
+    let mut file = fs.file(BIG_INO).unwrap();
+    let expected = (0..300 * 1024 + 123)
+        .map(|i: usize| ((i * 7 + i / 1024) % 256) as u8)
+        .collect::<Vec<_>>();
+
+    assert_eq!(file.len(), expected.len() as u64);
+    assert_eq!(read_to_end(&mut file), expected);
+
+    // Read across the boundary between direct and indirect blocks
+    let mut buf = [0; 8];
+    file.seek(SeekFrom::Start(12 * 1024 - 4)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, expected[12 * 1024 - 4..12 * 1024 + 4]);
+}
+
+#[test]
+fn read_sparse_file() {
+    let fs = open_ext2_fs();
+
+    let mut file = fs.file(SPARSE_INO).unwrap();
+    assert_eq!(file.len(), 70 * 1024 * 1024 + 4);
+
+    let mut buf = [0xff; 4];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"head");
+
+    // Hole between two allocated blocks
+    file.seek(SeekFrom::Start(50 * 1024)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [0; 4]);
+
+    let mut buf = [0; 6];
+    file.seek(SeekFrom::Start(100 * 1024)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"middle");
+
+    // Hole in an unallocated doubly indirect block
+    file.seek(SeekFrom::Start(30 * 1024 * 1024)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [0; 6]);
+
+    // Last block is only reachable through the triply indirect block
+    let mut buf = [0; 4];
+    file.seek(SeekFrom::End(-4)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"tail");
+
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn iterate_root_dir() {
+    let fs = open_ext2_fs();
+
+    let mut names = fs
+        .root_dir()
+        .unwrap()
+        .iter()
+        .map(|e| e.unwrap().name().unwrap().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    assert_eq!(
+        names,
+        [
+            ".",
+            "..",
+            "big",
+            "bin",
+            "dir",
+            "hello.txt",
+            "link",
+            "loop1",
+            "loop2",
+            "lost+found",
+            "slowlink",
+            "sparse",
+            "subdir-link",
+        ]
+    );
+
+    let entry = fs.root_dir().unwrap().find(b"hello.txt").unwrap().unwrap();
+    assert_eq!(entry.ino(), HELLO_INO);
+    assert!(fs.root_dir().unwrap().find(b"missing").unwrap().is_none());
+
+    assert!(matches!(fs.dir(HELLO_INO), Err(ext2::Error::NotADirectory)));
+}
+
+#[test]
+fn lookup_paths() {
+    let fs = open_ext2_fs();
+
+    assert_eq!(fs.lookup("/").unwrap(), Inode::ROOT_INO);
+    assert_eq!(fs.lookup("/hello.txt").unwrap(), HELLO_INO);
+    assert_eq!(fs.lookup("//bin/../hello.txt").unwrap(), HELLO_INO);
+    assert_eq!(fs.lookup("/dir/sub/./../..").unwrap(), Inode::ROOT_INO);
+
+    let mut file = fs.open("/dir/sub/file").unwrap();
+    assert_eq!(read_to_end(&mut file), b"nested\n");
+
+    assert!(matches!(fs.lookup("/missing"), Err(ext2::Error::NotFound)));
+    assert!(matches!(
+        fs.lookup("/hello.txt/foo"),
+        Err(ext2::Error::NotADirectory)
+    ));
+    assert!(matches!(
+        fs.lookup("hello.txt"),
+        Err(ext2::Error::InvalidInput)
+    ));
+}
+
+#[test]
+fn follow_symlinks() {
+    let fs = open_ext2_fs();
+
+    // Fast symlinks
+    assert_eq!(
+        fs.read_link(fs.root_dir().unwrap().find(b"link").unwrap().unwrap().ino())
+            .unwrap(),
+        b"hello.txt"
+    );
+    assert_eq!(fs.lookup("/link").unwrap(), HELLO_INO);
+    assert_eq!(
+        fs.lookup("/dir/init-link").unwrap(),
+        fs.lookup("/bin/init").unwrap()
+    );
+
+    // Slow symlink with an absolute target
+    assert_eq!(fs.lookup("/slowlink").unwrap(), HELLO_INO);
+
+    // Symlink in the middle of a path
+    let mut file = fs.open("/subdir-link/file").unwrap();
+    assert_eq!(read_to_end(&mut file), b"nested\n");
+    assert_eq!(
+        fs.lookup("/subdir-link/..").unwrap(),
+        fs.lookup("/dir").unwrap()
+    );
+
+    assert!(matches!(
+        fs.lookup("/loop1"),
+        Err(ext2::Error::TooManySymlinks)
+    ));
+}
+
+static EXT2_REV1_IMG: &str = "tests/data/ext2-rev1.img";
+/// Attribute block of `/hello.txt` in the revision 1 image.
+const XATTR_BLOCK: usize = 51;
+
+fn open_rev1_fs() -> ext2::FileSystem<ext2::StdIoWrapper<Cursor<Vec<u8>>>> {
+    let data = std::fs::read(EXT2_REV1_IMG).unwrap();
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
+
+#[test]
+fn read_rev1_fs() {
+    let fs = open_rev1_fs();
+
+    assert_eq!(fs.revision(), 1);
+    assert_eq!(fs.block_size(), 4096);
+    assert_eq!(fs.first_data_block(), 0);
+    assert_eq!(fs.group_count(), 4);
+    assert_eq!(fs.inode_size(), 256);
+    assert_eq!(fs.first_ino(), 11);
+    assert_eq!(fs.volume_name(), b"rev1-test");
+    assert_eq!(
+        fs.uuid(),
+        [
+            0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab,
+            0xcd, 0xef
+        ]
+    );
+    // filetype, and sparse_super and large_file
+    assert_eq!(fs.feature_incompat(), 0x2);
+    assert_eq!(fs.feature_ro_compat(), 0x3);
+    assert!(!fs.is_read_only());
+
+    let mut file = fs.open("/link").unwrap();
+    assert_eq!(read_to_end(&mut file), b"nested\n");
+    let mut file = fs.open("/hello.txt").unwrap();
+    assert_eq!(read_to_end(&mut file), b"Hello, rev 1!\n");
+}
+
+#[test]
+fn rev1_file_types() {
+    let fs = open_rev1_fs();
+
+    let types = fs
+        .root_dir()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            let e = e.unwrap();
+            (e.name().unwrap().to_string(), e.file_type())
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+
+    assert_eq!(types["."], FileType::Directory);
+    assert_eq!(types["dir"], FileType::Directory);
+    assert_eq!(types["hello.txt"], FileType::RegularFile);
+    assert_eq!(types["link"], FileType::Symlink);
+
+    // Without the filetype feature, entries carry no type
+    let fs = open_ext2_fs();
+    let entry = fs.root_dir().unwrap().find(b"hello.txt").unwrap().unwrap();
+    assert_eq!(entry.file_type(), FileType::Unknown);
+}
+
+#[test]
+fn read_large_file() {
+    let fs = open_rev1_fs();
+
+    let mut file = fs.open("/large").unwrap();
+    assert_eq!(file.len(), 5 * 1024 * 1024 * 1024 + 4);
+
+    let mut buf = [0xff; 4];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"head");
+
+    file.seek(SeekFrom::Start(4 * 1024 * 1024 * 1024)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [0; 4]);
+
+    file.seek(SeekFrom::End(-4)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"tail");
+}
+
+#[test]
+fn read_xattrs() {
+    let fs = open_rev1_fs();
+    let hello = fs.lookup("/hello.txt").unwrap();
+
+    // In-inode attributes come first, then those of the attribute block
+    let attrs = fs.xattrs(hello).unwrap();
+    let names: Vec<_> = attrs.iter().map(|a| a.name().unwrap()).collect();
+    assert_eq!(
+        names,
+        [
+            "user.comment",
+            "security.selinux",
+            "user.big",
+            "system.posix_acl_access"
+        ]
+    );
+
+    assert_eq!(
+        fs.get_xattr(hello, "user.comment").unwrap().unwrap(),
+        b"hello"
+    );
+    assert_eq!(
+        fs.get_xattr(hello, "security.selinux").unwrap().unwrap(),
+        b"system_u:object_r:etc_t:s0"
+    );
+    let big: Vec<u8> = (0..512).map(|i| (i % 251) as u8).collect();
+    assert_eq!(fs.get_xattr(hello, "user.big").unwrap().unwrap(), big);
+
+    assert_eq!(fs.get_xattr(hello, "user.missing").unwrap(), None);
+    assert_eq!(fs.get_xattr(hello, "user.").unwrap(), None);
+    assert!(fs.xattrs(fs.lookup("/link").unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn read_fast_symlink_with_xattrs() {
+    let fs = open_rev1_fs();
+    let xlink = fs
+        .root_dir()
+        .unwrap()
+        .find(b"xlink")
+        .unwrap()
+        .unwrap()
+        .ino();
+
+    // The attribute block is counted in the sectors of the symlink, which has no data block
+    assert_eq!(fs.inode(xlink).unwrap().sectors(), 8);
+    assert_eq!(fs.read_link(xlink).unwrap(), b"dir/file");
+    assert_eq!(
+        fs.lookup("/xlink").unwrap(),
+        fs.lookup("/dir/file").unwrap()
+    );
+
+    let big: Vec<u8> = (0..512).map(|i| (i % 251) as u8).collect();
+    assert_eq!(fs.get_xattr(xlink, "user.big").unwrap().unwrap(), big);
+}
+
+#[test]
+fn read_acls() {
+    let fs = open_rev1_fs();
+    let hello = fs.lookup("/hello.txt").unwrap();
+    let dir = fs.lookup("/dir").unwrap();
+
+    let acl = fs.access_acl(hello).unwrap().unwrap();
+    let entries: Vec<_> = acl.entries().iter().map(|e| (e.tag, e.perm)).collect();
+    assert_eq!(
+        entries,
+        [
+            (AclTag::UserObj, 6),
+            (AclTag::User(1000), 4),
+            (AclTag::GroupObj, 4),
+            (AclTag::Group(100), 6),
+            (AclTag::Mask, 6),
+            (AclTag::Other, 4),
+        ]
+    );
+    assert_eq!(fs.default_acl(hello).unwrap(), None);
+
+    let acl = fs.default_acl(dir).unwrap().unwrap();
+    let entries: Vec<_> = acl.entries().iter().map(|e| (e.tag, e.perm)).collect();
+    assert_eq!(
+        entries,
+        [
+            (AclTag::UserObj, 7),
+            (AclTag::GroupObj, 5),
+            (AclTag::Other, 5)
+        ]
+    );
+    assert_eq!(fs.access_acl(dir).unwrap(), None);
+}
+
+#[test]
+fn bad_xattr_block() {
+    let mut data = std::fs::read(EXT2_REV1_IMG).unwrap();
+    data[XATTR_BLOCK * 4096] ^= 0xff;
+    let fs = ext2::FileSystem::new(Cursor::new(data)).unwrap();
+
+    let hello = fs.lookup("/hello.txt").unwrap();
+    assert!(matches!(
+        fs.xattrs(hello),
+        Err(ext2::Error::CorruptedFileSystem)
+    ));
+}
+
+#[test]
+fn unsupported_incompat_feature() {
+    let mut data = std::fs::read(EXT2_REV1_IMG).unwrap();
+    // meta_bg
+    data[1024 + 96] |= 0x10;
+
+    assert!(matches!(
+        ext2::FileSystem::new(Cursor::new(data)),
+        Err(ext2::Error::UnsupportedFeature(0x10))
+    ));
+}
+
+#[test]
+fn unsupported_ro_compat_feature() {
+    let mut data = std::fs::read(EXT2_REV1_IMG).unwrap();
+    // btree_dir
+    data[1024 + 100] |= 0x4;
+
+    let fs = ext2::FileSystem::new(Cursor::new(data)).unwrap();
+    assert!(fs.is_read_only());
+
+    let mut file = fs.open("/hello.txt").unwrap();
+    assert_eq!(read_to_end(&mut file), b"Hello, rev 1!\n");
+
+    assert!(matches!(
+        fs.create("/new", 0o644),
+        Err(ext2::Error::ReadOnlyFileSystem)
+    ));
+    assert!(matches!(
+        fs.unlink("/hello.txt"),
+        Err(ext2::Error::ReadOnlyFileSystem)
+    ));
+    assert!(matches!(
+        ext2::Write::write(&mut file, b"data"),
+        Err(ext2::Error::ReadOnlyFileSystem)
+    ));
+}
+
+#[test]
+fn bad_inode_size() {
+    let mut data = std::fs::read(EXT2_REV1_IMG).unwrap();
+    data[1024 + 88..1024 + 90].copy_from_slice(&200_u16.to_le_bytes());
+
+    assert!(matches!(
+        ext2::FileSystem::new(Cursor::new(data)),
+        Err(ext2::Error::CorruptedFileSystem)
+    ));
+}
+
+static EXT2_HTREE_IMG: &str = "tests/data/ext2-htree.img";
+
+// Directories of `EXT2_HTREE_IMG`, indexed with each hash algorithm
+const HTREE_DIRS: [&str; 3] = ["half_md4", "tea", "legacy"];
+
+// Blocks of `/tea` in `EXT2_HTREE_IMG`, as reported by `debugfs -R "bmap tea <n>"`
+const TEA_ROOT_BLOCK: usize = 492;
+const TEA_LEAF_BLOCK: usize = 594;
+
+fn open_htree_fs(data: Vec<u8>) -> ext2::FileSystem<ext2::StdIoWrapper<Cursor<Vec<u8>>>> {
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
+
+/// Returns the names of the links to `target` in each directory of `EXT2_HTREE_IMG`.
+fn htree_names() -> impl Iterator<Item = String> {
+    (0..2000).map(|i| {
+        if i % 10 == 0 {
+            format!("café-{i:04}")
+        } else {
+            format!("file-{i:04}-{}", "x".repeat(70))
+        }
+    })
+}
+
+#[test]
+fn lookup_indexed_dirs() {
+    let fs = open_htree_fs(std::fs::read(EXT2_HTREE_IMG).unwrap());
+
+    for dir in HTREE_DIRS {
+        let dir_ino = fs.lookup(&format!("/{dir}")).unwrap();
+        assert_ne!(fs.inode(dir_ino).unwrap().flags() & Inode::INDEX_FL, 0);
+
+        let target = fs.lookup(&format!("/{dir}/target")).unwrap();
+        for name in htree_names() {
+            assert_eq!(
+                fs.lookup(&format!("/{dir}/{name}")).unwrap(),
+                target,
+                "{dir}/{name}"
+            );
+        }
+        assert!(matches!(
+            fs.lookup(&format!("/{dir}/file-2000")),
+            Err(ext2::Error::NotFound)
+        ));
+
+        // `.` and `..` are not part of the index
+        assert_eq!(fs.lookup(&format!("/{dir}/.")).unwrap(), dir_ino);
+        assert_eq!(fs.lookup(&format!("/{dir}/..")).unwrap(), Inode::ROOT_INO);
+
+        assert_eq!(fs.dir(dir_ino).unwrap().iter().count(), 2003);
+    }
+}
+
+#[test]
+fn lookup_through_index() {
+    // Only the names in the broken leaf can't be found, whereas a linear scan fails on it
+    let mut data = std::fs::read(EXT2_HTREE_IMG).unwrap();
+    data[TEA_LEAF_BLOCK * 1024..(TEA_LEAF_BLOCK + 1) * 1024].fill(0);
+    let fs = open_htree_fs(data);
+
+    let broken = htree_names()
+        .filter(|name| fs.lookup(&format!("/tea/{name}")).is_err())
+        .count();
+    assert!((1..20).contains(&broken));
+    assert!(matches!(
+        fs.lookup(&format!("/tea/file-1859-{}", "x".repeat(70))),
+        Err(ext2::Error::CorruptedFileSystem)
+    ));
+
+    let dir = fs.dir(fs.lookup("/tea").unwrap()).unwrap();
+    assert!(dir.iter().any(|e| e.is_err()));
+}
+
+#[test]
+fn unusable_index() {
+    // An unknown hash version makes lookups fall back to a linear scan
+    let mut data = std::fs::read(EXT2_HTREE_IMG).unwrap();
+    data[TEA_ROOT_BLOCK * 1024 + 28] = 0xff;
+    let fs = open_htree_fs(data);
+
+    let target = fs.lookup("/tea/target").unwrap();
+    for name in htree_names().step_by(7) {
+        assert_eq!(fs.lookup(&format!("/tea/{name}")).unwrap(), target);
+    }
 }
@@ -0,0 +1,537 @@
+use std::io::Cursor;
+
+use ext2::{FileType, Read, Seek, SeekFrom, Write};
+
+mod common;
+
+use common::{FileSystem, fsck};
+
+static EXT2_IMG: &str = "tests/data/ext2.img";
+static EXT2_REV1_IMG: &str = "tests/data/ext2-rev1.img";
+
+fn open_ext2_fs() -> FileSystem {
+    let data = std::fs::read(EXT2_IMG).unwrap();
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
+
+fn open_rev1_fs() -> FileSystem {
+    let data = std::fs::read(EXT2_REV1_IMG).unwrap();
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
+
+/// Unmounts the file system and mounts it again from the same data.
+fn remount(fs: FileSystem) -> FileSystem {
+    ext2::FileSystem::new(fs.into_inner().into_inner()).unwrap()
+}
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn read_all(fs: &FileSystem, path: &str) -> Vec<u8> {
+    let mut file = fs.open(path).unwrap();
+    let mut data = vec![0; file.len() as usize];
+    file.read_exact(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn create_and_write_file() {
+    let fs = open_ext2_fs();
+    let (free_blocks, free_inodes) = (fs.free_blocks_count(), fs.free_inodes_count());
+
+    let mut file = fs.create("/new.txt", 0o640).unwrap();
+    file.write_all(&pattern(5000)).unwrap();
+    assert_eq!(file.len(), 5000);
+
+    let fs = fsck(remount(fs), "create");
+
+    let ino = fs.lookup("/new.txt").unwrap();
+    let inode = fs.inode(ino).unwrap();
+    assert_eq!(inode.file_type(), FileType::RegularFile);
+    assert_eq!(inode.permissions(), 0o640);
+    assert_eq!(inode.links_count(), 1);
+    assert_eq!(read_all(&fs, "/new.txt"), pattern(5000));
+
+    assert_eq!(fs.free_blocks_count(), free_blocks - 5);
+    assert_eq!(fs.free_inodes_count(), free_inodes - 1);
+
+    assert!(matches!(
+        fs.create("/new.txt", 0o644),
+        Err(ext2::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        fs.create("/missing/new.txt", 0o644),
+        Err(ext2::Error::NotFound)
+    ));
+}
+
+#[test]
+fn overwrite_and_truncate() {
+    let fs = open_ext2_fs();
+    let free_blocks = fs.free_blocks_count();
+
+    // Spans direct, singly and doubly indirect blocks
+    let data = pattern(400 * 1024);
+    let mut file = fs.create("/large", 0o644).unwrap();
+    file.write_all(&data).unwrap();
+
+    // 400 data blocks, 1 singly indirect and 2 doubly indirect blocks
+    assert_eq!(fs.free_blocks_count(), free_blocks - 403);
+
+    file.seek(SeekFrom::Start(100)).unwrap();
+    file.write_all(b"overwritten").unwrap();
+    assert_eq!(file.len(), data.len() as u64);
+
+    file.set_len(1000).unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks - 1);
+
+    // Growing the file again exposes zeroes, not stale data
+    file.set_len(3000).unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks - 1);
+
+    let fs = fsck(remount(fs), "truncate");
+
+    let mut expected = data[..1000].to_vec();
+    expected[100..111].copy_from_slice(b"overwritten");
+    expected.resize(3000, 0);
+    assert_eq!(read_all(&fs, "/large"), expected);
+
+    fs.open("/large").unwrap().set_len(0).unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks);
+
+    fsck(fs, "truncate-empty");
+}
+
+#[test]
+fn open_symlinks_as_files() {
+    let fs = open_ext2_fs();
+    let root = fs.root_dir().unwrap();
+
+    // The block pointers of a fast symlink hold its target, and must not be mapped
+    for name in [&b"link"[..], b"slowlink"] {
+        let ino = root.find(name).unwrap().unwrap().ino();
+        assert!(matches!(fs.file(ino), Err(ext2::Error::InvalidInput)));
+    }
+    assert_eq!(read_all(&fs, "/link"), b"Hello, ext2!\n");
+}
+
+#[test]
+fn write_sparse_file() {
+    let fs = open_ext2_fs();
+    let free_blocks = fs.free_blocks_count();
+
+    let mut file = fs.create("/holes", 0o644).unwrap();
+    file.seek(SeekFrom::Start(20 * 1024 * 1024)).unwrap();
+    file.write_all(b"far away").unwrap();
+
+    // One data block, plus a doubly indirect block and its child
+    assert_eq!(fs.free_blocks_count(), free_blocks - 3);
+
+    let mut fs = fsck(remount(fs), "sparse");
+
+    let mut file = fs.open("/holes").unwrap();
+    let mut buf = [0xff; 8];
+    file.seek(SeekFrom::Start(1024 * 1024)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [0; 8]);
+
+    file.seek(SeekFrom::End(-8)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"far away");
+
+    fs.unlink("/holes").unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks);
+
+    fs = fsck(fs, "sparse-unlink");
+    assert!(matches!(fs.lookup("/holes"), Err(ext2::Error::NotFound)));
+}
+
+#[test]
+fn unlink_files() {
+    let fs = open_ext2_fs();
+    let (free_blocks, free_inodes) = (fs.free_blocks_count(), fs.free_inodes_count());
+
+    let sectors = fs.inode(fs.lookup("/big").unwrap()).unwrap().sectors();
+    fs.unlink("/big").unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks + sectors / 2);
+
+    // Symlinks are removed, not followed
+    fs.unlink("/link").unwrap();
+    fs.unlink("/slowlink").unwrap();
+    assert_eq!(fs.lookup("/hello.txt").unwrap(), 19);
+    assert_eq!(fs.free_inodes_count(), free_inodes + 3);
+
+    assert!(matches!(fs.unlink("/dir"), Err(ext2::Error::IsADirectory)));
+    assert!(matches!(fs.unlink("/big"), Err(ext2::Error::NotFound)));
+
+    let fs = fsck(fs, "unlink");
+
+    let names = fs
+        .root_dir()
+        .unwrap()
+        .iter()
+        .map(|e| e.unwrap().name().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert!(
+        !names
+            .iter()
+            .any(|n| n == "big" || n == "link" || n == "slowlink")
+    );
+}
+
+#[test]
+fn unlink_file_with_xattrs() {
+    let fs = open_rev1_fs();
+    let free_blocks = fs.free_blocks_count();
+
+    // One data block and one attribute block
+    let hello = fs.lookup("/hello.txt").unwrap();
+    assert_eq!(fs.inode(hello).unwrap().sectors(), 16);
+    fs.unlink("/hello.txt").unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks + 2);
+
+    let fs = fsck(fs, "unlink-xattrs");
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn unlink_fast_symlink_with_xattrs() {
+    let fs = open_rev1_fs();
+    let free_blocks = fs.free_blocks_count();
+
+    // The target is stored in the block pointers, and the attributes in a block of their own
+    let xlink = fs
+        .root_dir()
+        .unwrap()
+        .find(b"xlink")
+        .unwrap()
+        .unwrap()
+        .ino();
+    assert_eq!(fs.inode(xlink).unwrap().sectors(), 8);
+    fs.unlink("/xlink").unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks + 1);
+
+    let fs = fsck(fs, "unlink-fast-symlink-xattrs");
+    assert!(fs.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn make_and_remove_dirs() {
+    let fs = open_ext2_fs();
+    let (free_blocks, free_inodes) = (fs.free_blocks_count(), fs.free_inodes_count());
+    let root_links = fs.inode(2).unwrap().links_count();
+
+    let a = fs.mkdir("/a", 0o755).unwrap();
+    let b = fs.mkdir("/a/b", 0o700).unwrap();
+    fs.create("/a/b/file", 0o644)
+        .unwrap()
+        .write_all(b"data")
+        .unwrap();
+
+    assert_eq!(fs.inode(2).unwrap().links_count(), root_links + 1);
+    assert_eq!(fs.inode(a).unwrap().links_count(), 3);
+    assert_eq!(fs.inode(b).unwrap().links_count(), 2);
+    assert_eq!(fs.lookup("/a/b/..").unwrap(), a);
+
+    let fs = fsck(remount(fs), "mkdir");
+
+    assert!(matches!(
+        fs.rmdir("/a"),
+        Err(ext2::Error::DirectoryIsNotEmpty)
+    ));
+    assert!(matches!(
+        fs.rmdir("/a/b/file"),
+        Err(ext2::Error::NotADirectory)
+    ));
+    assert!(matches!(
+        fs.mkdir("/a/b", 0o755),
+        Err(ext2::Error::AlreadyExists)
+    ));
+    assert!(matches!(fs.rmdir("/a/.."), Err(ext2::Error::InvalidInput)));
+
+    fs.unlink("/a/b/file").unwrap();
+    fs.rmdir("/a/b").unwrap();
+    fs.rmdir("/a/").unwrap();
+
+    assert_eq!(fs.inode(2).unwrap().links_count(), root_links);
+    assert_eq!(fs.free_blocks_count(), free_blocks);
+    assert_eq!(fs.free_inodes_count(), free_inodes);
+
+    fsck(fs, "rmdir");
+}
+
+#[test]
+fn grow_directory() {
+    let fs = open_ext2_fs();
+    let free_blocks = fs.free_blocks_count();
+
+    let dir = fs.mkdir("/many", 0o755).unwrap();
+    for i in 0..80 {
+        fs.create(&format!("/many/file-with-a-long-name-{i:03}"), 0o644)
+            .unwrap();
+    }
+
+    assert!(fs.inode(dir).unwrap().size() > 1024);
+
+    let fs = fsck(remount(fs), "grow-dir");
+    assert_eq!(fs.dir(dir).unwrap().iter().count(), 82);
+
+    for i in (0..80).rev() {
+        fs.unlink(&format!("/many/file-with-a-long-name-{i:03}"))
+            .unwrap();
+    }
+    fs.rmdir("/many").unwrap();
+    assert_eq!(fs.free_blocks_count(), free_blocks);
+
+    fsck(fs, "shrink-dir");
+}
+
+#[test]
+fn rename_files_and_dirs() {
+    let fs = open_ext2_fs();
+
+    let hello = fs.lookup("/hello.txt").unwrap();
+    let sub = fs.lookup("/dir/sub").unwrap();
+    let bin = fs.lookup("/bin").unwrap();
+
+    // Same directory
+    fs.rename("/hello.txt", "/hello2.txt").unwrap();
+    assert_eq!(fs.lookup("/hello2.txt").unwrap(), hello);
+    assert!(matches!(
+        fs.lookup("/hello.txt"),
+        Err(ext2::Error::NotFound)
+    ));
+
+    // Across directories, replacing an existing file
+    fs.rename("/hello2.txt", "/bin/init").unwrap();
+    assert_eq!(fs.lookup("/bin/init").unwrap(), hello);
+
+    // Directory to a new parent
+    let dir_links = fs.inode(fs.lookup("/dir").unwrap()).unwrap().links_count();
+    fs.rename("/dir/sub", "/bin/sub").unwrap();
+    assert_eq!(fs.lookup("/bin/sub").unwrap(), sub);
+    assert_eq!(fs.lookup("/bin/sub/..").unwrap(), bin);
+    assert_eq!(
+        fs.inode(fs.lookup("/dir").unwrap()).unwrap().links_count(),
+        dir_links - 1
+    );
+
+    assert!(matches!(
+        fs.rename("/bin", "/bin/sub/bin"),
+        Err(ext2::Error::InvalidInput)
+    ));
+    assert!(matches!(
+        fs.rename("/bin/init", "/dir"),
+        Err(ext2::Error::IsADirectory)
+    ));
+    assert!(matches!(
+        fs.rename("/bin", "/big"),
+        Err(ext2::Error::NotADirectory)
+    ));
+
+    let fs = fsck(remount(fs), "rename");
+    assert_eq!(read_all(&fs, "/bin/init"), b"Hello, ext2!\n");
+    assert_eq!(read_all(&fs, "/bin/sub/file"), b"nested\n");
+}
+
+#[test]
+fn out_of_space() {
+    let fs = open_ext2_fs();
+
+    let mut file = fs.create("/huge", 0o644).unwrap();
+    let chunk = pattern(64 * 1024);
+    let err = loop {
+        if let Err(e) = file.write_all(&chunk) {
+            break e;
+        }
+    };
+
+    assert!(matches!(err, ext2::Error::NotEnoughSpace));
+    assert_eq!(fs.free_blocks_count(), 0);
+
+    // Failing to create entries must not leave allocated inodes behind
+    let free_inodes = fs.free_inodes_count();
+    assert!(matches!(
+        fs.mkdir("/new-dir", 0o755),
+        Err(ext2::Error::NotEnoughSpace)
+    ));
+    let err = (0..)
+        .find_map(|i| fs.create(&format!("/file-{i}"), 0o644).err())
+        .unwrap();
+    assert!(matches!(err, ext2::Error::NotEnoughSpace));
+    assert!(fs.lookup("/new-dir").is_err());
+
+    let fs = fsck(remount(fs), "enospc");
+    let created = (0..)
+        .take_while(|i| fs.lookup(&format!("/file-{i}")).is_ok())
+        .count() as u32;
+    assert_eq!(fs.free_inodes_count(), free_inodes - created);
+    fs.unlink("/huge").unwrap();
+    fsck(fs, "enospc-unlink");
+}
+
+#[test]
+fn stale_counters() {
+    let mut data = std::fs::read(EXT2_IMG).unwrap();
+    let ino = open_ext2_fs().lookup("/hello.txt").unwrap() as usize;
+
+    // Make the descriptor of group 0 claim that there are no directories, and clear the bit of
+    // `/hello.txt` in the inode bitmap
+    let gd = 2048;
+    data[gd + 16..gd + 18].fill(0);
+    let inode_bitmap = u32::from_le_bytes(data[gd + 4..gd + 8].try_into().unwrap()) as usize;
+    data[inode_bitmap * 1024 + (ino - 1) / 8] &= !(1 << ((ino - 1) % 8));
+
+    let fs = ext2::FileSystem::new(Cursor::new(data)).unwrap();
+    let free_inodes = fs.free_inodes_count();
+
+    // Neither underflows nor counts the inode twice
+    fs.unlink("/dir/sub/file").unwrap();
+    fs.rmdir("/dir/sub").unwrap();
+    fs.unlink("/hello.txt").unwrap();
+    assert_eq!(fs.free_inodes_count(), free_inodes + 2);
+}
+
+#[test]
+fn write_rev1_fs() {
+    let fs = open_rev1_fs();
+    let (free_blocks, free_inodes) = (fs.free_blocks_count(), fs.free_inodes_count());
+
+    fs.mkdir("/new-dir", 0o755).unwrap();
+    fs.create("/new-dir/file", 0o644)
+        .unwrap()
+        .write_all(&pattern(10000))
+        .unwrap();
+    fs.rename("/hello.txt", "/new-dir/hello.txt").unwrap();
+
+    let fs = fsck(remount(fs), "rev1");
+
+    let entry = fs
+        .dir(fs.lookup("/new-dir").unwrap())
+        .unwrap()
+        .find(b"file")
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.file_type(), FileType::RegularFile);
+    assert_eq!(read_all(&fs, "/new-dir/file"), pattern(10000));
+    assert_eq!(read_all(&fs, "/new-dir/hello.txt"), b"Hello, rev 1!\n");
+
+    // 1 directory block and 3 data blocks
+    assert_eq!(fs.free_blocks_count(), free_blocks - 4);
+    assert_eq!(fs.free_inodes_count(), free_inodes - 2);
+
+    fs.unlink("/new-dir/file").unwrap();
+    fs.unlink("/new-dir/hello.txt").unwrap();
+    fs.rmdir("/new-dir").unwrap();
+    fs.unlink("/large").unwrap();
+
+    fsck(fs, "rev1-rm");
+}
+
+#[test]
+fn write_large_file() {
+    let fs = open_rev1_fs();
+
+    let mut file = fs.create("/huge", 0o644).unwrap();
+    file.seek(SeekFrom::Start(6 * 1024 * 1024 * 1024)).unwrap();
+    file.write_all(b"beyond 4 GiB").unwrap();
+
+    let fs = fsck(remount(fs), "large-file");
+
+    let mut file = fs.open("/huge").unwrap();
+    assert_eq!(file.len(), 6 * 1024 * 1024 * 1024 + 12);
+    file.set_len(5 * 1024 * 1024 * 1024).unwrap();
+
+    let fs = fsck(remount(fs), "large-file-truncate");
+    assert_eq!(fs.open("/huge").unwrap().len(), 5 * 1024 * 1024 * 1024);
+}
+
+#[test]
+fn upgrade_rev0_for_large_file() {
+    let fs = open_ext2_fs();
+    assert_eq!(fs.revision(), 0);
+
+    let mut file = fs.create("/huge", 0o644).unwrap();
+    file.seek(SeekFrom::Start(3 * 1024 * 1024 * 1024)).unwrap();
+    file.write_all(b"past 2 GiB").unwrap();
+
+    // Files larger than 2 GiB need the large_file feature, which requires revision 1
+    let fs = fsck(remount(fs), "upgrade");
+    assert_eq!(fs.revision(), 1);
+    assert_eq!(fs.inode_size(), 128);
+    assert_eq!(fs.feature_ro_compat(), 0x2);
+    assert_eq!(fs.open("/huge").unwrap().len(), 3 * 1024 * 1024 * 1024 + 10);
+}
+
+static EXT2_HTREE_IMG: &str = "tests/data/ext2-htree.img";
+
+fn open_htree_fs() -> FileSystem {
+    let data = std::fs::read(EXT2_HTREE_IMG).unwrap();
+    ext2::FileSystem::new(Cursor::new(data)).unwrap()
+}
+
+fn long_name(i: usize) -> String {
+    format!("file-{i:04}-{}", "x".repeat(70))
+}
+
+fn is_indexed(fs: &FileSystem, path: &str) -> bool {
+    let ino = fs.lookup(path).unwrap();
+    fs.inode(ino).unwrap().flags() & ext2::Inode::INDEX_FL != 0
+}
+
+#[test]
+fn update_indexed_dir() {
+    let fs = open_htree_fs();
+
+    for dir in ["half_md4", "tea", "legacy"] {
+        let target = fs.lookup(&format!("/{dir}/target")).unwrap();
+
+        // Names are removed from and added back to the leaf covering their hash
+        for i in (1..2000).step_by(97).filter(|i| i % 10 != 0) {
+            fs.unlink(&format!("/{dir}/{}", long_name(i))).unwrap();
+        }
+        for i in (1..2000).step_by(97).filter(|i| i % 10 != 0) {
+            let mut file = fs
+                .create(&format!("/{dir}/{}", long_name(i)), 0o644)
+                .unwrap();
+            file.write_all(b"new").unwrap();
+        }
+        fs.rename(
+            &format!("/{dir}/{}", long_name(2)),
+            &format!("/{dir}/renamed"),
+        )
+        .unwrap();
+
+        assert!(is_indexed(&fs, &format!("/{dir}")));
+        assert!(fs.lookup(&format!("/{dir}/{}", long_name(2))).is_err());
+        assert_eq!(fs.lookup(&format!("/{dir}/renamed")).unwrap(), target);
+        assert_eq!(
+            fs.lookup(&format!("/{dir}/{}", long_name(3))).unwrap(),
+            target
+        );
+        for i in (1..2000).step_by(97).filter(|i| i % 10 != 0) {
+            assert_eq!(read_all(&fs, &format!("/{dir}/{}", long_name(i))), b"new");
+        }
+    }
+
+    let fs = fsck(remount(fs), "htree-update");
+    assert!(is_indexed(&fs, "/tea"));
+}
+
+#[test]
+fn drop_index_when_leaf_is_full() {
+    let fs = open_htree_fs();
+
+    for i in 2000..2300 {
+        fs.create(&format!("/tea/{}", long_name(i)), 0o644).unwrap();
+    }
+    assert!(!is_indexed(&fs, "/tea"));
+
+    let fs = fsck(remount(fs), "htree-drop");
+    let target = fs.lookup("/tea/target").unwrap();
+    for i in (0..2300).filter(|i| i % 10 != 0) {
+        let ino = fs.lookup(&format!("/tea/{}", long_name(i))).unwrap();
+        assert_eq!(ino == target, i < 2000);
+    }
+}
@@ -0,0 +1,147 @@
+//! Derive macro for `fdt::FromNode`, reading a struct from the properties of a device tree node.
+//!
+//! See the documentation of `FromNode` in the `fdt` crate, which re-exports this macro with its
+//! `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Error, Expr, Field, Fields, GenericParam, Ident, Lifetime, LifetimeParam,
+    LitStr, Token, Type, ext::IdentExt, parse::ParseStream, parse_macro_input,
+};
+
+#[proc_macro_derive(FromNode, attributes(fdt))]
+pub fn derive_from_node(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Options of a field, given by its `fdt` attribute.
+#[derive(Default)]
+struct FieldOptions {
+    /// Name of the property, if not derived from the name of the field.
+    name: Option<LitStr>,
+    /// Value used when the property is missing.
+    default: Option<Expr>,
+}
+
+impl FieldOptions {
+    /// Parses the options of the `#[fdt("name", default = expr)]` attributes of `field`.
+    fn from_field(field: &Field) -> syn::Result<Self> {
+        let mut options = Self::default();
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("fdt")) {
+            attr.parse_args_with(|input: ParseStream| {
+                if input.peek(LitStr) {
+                    options.name = Some(input.parse()?);
+                    if !input.is_empty() {
+                        input.parse::<Token![,]>()?;
+                    }
+                }
+
+                while !input.is_empty() {
+                    let key: Ident = input.parse()?;
+                    if key != "default" {
+                        return Err(Error::new(key.span(), "unknown option, expected `default`"));
+                    }
+                    input.parse::<Token![=]>()?;
+                    options.default = Some(input.parse()?);
+
+                    if !input.is_empty() {
+                        input.parse::<Token![,]>()?;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Returns whether `ty` is an `Option`, whose property is not required.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => {
+            path.qself.is_none()
+                && path
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|s| s.ident == "Option")
+        }
+        _ => false,
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "FromNode can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "FromNode can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut inits = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().unwrap();
+        let options = FieldOptions::from_field(field)?;
+        let name = options.name.unwrap_or_else(|| {
+            LitStr::new(&ident.unraw().to_string().replace('_', "-"), ident.span())
+        });
+
+        let value = quote! { node.try_property(#name)? };
+        let value = match (options.default, is_option(&field.ty)) {
+            (Some(default), false) => quote! { #value.unwrap_or_else(|| #default) },
+            (Some(default), true) => {
+                return Err(Error::new_spanned(
+                    default,
+                    "an `Option` field cannot have a default value",
+                ));
+            }
+            (None, true) => value,
+            (None, false) => {
+                quote! { #value.ok_or(::fdt::PropertyError::Missing(#name))? }
+            }
+        };
+        inits.push(quote! { #ident: #value });
+    }
+
+    // Values borrowed from the tree live as long as the first lifetime of the struct
+    let mut generics = input.generics.clone();
+    let lifetime = match input.generics.lifetimes().next() {
+        Some(param) => param.lifetime.clone(),
+        None => {
+            let lifetime = Lifetime::new("'__d", Span::call_site());
+            generics.params.insert(
+                0,
+                GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+            );
+            lifetime
+        }
+    };
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let ident = &input.ident;
+
+    Ok(quote! {
+        impl #impl_generics ::fdt::FromNode<#lifetime> for #ident #ty_generics #where_clause {
+            fn from_node(
+                node: &::fdt::Node<#lifetime, '_>,
+            ) -> ::core::result::Result<Self, ::fdt::PropertyError> {
+                ::core::result::Result::Ok(Self {
+                    #(#inits,)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,104 @@
+use fdt::{Fdt, FdtBuilder, FromNode, PropertyError, StringList};
+
+#[derive(Debug, PartialEq, FromNode)]
+struct UartConfig {
+    #[fdt("clock-frequency")]
+    clock: u32,
+    #[fdt("reg-shift", default = 0)]
+    shift: u32,
+    #[fdt("current-speed")]
+    speed: Option<u32>,
+    reg_io_width: Option<u32>,
+}
+
+#[derive(FromNode)]
+struct Identity<'d> {
+    compatible: StringList<'d>,
+    #[fdt("status", default = "okay")]
+    status: &'d str,
+}
+
+fn build_uart(f: impl FnOnce(&mut FdtBuilder)) -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+    b.begin_node("serial@10000000").unwrap();
+    b.property("compatible", ["snps,dw-apb-uart", "ns16550a"])
+        .unwrap();
+    f(&mut b);
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+fn uart_config(blob: &[u8]) -> Result<UartConfig, PropertyError> {
+    let fdt = Fdt::from_bytes(blob).unwrap();
+    let node = fdt.find_compatible("ns16550a").unwrap().unwrap();
+    UartConfig::from_node(&node)
+}
+
+#[test]
+fn derive() {
+    let blob = build_uart(|b| {
+        b.property("clock-frequency", 3686400u32).unwrap();
+        b.property("reg-shift", 2u32).unwrap();
+        b.property("reg-io-width", 4u32).unwrap();
+    });
+    assert_eq!(
+        uart_config(&blob),
+        Ok(UartConfig {
+            clock: 3686400,
+            shift: 2,
+            speed: None,
+            reg_io_width: Some(4),
+        })
+    );
+
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+    let node = fdt.find_compatible("ns16550a").unwrap().unwrap();
+    let identity = Identity::from_node(&node).unwrap();
+    assert_eq!(
+        identity.compatible.collect::<Vec<_>>(),
+        ["snps,dw-apb-uart", "ns16550a"]
+    );
+    assert_eq!(identity.status, "okay");
+}
+
+#[test]
+fn derive_defaults() {
+    let blob = build_uart(|b| {
+        b.property("clock-frequency", 3686400u32).unwrap();
+        b.property("current-speed", 115200u32).unwrap();
+    });
+    assert_eq!(
+        uart_config(&blob),
+        Ok(UartConfig {
+            clock: 3686400,
+            shift: 0,
+            speed: Some(115200),
+            reg_io_width: None,
+        })
+    );
+}
+
+#[test]
+fn derive_errors() {
+    let blob = build_uart(|_| {});
+    assert_eq!(
+        uart_config(&blob),
+        Err(PropertyError::Missing("clock-frequency"))
+    );
+
+    let blob = build_uart(|b| {
+        b.property("clock-frequency", 3686400u32).unwrap();
+        b.property("reg-shift", [0u8; 2]).unwrap();
+    });
+    assert_eq!(uart_config(&blob), Err(PropertyError::Invalid("reg-shift")));
+}
+
+#[test]
+fn qemu_serial() {
+    let fdt = Fdt::from_bytes(include_bytes!("../../fdt/tests/data/qemu-riscv.dtb")).unwrap();
+    let node = fdt.find_compatible("ns16550a").unwrap().unwrap();
+    let config = UartConfig::from_node(&node).unwrap();
+    assert_eq!((config.clock, config.shift), (0x384000, 0));
+}
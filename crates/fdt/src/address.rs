@@ -0,0 +1,177 @@
+use crate::{Node, PropValue};
+
+/// Default value of `#address-cells` when a node does not specify it.
+const DEFAULT_ADDRESS_CELLS: u32 = 2;
+/// Default value of `#size-cells` when a node does not specify it.
+const DEFAULT_SIZE_CELLS: u32 = 1;
+
+/// Largest number of cells an address or a size can span. Four cells are enough for every bus
+/// binding, including PCI whose addresses are three cells long.
+const MAX_CELLS: u32 = 4;
+
+/// A region of the address space, as described by an entry of a `reg` property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub address: u64,
+    pub size: u64,
+}
+
+impl Region {
+    /// Returns the address following the end of the region.
+    pub fn end(&self) -> u64 {
+        self.address.saturating_add(self.size)
+    }
+}
+
+/// Reads an integer spanning `cells` 32-bit cells.
+pub(crate) fn read_cells(data: &[u8], cells: u32) -> Option<(&[u8], u128)> {
+    if cells > MAX_CELLS {
+        return None;
+    }
+
+    let mut value = 0u128;
+    let mut data = data;
+    for _ in 0..cells {
+        let (rest, cell) = u32::parse(data)?;
+        value = value << 32 | cell as u128;
+        data = rest;
+    }
+    Some((data, value))
+}
+
+impl<'d, 'fdt> Node<'d, 'fdt> {
+    /// Returns the number of cells used to encode the addresses of the children of this node.
+    pub fn address_cells(&self) -> u32 {
+        self.property("#address-cells")
+            .unwrap_or(DEFAULT_ADDRESS_CELLS)
+    }
+
+    /// Returns the number of cells used to encode the sizes of the children of this node.
+    pub fn size_cells(&self) -> u32 {
+        self.property("#size-cells").unwrap_or(DEFAULT_SIZE_CELLS)
+    }
+
+    /// Returns the regions of the `reg` property of this node, decoded according to the cell
+    /// sizes of its parent, and translated to CPU physical addresses through the `ranges` of
+    /// its ancestors.
+    ///
+    /// Returns `None` if the node has no `reg` property, or if its bus is not mapped in the
+    /// address space of the CPU. Entries which are outside the `ranges` of a parent bus are
+    /// skipped.
+    pub fn reg(&self) -> Option<Reg<'d, 'fdt>> {
+        self.regions("reg")
+    }
+
+    /// Returns the regions of a property encoded like `reg`, such as `alloc-ranges`.
+    pub(crate) fn regions(&self, name: &str) -> Option<Reg<'d, 'fdt>> {
+        let parent = self.parent()?;
+        let data = self.raw_property(name)?;
+
+        // Buses without `ranges` have their own address space
+        let mut node = parent.clone();
+        while let Some(grandparent) = node.parent() {
+            node.raw_property("ranges")?;
+            node = grandparent;
+        }
+
+        Some(Reg {
+            address_cells: parent.address_cells(),
+            size_cells: parent.size_cells(),
+            parent,
+            data,
+        })
+    }
+
+    /// Translates `address` from the address space of the children of this node to the one of
+    /// the CPU, going through the `ranges` property of this node and its ancestors.
+    ///
+    /// Returns `None` if the address is not visible from the CPU.
+    pub fn translate_address(&self, address: u128) -> Option<u64> {
+        let mut address = address;
+        let mut node = self.clone();
+
+        while let Some(parent) = node.parent() {
+            let ranges = node.raw_property("ranges")?;
+            address = translate(
+                ranges,
+                address,
+                node.address_cells(),
+                parent.address_cells(),
+                node.size_cells(),
+            )?;
+            node = parent;
+        }
+
+        address.try_into().ok()
+    }
+}
+
+/// Translates `address` through the `ranges` of a bus, an empty property meaning that the
+/// address spaces of the bus and of its parent are identical.
+fn translate(
+    ranges: &[u8],
+    address: u128,
+    child_cells: u32,
+    parent_cells: u32,
+    size_cells: u32,
+) -> Option<u128> {
+    if ranges.is_empty() {
+        return Some(address);
+    }
+
+    // Bound the counts before adding them, as they come straight from the blob
+    let cells = [child_cells, parent_cells, size_cells];
+    if cells.iter().any(|&c| c > MAX_CELLS) || cells.iter().sum::<u32>() == 0 {
+        return None;
+    }
+
+    let mut data = ranges;
+    while !data.is_empty() {
+        let (rest, child) = read_cells(data, child_cells)?;
+        let (rest, parent) = read_cells(rest, parent_cells)?;
+        let (rest, size) = read_cells(rest, size_cells)?;
+        data = rest;
+
+        if (child..child.saturating_add(size)).contains(&address) {
+            return parent.checked_add(address - child);
+        }
+    }
+
+    None
+}
+
+/// Iterator over the regions of a `reg` property, returned by [`Node::reg`].
+#[derive(Clone)]
+pub struct Reg<'d, 'fdt> {
+    parent: Node<'d, 'fdt>,
+    address_cells: u32,
+    size_cells: u32,
+    data: &'d [u8],
+}
+
+impl Iterator for Reg<'_, '_> {
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Bound the counts before adding them, as they come straight from the blob
+        if self.address_cells > MAX_CELLS
+            || self.size_cells > MAX_CELLS
+            || self.address_cells + self.size_cells == 0
+        {
+            return None;
+        }
+
+        loop {
+            let (rest, address) = read_cells(self.data, self.address_cells)?;
+            let (rest, size) = read_cells(rest, self.size_cells)?;
+            self.data = rest;
+
+            let Some(address) = self.parent.translate_address(address) else {
+                continue;
+            };
+            if let Ok(size) = size.try_into() {
+                return Some(Region { address, size });
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::{
+    FDT_BEGIN_NODE, FDT_END, FDT_END_NODE, FDT_MAGIC, FDT_PROP, FDT_VERSION, HEADER_SIZE,
+    ReserveEntry,
+};
+
+const FDT_LAST_COMP_VERSION: u32 = 16;
+
+/// Errors returned by [`FdtBuilder`] when the tree it is given is not well-formed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtBuildError {
+    /// The root node has a name, or another node or a property has an empty name or one with
+    /// invalid characters.
+    InvalidName,
+    /// A property was added outside of any node, or after a child of its node.
+    MisplacedProperty,
+    /// A node was closed without being opened or left open, or there are several root nodes.
+    UnbalancedNodes,
+    /// The blob would not fit in the 32-bit offsets of the header.
+    TooLarge,
+}
+
+/// Values which can be stored in a property, the counterpart of [`PropValue`](crate::PropValue).
+///
+/// Integers are stored as big-endian cells, strings are NUL-terminated, and sequences are
+/// concatenated, so that `["a", "b"]` is a string list and `[(addr, size)]` a `reg` property.
+pub trait PropEncode {
+    fn encode(&self, buf: &mut Vec<u8>);
+}
+
+impl PropEncode for () {
+    fn encode(&self, _buf: &mut Vec<u8>) {}
+}
+
+impl PropEncode for u8 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self);
+    }
+}
+
+impl PropEncode for u32 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl PropEncode for u64 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl PropEncode for str {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+        buf.push(0);
+    }
+}
+
+impl<T: PropEncode + ?Sized> PropEncode for &T {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (**self).encode(buf);
+    }
+}
+
+impl<T: PropEncode> PropEncode for [T] {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        for value in self {
+            value.encode(buf);
+        }
+    }
+}
+
+impl<T: PropEncode, const N: usize> PropEncode for [T; N] {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.as_slice().encode(buf);
+    }
+}
+
+impl<T: PropEncode, U: PropEncode> PropEncode for (T, U) {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf);
+        self.1.encode(buf);
+    }
+}
+
+impl<T: PropEncode, U: PropEncode, V: PropEncode> PropEncode for (T, U, V) {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf);
+        self.1.encode(buf);
+        self.2.encode(buf);
+    }
+}
+
+/// Builds a flattened device tree blob.
+///
+/// Nodes are opened with [`begin_node`](Self::begin_node) and closed with
+/// [`end_node`](Self::end_node), starting with the root node whose name is empty. Properties
+/// are added to the innermost open node, before any of its children.
+#[derive(Default)]
+pub struct FdtBuilder {
+    boot_cpuid: u32,
+    reserved: Vec<ReserveEntry>,
+    structs: Vec<u8>,
+    strings: Vec<u8>,
+    string_offsets: BTreeMap<String, u32>,
+    /// For each open node, whether it already has children
+    open: Vec<bool>,
+    has_root: bool,
+}
+
+impl FdtBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the physical id of the boot CPU recorded in the header.
+    pub fn set_boot_cpuid(&mut self, cpuid: u32) {
+        self.boot_cpuid = cpuid;
+    }
+
+    /// Adds an entry to the memory reservation block.
+    pub fn reserve_memory(&mut self, address: u64, size: u64) {
+        self.reserved.push(ReserveEntry { address, size });
+    }
+
+    /// Opens a child of the current node, or the root node if no node is open.
+    pub fn begin_node(&mut self, name: &str) -> Result<(), FdtBuildError> {
+        match self.open.last_mut() {
+            None if self.has_root => return Err(FdtBuildError::UnbalancedNodes),
+            None if !name.is_empty() => return Err(FdtBuildError::InvalidName),
+            None => self.has_root = true,
+            Some(_) if name.is_empty() || name.contains(['/', '\0']) => {
+                return Err(FdtBuildError::InvalidName);
+            }
+            Some(has_children) => *has_children = true,
+        }
+
+        self.open.push(false);
+        self.push_u32(FDT_BEGIN_NODE);
+        self.structs.extend_from_slice(name.as_bytes());
+        self.structs.push(0);
+        self.pad();
+        Ok(())
+    }
+
+    /// Closes the current node.
+    pub fn end_node(&mut self) -> Result<(), FdtBuildError> {
+        self.open.pop().ok_or(FdtBuildError::UnbalancedNodes)?;
+        self.push_u32(FDT_END_NODE);
+        Ok(())
+    }
+
+    /// Adds a property to the current node.
+    pub fn property<T: PropEncode>(&mut self, name: &str, value: T) -> Result<(), FdtBuildError> {
+        match self.open.last() {
+            Some(false) => {}
+            _ => return Err(FdtBuildError::MisplacedProperty),
+        }
+        if name.is_empty() || name.contains('\0') {
+            return Err(FdtBuildError::InvalidName);
+        }
+
+        let mut data = Vec::new();
+        value.encode(&mut data);
+        let len = u32::try_from(data.len()).map_err(|_| FdtBuildError::TooLarge)?;
+        let name_off = self.string_offset(name)?;
+
+        self.push_u32(FDT_PROP);
+        self.push_u32(len);
+        self.push_u32(name_off);
+        self.structs.extend_from_slice(&data);
+        self.pad();
+        Ok(())
+    }
+
+    /// Returns the blob, once all the nodes have been closed.
+    pub fn finish(mut self) -> Result<Vec<u8>, FdtBuildError> {
+        if !self.has_root || !self.open.is_empty() {
+            return Err(FdtBuildError::UnbalancedNodes);
+        }
+        self.push_u32(FDT_END);
+
+        let rsvmap_size = (self.reserved.len() + 1) * 16;
+        let off_dt_struct = HEADER_SIZE + rsvmap_size;
+        let off_dt_strings = off_dt_struct + self.structs.len();
+        let totalsize = off_dt_strings + self.strings.len();
+        let totalsize = u32::try_from(totalsize).map_err(|_| FdtBuildError::TooLarge)?;
+
+        let mut blob = Vec::with_capacity(totalsize as usize);
+        for field in [
+            FDT_MAGIC,
+            totalsize,
+            off_dt_struct as u32,
+            off_dt_strings as u32,
+            HEADER_SIZE as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            self.boot_cpuid,
+            self.strings.len() as u32,
+            self.structs.len() as u32,
+        ] {
+            blob.extend_from_slice(&field.to_be_bytes());
+        }
+
+        for entry in &self.reserved {
+            blob.extend_from_slice(&entry.address.to_be_bytes());
+            blob.extend_from_slice(&entry.size.to_be_bytes());
+        }
+        // The reservation block ends with an empty entry
+        blob.extend_from_slice(&[0; 16]);
+
+        blob.extend_from_slice(&self.structs);
+        blob.extend_from_slice(&self.strings);
+        Ok(blob)
+    }
+
+    /// Returns the offset of `name` in the strings block, adding it if needed.
+    fn string_offset(&mut self, name: &str) -> Result<u32, FdtBuildError> {
+        if let Some(&off) = self.string_offsets.get(name) {
+            return Ok(off);
+        }
+
+        let off = u32::try_from(self.strings.len()).map_err(|_| FdtBuildError::TooLarge)?;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.string_offsets.insert(String::from(name), off);
+        Ok(off)
+    }
+
+    fn push_u32(&mut self, value: u32) {
+        self.structs.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Pads the struct block to the next token boundary.
+    fn pad(&mut self) {
+        let len = self.structs.len().next_multiple_of(4);
+        self.structs.resize(len, 0);
+    }
+}
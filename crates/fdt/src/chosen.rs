@@ -0,0 +1,86 @@
+use core::ops::Range;
+
+use crate::{Fdt, FdtParseError, Node, PropValue};
+
+/// Console selected by the `stdout-path` property of `/chosen`.
+#[derive(Debug, Clone)]
+pub struct Stdout<'d, 'fdt> {
+    pub node: Node<'d, 'fdt>,
+    /// Options following the path, such as `115200n8` for a serial port.
+    pub options: Option<&'d str>,
+}
+
+impl Stdout<'_, '_> {
+    /// Returns the baud rate given by the options of a serial console.
+    pub fn baud_rate(&self) -> Option<u32> {
+        let options = self.options?;
+        let end = options
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(options.len());
+        options[..end].parse().ok()
+    }
+}
+
+/// Reads an integer of one or two cells, filling the whole of `data`.
+fn read_int(data: &[u8]) -> Option<u64> {
+    match data.len() {
+        4 => u32::parse(data).map(|(_, v)| v as u64),
+        8 => u64::parse(data).map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+impl<'d> Fdt<'d> {
+    /// Returns the path of the node that the alias `name` stands for.
+    pub fn alias(&self, name: &str) -> Result<Option<&'d str>, FdtParseError<'d>> {
+        let aliases = self.root_node()?.children().find(|n| n.name == "aliases");
+        Ok(aliases.and_then(|n| n.property(name)))
+    }
+
+    /// Returns the `/chosen` node, which holds the parameters given by the firmware.
+    pub fn chosen<'fdt>(&'fdt self) -> Result<Option<Node<'d, 'fdt>>, FdtParseError<'d>> {
+        self.find_by_path("/chosen")
+    }
+
+    /// Returns the command line of the kernel.
+    pub fn bootargs(&self) -> Result<Option<&'d str>, FdtParseError<'d>> {
+        Ok(self.chosen()?.and_then(|n| n.property("bootargs")))
+    }
+
+    /// Returns the node of the console, and its options.
+    pub fn stdout<'fdt>(&'fdt self) -> Result<Option<Stdout<'d, 'fdt>>, FdtParseError<'d>> {
+        let Some(chosen) = self.chosen()? else {
+            return Ok(None);
+        };
+        let Some(path) = chosen
+            .property::<&str>("stdout-path")
+            .or_else(|| chosen.property("linux,stdout-path"))
+        else {
+            return Ok(None);
+        };
+
+        let (path, options) = match path.split_once(':') {
+            Some((path, options)) => (path, Some(options)),
+            None => (path, None),
+        };
+        Ok(self
+            .find_by_path(path)?
+            .map(|node| Stdout { node, options }))
+    }
+
+    /// Returns the physical address range of the initial ramdisk loaded by the firmware.
+    pub fn initrd(&self) -> Result<Option<Range<u64>>, FdtParseError<'d>> {
+        let Some(chosen) = self.chosen()? else {
+            return Ok(None);
+        };
+
+        let start = chosen.raw_property("linux,initrd-start").and_then(read_int);
+        let end = chosen.raw_property("linux,initrd-end").and_then(read_int);
+        Ok(start.zip(end).map(|(start, end)| start..end))
+    }
+
+    /// Returns the random bytes given by the firmware to seed the entropy pool.
+    pub fn rng_seed(&self) -> Result<Option<&'d [u8]>, FdtParseError<'d>> {
+        Ok(self.chosen()?.and_then(|n| n.raw_property("rng-seed")))
+    }
+}
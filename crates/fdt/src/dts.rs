@@ -0,0 +1,235 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    string::String,
+};
+
+use crate::{Fdt, FdtParseError, Node, Property};
+
+/// Properties whose value is the phandle of another node.
+const PHANDLE_PROPERTIES: &[&str] = &[
+    "interrupt-parent",
+    "msi-parent",
+    "regmap",
+    "memory-region",
+    "cpu",
+    "remote-endpoint",
+];
+
+impl<'d> Fdt<'d> {
+    /// Renders the tree as device tree source, in the format of `dtc -O dts`.
+    ///
+    /// Since the blob does not record the types of properties, they are guessed from their
+    /// values: printable NUL-terminated values are shown as strings, values whose length is a
+    /// multiple of 4 as cells, and anything else as bytes. Nodes with a phandle are labeled,
+    /// using the names of `__symbols__` if the tree has one, and references to them in known
+    /// properties are shown with these labels.
+    pub fn to_dts(&self) -> Result<String, FdtParseError<'d>> {
+        let root = self.root_node()?;
+        let labels = labels(&root);
+
+        let mut dts = String::from("/dts-v1/;\n\n");
+        for entry in self.reserved_memory_map() {
+            let entry = entry?;
+            writeln!(
+                dts,
+                "/memreserve/ {:#018x} {:#018x};",
+                entry.address, entry.size
+            )
+            .unwrap();
+        }
+        if self.reserved_memory_map().next().is_some() {
+            dts.push('\n');
+        }
+
+        write_node(&mut dts, &root, &labels, 0).unwrap();
+        Ok(dts)
+    }
+}
+
+/// Returns the labels of the nodes of the tree with a phandle, by phandle.
+fn labels(root: &Node) -> BTreeMap<u32, String> {
+    let mut labels = BTreeMap::new();
+
+    // Labels of the original source, if it was compiled with symbols
+    if let Some(symbols) = root.children().find(|n| n.name == "__symbols__") {
+        for prop in symbols.properties() {
+            let (Some(label), Some(path)) = (prop.name(), prop.value::<&str>()) else {
+                continue;
+            };
+            let node = root.fdt.find_by_path(path).ok().flatten();
+            if let Some(phandle) = node.and_then(|n| n.phandle()) {
+                labels.entry(phandle).or_insert_with(|| String::from(label));
+            }
+        }
+    }
+
+    derive_labels(root, &mut labels);
+    labels
+}
+
+/// Derives labels from the names of the nodes with a phandle but without a label.
+fn derive_labels(node: &Node, labels: &mut BTreeMap<u32, String>) {
+    if let Some(phandle) = node.phandle()
+        && !labels.contains_key(&phandle)
+    {
+        let mut label: String = node
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if !label.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            label.insert(0, '_');
+        }
+        if labels.values().any(|l| *l == label) {
+            write!(label, "_{phandle:x}").unwrap();
+        }
+        labels.insert(phandle, label);
+    }
+
+    for child in node.children() {
+        derive_labels(&child, labels);
+    }
+}
+
+fn indent(w: &mut impl Write, depth: usize) -> fmt::Result {
+    for _ in 0..depth {
+        w.write_char('\t')?;
+    }
+    Ok(())
+}
+
+fn write_node(
+    w: &mut impl Write,
+    node: &Node,
+    labels: &BTreeMap<u32, String>,
+    depth: usize,
+) -> fmt::Result {
+    indent(w, depth)?;
+    if let Some(label) = node.phandle().and_then(|p| labels.get(&p)) {
+        write!(w, "{label}: ")?;
+    }
+    match node.name {
+        "" => writeln!(w, "/ {{")?,
+        name => writeln!(w, "{name} {{")?,
+    }
+
+    for prop in node.properties() {
+        indent(w, depth + 1)?;
+        write_property(w, node, &prop, labels)?;
+    }
+
+    for (i, child) in node.children().enumerate() {
+        if i > 0 || node.properties().next().is_some() {
+            writeln!(w)?;
+        }
+        write_node(w, &child, labels, depth + 1)?;
+    }
+
+    indent(w, depth)?;
+    writeln!(w, "}};")
+}
+
+fn write_property(
+    w: &mut impl Write,
+    node: &Node,
+    prop: &Property,
+    labels: &BTreeMap<u32, String>,
+) -> fmt::Result {
+    let name = prop.name().unwrap_or("<invalid>");
+    let value = prop.raw_value();
+
+    if value.is_empty() {
+        return writeln!(w, "{name};");
+    }
+    write!(w, "{name} = ")?;
+
+    if is_string_list(value) {
+        for (i, s) in value[..value.len() - 1].split(|&b| b == 0).enumerate() {
+            if i > 0 {
+                w.write_str(", ")?;
+            }
+            write_string(w, s)?;
+        }
+    } else if value.len().is_multiple_of(4) {
+        let cells = value
+            .as_chunks::<4>()
+            .0
+            .iter()
+            .map(|c| u32::from_be_bytes(*c));
+        write_cells(w, node, name, cells, labels)?;
+    } else {
+        w.write_char('[')?;
+        for (i, b) in value.iter().enumerate() {
+            if i > 0 {
+                w.write_char(' ')?;
+            }
+            write!(w, "{b:02x}")?;
+        }
+        w.write_char(']')?;
+    }
+
+    writeln!(w, ";")
+}
+
+/// Returns whether `value` looks like a list of non-empty printable strings.
+fn is_string_list(value: &[u8]) -> bool {
+    let Some((&0, strings)) = value.split_last() else {
+        return false;
+    };
+
+    !strings.is_empty()
+        && strings
+            .split(|&b| b == 0)
+            .all(|s| !s.is_empty() && s.iter().all(|&b| b.is_ascii_graphic() || b == b' '))
+}
+
+fn write_string(w: &mut impl Write, s: &[u8]) -> fmt::Result {
+    w.write_char('"')?;
+    for &b in s {
+        match b {
+            b'"' => w.write_str("\\\"")?,
+            b'\\' => w.write_str("\\\\")?,
+            _ => w.write_char(b as char)?,
+        }
+    }
+    w.write_char('"')
+}
+
+fn write_cells(
+    w: &mut impl Write,
+    node: &Node,
+    name: &str,
+    cells: impl Iterator<Item = u32>,
+    labels: &BTreeMap<u32, String>,
+) -> fmt::Result {
+    // Positions of the cells holding phandles, as far as they are known
+    let mut next_phandle =
+        (PHANDLE_PROPERTIES.contains(&name) || name == "interrupts-extended").then_some(0);
+
+    w.write_char('<')?;
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            w.write_char(' ')?;
+        }
+
+        match labels.get(&cell).filter(|_| next_phandle == Some(i)) {
+            Some(label) => {
+                write!(w, "&{label}")?;
+                next_phandle = match name {
+                    // Each specifier is preceded by the phandle of its controller
+                    "interrupts-extended" => node
+                        .fdt
+                        .node_by_phandle(cell)
+                        .ok()
+                        .flatten()
+                        .and_then(|n| n.interrupt_cells())
+                        .map(|cells| i + 1 + cells as usize),
+                    _ => None,
+                };
+            }
+            None => write!(w, "{cell:#04x}")?,
+        }
+    }
+    w.write_char('>')
+}
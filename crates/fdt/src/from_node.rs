@@ -0,0 +1,48 @@
+use crate::{Node, PropValue};
+
+/// Error returned when the properties of a node do not match what is expected of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyError {
+    /// A required property is missing.
+    Missing(&'static str),
+    /// A property has a value which cannot be decoded as the expected type.
+    Invalid(&'static str),
+}
+
+/// Types which can be read from the properties of a node.
+///
+/// This is usually derived with `#[derive(FromNode)]`, available with the `derive` feature,
+/// naming the property of each field with an `fdt` attribute:
+///
+/// ```ignore
+/// #[derive(FromNode)]
+/// struct UartConfig {
+///     #[fdt("clock-frequency")]
+///     clock: u32,
+///     #[fdt("reg-shift", default = 0)]
+///     shift: u32,
+///     #[fdt("current-speed")]
+///     speed: Option<u32>,
+/// }
+/// ```
+///
+/// Fields of any [`PropValue`] type are required, unless they have a default value or are an
+/// `Option`. Fields without a property name are read from the property named after them, with
+/// underscores replaced by hyphens.
+pub trait FromNode<'d>: Sized {
+    fn from_node(node: &Node<'d, '_>) -> Result<Self, PropertyError>;
+}
+
+impl<'d> Node<'d, '_> {
+    /// Returns the value of a property, distinguishing a missing property, for which `None` is
+    /// returned, from one whose value cannot be decoded.
+    pub fn try_property<T>(&self, name: &'static str) -> Result<Option<T>, PropertyError>
+    where
+        T: PropValue<'d> + 'd,
+    {
+        match self.properties().find(|p| p.name() == Some(name)) {
+            Some(prop) => prop.value().map(Some).ok_or(PropertyError::Invalid(name)),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{Node, PropValue};
+
+/// Maximum number of interrupt parents and nexus nodes followed when resolving an interrupt,
+/// which protects against cycles in malformed trees.
+const MAX_DEPTH: usize = 32;
+
+/// Cells describing an interrupt to its controller, whose meaning is defined by the binding of
+/// the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptSpecifier<'d> {
+    data: &'d [u8],
+}
+
+impl<'d> InterruptSpecifier<'d> {
+    /// Returns the number of cells of the specifier.
+    pub fn len(&self) -> usize {
+        self.data.len() / 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the `i`-th cell of the specifier.
+    pub fn cell(&self, i: usize) -> Option<u32> {
+        cell(self.data, i)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = u32> + use<'d> {
+        let data = self.data;
+        (0..self.len()).filter_map(move |i| cell(data, i))
+    }
+}
+
+/// An interrupt of a device, routed to the controller which handles it.
+#[derive(Debug, Clone)]
+pub struct Interrupt<'d, 'fdt> {
+    pub controller: Node<'d, 'fdt>,
+    pub specifier: InterruptSpecifier<'d>,
+}
+
+fn cell(data: &[u8], i: usize) -> Option<u32> {
+    let (_, cell) = u32::parse(data.get(i * 4..)?)?;
+    Some(cell)
+}
+
+impl<'d, 'fdt> Node<'d, 'fdt> {
+    /// Returns whether this node is an interrupt controller.
+    pub fn is_interrupt_controller(&self) -> bool {
+        self.raw_property("interrupt-controller").is_some()
+    }
+
+    /// Returns the number of cells of the interrupt specifiers of this interrupt controller or
+    /// nexus.
+    pub fn interrupt_cells(&self) -> Option<u32> {
+        self.property("#interrupt-cells")
+    }
+
+    /// Returns the node the interrupts of this node are routed to, given by the
+    /// `interrupt-parent` property of this node or of its closest ancestor.
+    pub fn interrupt_parent(&self) -> Option<Node<'d, 'fdt>> {
+        let mut node = self.clone();
+
+        for _ in 0..MAX_DEPTH {
+            let parent = match node.property::<u32>("interrupt-parent") {
+                Some(phandle) => self.fdt.node_by_phandle(phandle).ok()??,
+                None => node.parent()?,
+            };
+
+            // Only nodes which can receive interrupts can be interrupt parents
+            if parent.interrupt_cells().is_some() {
+                return Some(parent);
+            }
+            node = parent;
+        }
+
+        None
+    }
+
+    /// Returns the interrupts generated by this node, from its `interrupts-extended` property
+    /// or else its `interrupts` property, resolved through `interrupt-map` properties up to the
+    /// interrupt controllers.
+    ///
+    /// Interrupts which are not mapped to any controller are skipped, and malformed properties
+    /// end the iteration.
+    pub fn interrupts(&self) -> Interrupts<'d, 'fdt> {
+        if let Some(data) = self.raw_property("interrupts-extended") {
+            return Interrupts {
+                node: self.clone(),
+                parent: None,
+                data,
+            };
+        }
+
+        let parent = self.interrupt_parent();
+        let data = match parent {
+            Some(_) => self.raw_property("interrupts").unwrap_or_default(),
+            None => &[],
+        };
+
+        Interrupts {
+            node: self.clone(),
+            parent,
+            data,
+        }
+    }
+
+    /// Resolves an interrupt of `device` given by `specifier` relative to this node, following
+    /// interrupt nexus nodes up to an interrupt controller.
+    fn resolve_interrupt(
+        &self,
+        device: &Node<'d, 'fdt>,
+        specifier: &'d [u8],
+    ) -> Option<Interrupt<'d, 'fdt>> {
+        let mut parent = self.clone();
+        let mut specifier = specifier;
+        // Nexus nodes can map interrupts based on the unit address of the device
+        let mut address = device.raw_property("reg").unwrap_or_default();
+
+        for _ in 0..MAX_DEPTH {
+            if parent.is_interrupt_controller() {
+                return Some(Interrupt {
+                    controller: parent,
+                    specifier: InterruptSpecifier { data: specifier },
+                });
+            }
+
+            let Some(map) = parent.raw_property("interrupt-map") else {
+                parent = parent.interrupt_parent()?;
+                continue;
+            };
+            (parent, address, specifier) = parent.map_interrupt(map, address, specifier)?;
+        }
+
+        None
+    }
+
+    /// Looks up the entry of the `interrupt-map` of this nexus node matching the unit
+    /// `address` and `specifier` of a child, returning the parent of the entry with the
+    /// unit address and specifier of the interrupt in its space.
+    fn map_interrupt(
+        &self,
+        map: &'d [u8],
+        address: &'d [u8],
+        specifier: &'d [u8],
+    ) -> Option<(Node<'d, 'fdt>, &'d [u8], &'d [u8])> {
+        let address_cells = self.property::<u32>("#address-cells").unwrap_or(0) as usize;
+        let interrupt_cells = self.interrupt_cells()? as usize;
+        let mask = self.raw_property("interrupt-map-mask");
+
+        // Cells of the child unit address followed by the child specifier
+        let key = |i: usize| match i.checked_sub(address_cells) {
+            None => cell(address, i).unwrap_or(0),
+            Some(i) => cell(specifier, i).unwrap_or(0),
+        };
+        let matches = |child: &[u8]| {
+            (0..address_cells + interrupt_cells).all(|i| {
+                let mask = mask.map_or(u32::MAX, |mask| cell(mask, i).unwrap_or(0));
+                key(i) & mask == cell(child, i).unwrap_or(0) & mask
+            })
+        };
+
+        let mut data = map;
+        while !data.is_empty() {
+            let child = data.get(..(address_cells + interrupt_cells) * 4)?;
+            let (rest, phandle) = u32::parse(&data[child.len()..])?;
+            let parent = self.fdt.node_by_phandle(phandle).ok()??;
+
+            let parent_address_cells = parent.property::<u32>("#address-cells").unwrap_or(0);
+            let parent_address = rest.get(..parent_address_cells as usize * 4)?;
+            let rest = &rest[parent_address.len()..];
+            let parent_specifier = rest.get(..parent.interrupt_cells()? as usize * 4)?;
+            data = &rest[parent_specifier.len()..];
+
+            if matches(child) {
+                return Some((parent, parent_address, parent_specifier));
+            }
+        }
+
+        None
+    }
+}
+
+/// Iterator over the interrupts of a node, returned by [`Node::interrupts`].
+#[derive(Clone)]
+pub struct Interrupts<'d, 'fdt> {
+    node: Node<'d, 'fdt>,
+    /// Interrupt parent of the node, or `None` for an `interrupts-extended` property where
+    /// each specifier is preceded by the phandle of its parent
+    parent: Option<Node<'d, 'fdt>>,
+    data: &'d [u8],
+}
+
+impl<'d, 'fdt> Interrupts<'d, 'fdt> {
+    /// Splits the next specifier and its interrupt parent from the property.
+    fn next_specifier(&mut self) -> Option<(Node<'d, 'fdt>, &'d [u8])> {
+        let parent = match &self.parent {
+            Some(parent) => parent.clone(),
+            None => {
+                let (rest, phandle) = u32::parse(self.data)?;
+                self.data = rest;
+                self.node.fdt.node_by_phandle(phandle).ok()??
+            }
+        };
+
+        let specifier = self.data.get(..parent.interrupt_cells()? as usize * 4)?;
+        if specifier.is_empty() && self.parent.is_some() {
+            return None;
+        }
+        self.data = &self.data[specifier.len()..];
+        Some((parent, specifier))
+    }
+}
+
+impl<'d, 'fdt> Iterator for Interrupts<'d, 'fdt> {
+    type Item = Interrupt<'d, 'fdt>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.data.is_empty() {
+            let Some((parent, specifier)) = self.next_specifier() else {
+                self.data = &[];
+                return None;
+            };
+
+            if let Some(irq) = parent.resolve_interrupt(&self.node, specifier) {
+                return Some(irq);
+            }
+        }
+
+        None
+    }
+}
@@ -1,6 +1,26 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use core::fmt;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod address;
+#[cfg(feature = "alloc")]
+mod builder;
+mod chosen;
+#[cfg(feature = "std")]
+mod dts;
+mod from_node;
+mod irq;
+mod memory;
+#[cfg(feature = "alloc")]
+mod overlay;
+mod validate;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::cell::OnceCell;
+use core::{fmt, ptr};
 
 use nom::{
     IResult, Parser,
@@ -10,9 +30,38 @@ use nom::{
     number::complete::{be_u32, be_u64},
 };
 
+pub use crate::address::*;
+#[cfg(feature = "alloc")]
+pub use crate::builder::*;
+pub use crate::chosen::*;
+pub use crate::from_node::*;
+pub use crate::irq::*;
+pub use crate::memory::*;
+#[cfg(feature = "alloc")]
+pub use crate::overlay::*;
+pub use crate::validate::*;
+#[cfg(feature = "derive")]
+pub use fdt_derive::FromNode;
+
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_VERSION: u32 = 17;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Size of the header, which is directly followed by the memory reservation block.
+const HEADER_SIZE: usize = 40;
+
 pub struct Fdt<'d> {
     hdr: Header,
     data: &'d [u8],
+    /// Phandles of the tree, with the offsets of their node and of its parent, sorted by
+    /// phandle. Built on the first lookup.
+    #[cfg(feature = "alloc")]
+    phandles: OnceCell<Vec<(u32, usize, Option<usize>)>>,
 }
 
 impl<'d> Fdt<'d> {
@@ -23,7 +72,12 @@ impl<'d> Fdt<'d> {
             return Err(FdtParseError::Truncated);
         }
 
-        Ok(Self { hdr, data: fdt })
+        Ok(Self {
+            hdr,
+            data: fdt,
+            #[cfg(feature = "alloc")]
+            phandles: OnceCell::new(),
+        })
     }
 
     /// # Safety
@@ -46,11 +100,14 @@ impl<'d> Fdt<'d> {
     }
 
     pub fn reserved_memory_map(
-        &'_ self,
-    ) -> impl Iterator<Item = Result<ReserveEntry, FdtParseError<'_>>> {
-        self.data[self.hdr.off_mem_rsvmap as usize..]
-            .chunks_exact(16)
-            .map(ReserveEntry::from_bytes)
+        &self,
+    ) -> impl Iterator<Item = Result<ReserveEntry, FdtParseError<'d>>> + use<'d> {
+        let data: &'d [u8] = self.data;
+        data[self.hdr.off_mem_rsvmap as usize..]
+            .as_chunks::<16>()
+            .0
+            .iter()
+            .map(|entry| ReserveEntry::from_bytes(entry))
             .take_while(|res| match res {
                 Ok(res) => !res.is_empty(),
                 _ => false,
@@ -58,28 +115,31 @@ impl<'d> Fdt<'d> {
     }
 
     pub fn root_node<'fdt>(&'fdt self) -> Result<Node<'d, 'fdt>, FdtParseError<'d>> {
-        Node::from_bytes(
-            self,
-            0,
-            None,
-            &self.data[self.hdr.off_dt_struct as usize
-                ..(self.hdr.off_dt_struct + self.hdr.size_dt_struct) as usize],
-        )
+        Node::from_bytes(self, 0, None, self.structs())
     }
 
+    /// Returns the node at `path`, which is either absolute or starts with an alias defined in
+    /// the `/aliases` node.
     pub fn find_by_path<'fdt>(
         &'fdt self,
         path: &str,
     ) -> Result<Option<Node<'d, 'fdt>>, FdtParseError<'d>> {
-        let root = self.root_node()?;
-
-        if path.is_empty() || path == "/" {
-            return Ok(Some(root));
-        }
-
-        let mut node = root;
+        let (mut node, rest) = match path.strip_prefix('/') {
+            Some(rest) => (self.root_node()?, rest),
+            None if path.is_empty() => return Ok(Some(self.root_node()?)),
+            None => {
+                let (alias, rest) = path.split_once('/').unwrap_or((path, ""));
+
+                // Aliases are absolute paths, so that they cannot be recursive
+                let target = self.alias(alias)?.filter(|target| target.starts_with('/'));
+                let Some(node) = target.map(|t| self.find_by_path(t)).transpose()?.flatten() else {
+                    return Ok(None);
+                };
+                (node, rest)
+            }
+        };
 
-        for name in path[1..].split('/') {
+        for name in rest.split('/').filter(|name| !name.is_empty()) {
             if let Some(child) = node.children().find(|n| n.identifier() == name) {
                 node = child;
             } else {
@@ -110,6 +170,40 @@ impl<'d> Fdt<'d> {
         self.root_node()?.find(f)
     }
 
+    /// Returns the node whose phandle is `phandle`.
+    ///
+    /// With the `alloc` feature, the phandles of the whole tree are indexed by the first call,
+    /// so that later lookups do not walk the tree.
+    pub fn node_by_phandle<'fdt>(
+        &'fdt self,
+        phandle: u32,
+    ) -> Result<Option<Node<'d, 'fdt>>, FdtParseError<'d>> {
+        #[cfg(feature = "alloc")]
+        {
+            let root = self.root_node()?;
+            let index = self.phandles.get_or_init(|| {
+                let mut index = Vec::new();
+                index_phandles(&root, &mut index);
+                index.sort_unstable();
+                index
+            });
+
+            let Ok(i) = index.binary_search_by_key(&phandle, |&(phandle, ..)| phandle) else {
+                return Ok(None);
+            };
+            let (_, off, parent_off) = index[i];
+            Node::from_bytes(self, off, parent_off, &self.structs()[off..]).map(Some)
+        }
+
+        #[cfg(not(feature = "alloc"))]
+        self.find(|n| n.phandle() == Some(phandle))
+    }
+
+    fn structs(&self) -> &'d [u8] {
+        &self.data[self.hdr.off_dt_struct as usize
+            ..(self.hdr.off_dt_struct + self.hdr.size_dt_struct) as usize]
+    }
+
     fn get_string(&self, off: u32) -> Option<&'d str> {
         let start = self.hdr.off_dt_strings + off;
         let len = self.data[start as usize..].iter().position(|&b| b == 0)?;
@@ -119,6 +213,16 @@ impl<'d> Fdt<'d> {
     }
 }
 
+#[cfg(feature = "alloc")]
+fn index_phandles(node: &Node, index: &mut Vec<(u32, usize, Option<usize>)>) {
+    if let Some(phandle) = node.phandle() {
+        index.push((phandle, node.off, node.parent_off));
+    }
+    for child in node.children() {
+        index_phandles(&child, index);
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Header {
     magic: u32,
@@ -129,7 +233,7 @@ struct Header {
     version: u32,
     last_comp_version: u32,
     boot_cpuid_phys: u32,
-    _size_dt_strings: u32,
+    size_dt_strings: u32,
     size_dt_struct: u32,
 }
 
@@ -137,11 +241,15 @@ impl Header {
     fn from_bytes<'d>(s: &'d [u8]) -> Result<Self, FdtParseError<'d>> {
         let (_, header) = Header::parse(s).map_err(FdtParseError::ParseError)?;
 
-        if header.magic != 0xd00dfeed {
+        if header.magic != FDT_MAGIC {
             return Err(FdtParseError::InvalidHeader);
         }
 
-        if header.version != 17 || header.last_comp_version != 16 {
+        // Later versions remain readable as long as they are backward compatible with ours
+        if header.version < FDT_VERSION {
+            return Err(FdtParseError::UnsupportedVersion(header.version));
+        }
+        if header.last_comp_version > FDT_VERSION {
             return Err(FdtParseError::UnsupportedVersion(header.last_comp_version));
         }
 
@@ -171,7 +279,7 @@ impl Header {
                 version,
                 last_comp_version,
                 boot_cpuid_phys,
-                _size_dt_strings: size_dt_strings,
+                size_dt_strings,
                 size_dt_struct,
             },
         ))
@@ -252,6 +360,19 @@ impl<'d, 'fdt> Node<'d, 'fdt> {
             .and_then(|p| p.value())
     }
 
+    /// Returns the raw value of a property, which may be empty.
+    pub fn raw_property(&self, name: &str) -> Option<&'d [u8]> {
+        self.properties()
+            .find(|p| p.name() == Some(name))
+            .map(|p| p.raw_value())
+    }
+
+    /// Returns the handle other nodes use to reference this one.
+    pub fn phandle(&self) -> Option<u32> {
+        self.property("phandle")
+            .or_else(|| self.property("linux,phandle"))
+    }
+
     pub fn children(&self) -> impl Iterator<Item = Node<'d, 'fdt>> + use<'d, 'fdt> {
         self.children.clone()
     }
@@ -344,6 +465,14 @@ impl<'d, 'fdt> Node<'d, 'fdt> {
     }
 }
 
+impl PartialEq for Node<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.fdt, other.fdt) && self.off == other.off
+    }
+}
+
+impl Eq for Node<'_, '_> {}
+
 impl<'d> fmt::Debug for Node<'d, '_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Node")
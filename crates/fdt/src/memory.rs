@@ -0,0 +1,270 @@
+use crate::{Fdt, FdtParseError, Node, NodeIter, Reg, Region, address::read_cells};
+
+/// Largest number of `/reserved-memory` regions without a `reg` property that can be placed by
+/// [`Fdt::usable_memory`].
+const MAX_DYNAMIC_RESERVATIONS: usize = 16;
+
+/// Returns whether `node` describes RAM.
+fn is_memory(node: &Node) -> bool {
+    match node.property::<&str>("device_type") {
+        Some(device_type) => device_type == "memory",
+        None => node.name() == "memory",
+    }
+}
+
+/// Returns the children of `/reserved-memory` which are not disabled.
+fn reserved_children<'d, 'fdt>(
+    reserved: &Node<'d, 'fdt>,
+) -> impl Iterator<Item = ReservedMemory<'d, 'fdt>> + use<'d, 'fdt> {
+    reserved
+        .children()
+        .filter(|n| matches!(n.property::<&str>("status"), None | Some("okay" | "ok")))
+        .map(|node| ReservedMemory { node })
+}
+
+impl<'d> Fdt<'d> {
+    /// Returns the regions of RAM, across every entry of the `reg` property of every memory
+    /// node.
+    pub fn memory<'fdt>(&'fdt self) -> Result<Memory<'d, 'fdt>, FdtParseError<'d>> {
+        Ok(Memory {
+            nodes: self.root_node()?.children.clone(),
+            reg: None,
+        })
+    }
+
+    /// Returns the regions of memory set aside by the children of `/reserved-memory`.
+    pub fn reserved_memory<'fdt>(
+        &'fdt self,
+    ) -> Result<impl Iterator<Item = ReservedMemory<'d, 'fdt>> + use<'d, 'fdt>, FdtParseError<'d>>
+    {
+        let reserved = self.find_by_path("/reserved-memory")?;
+        Ok(reserved.into_iter().flat_map(|n| reserved_children(&n)))
+    }
+
+    /// Returns the regions of RAM which are free for the kernel to use.
+    ///
+    /// These are the regions of [`Fdt::memory`], minus the entries of the memory reservation
+    /// block and the regions of [`Fdt::reserved_memory`]. Reserved regions given by a size
+    /// rather than by `reg` are placed the way Linux does it, at the highest suitably aligned
+    /// address of their `alloc-ranges`, or of RAM if they have none. Only the first 16 of these
+    /// are honored.
+    pub fn usable_memory<'fdt>(&'fdt self) -> Result<UsableMemory<'d, 'fdt>, FdtParseError<'d>> {
+        for entry in self.reserved_memory_map() {
+            entry?;
+        }
+
+        let reserved = self.find_by_path("/reserved-memory")?;
+        let mut usable = UsableMemory {
+            fdt: self,
+            reserved: reserved.clone(),
+            memory: self.memory()?,
+            current: None,
+            dynamic: [Region {
+                address: 0,
+                size: 0,
+            }; MAX_DYNAMIC_RESERVATIONS],
+            n_dynamic: 0,
+        };
+
+        let dynamic = reserved
+            .iter()
+            .flat_map(reserved_children)
+            .filter(|r| r.regions().is_none());
+        for reserved in dynamic.take(MAX_DYNAMIC_RESERVATIONS) {
+            let Some(size) = reserved.size().filter(|&size| size > 0) else {
+                continue;
+            };
+            let alignment = reserved.alignment().unwrap_or(1).max(1);
+
+            if let Some(region) = allocate(usable.clone(), size, alignment, reserved.alloc_ranges())
+            {
+                usable.dynamic[usable.n_dynamic] = region;
+                usable.n_dynamic += 1;
+            }
+        }
+
+        Ok(usable)
+    }
+}
+
+/// Returns the highest region of `size` bytes aligned on `alignment` within the `free` regions
+/// and the `alloc_ranges`.
+fn allocate(
+    free: UsableMemory,
+    size: u64,
+    alignment: u64,
+    alloc_ranges: Option<Reg>,
+) -> Option<Region> {
+    let mut best: Option<u64> = None;
+    let mut fit = |start: u64, end: u64| {
+        if let Some(top) = end.checked_sub(size) {
+            let address = top - top % alignment;
+            if address >= start && best.is_none_or(|best| address > best) {
+                best = Some(address);
+            }
+        }
+    };
+
+    for region in free {
+        match &alloc_ranges {
+            Some(ranges) => {
+                for range in ranges.clone() {
+                    fit(
+                        region.address.max(range.address),
+                        region.end().min(range.end()),
+                    );
+                }
+            }
+            None => fit(region.address, region.end()),
+        }
+    }
+
+    best.map(|address| Region { address, size })
+}
+
+/// A child of `/reserved-memory`, describing memory set aside for a specific use.
+#[derive(Debug, Clone)]
+pub struct ReservedMemory<'d, 'fdt> {
+    pub node: Node<'d, 'fdt>,
+}
+
+impl<'d, 'fdt> ReservedMemory<'d, 'fdt> {
+    /// Returns the regions given by `reg`, or `None` if the region is allocated dynamically.
+    pub fn regions(&self) -> Option<Reg<'d, 'fdt>> {
+        self.node.reg()
+    }
+
+    /// Returns the size of a dynamically allocated region.
+    pub fn size(&self) -> Option<u64> {
+        self.read_size("size")
+    }
+
+    /// Returns the alignment of a dynamically allocated region.
+    pub fn alignment(&self) -> Option<u64> {
+        self.read_size("alignment")
+    }
+
+    /// Returns the regions a dynamically allocated region must be placed in.
+    pub fn alloc_ranges(&self) -> Option<Reg<'d, 'fdt>> {
+        self.node.regions("alloc-ranges")
+    }
+
+    /// Returns whether the region must not be mapped by the operating system.
+    pub fn no_map(&self) -> bool {
+        self.node.raw_property("no-map").is_some()
+    }
+
+    /// Returns whether the operating system may use the region when its owner does not.
+    pub fn reusable(&self) -> bool {
+        self.node.raw_property("reusable").is_some()
+    }
+
+    /// Reads a property holding a single size, encoded with the `#size-cells` of the parent.
+    fn read_size(&self, name: &str) -> Option<u64> {
+        let cells = self.node.parent()?.size_cells();
+        match read_cells(self.node.raw_property(name)?, cells)? {
+            ([], size) => size.try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over the regions of RAM, returned by [`Fdt::memory`].
+#[derive(Clone)]
+pub struct Memory<'d, 'fdt> {
+    nodes: NodeIter<'d, 'fdt>,
+    reg: Option<Reg<'d, 'fdt>>,
+}
+
+impl Iterator for Memory<'_, '_> {
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(region) = self.reg.as_mut().and_then(Iterator::next) {
+                return Some(region);
+            }
+
+            let node = self.nodes.next()?;
+            self.reg = if is_memory(&node) { node.reg() } else { None };
+        }
+    }
+}
+
+/// Iterator over the regions of RAM free for use, returned by [`Fdt::usable_memory`].
+#[derive(Clone)]
+pub struct UsableMemory<'d, 'fdt> {
+    fdt: &'fdt Fdt<'d>,
+    reserved: Option<Node<'d, 'fdt>>,
+    memory: Memory<'d, 'fdt>,
+    /// Part of the current region of RAM left to go through.
+    current: Option<(u64, u64)>,
+    /// Dynamically allocated reserved regions, as placed when creating the iterator.
+    dynamic: [Region; MAX_DYNAMIC_RESERVATIONS],
+    n_dynamic: usize,
+}
+
+impl UsableMemory<'_, '_> {
+    /// Returns all the reserved regions.
+    fn reservations(&self) -> impl Iterator<Item = Region> + '_ {
+        let memreserve = self
+            .fdt
+            .reserved_memory_map()
+            .filter_map(Result::ok)
+            .map(|entry| Region {
+                address: entry.address,
+                size: entry.size,
+            });
+        let reserved = self
+            .reserved
+            .iter()
+            .flat_map(reserved_children)
+            .flat_map(|r| r.regions().into_iter().flatten());
+
+        memreserve
+            .chain(reserved)
+            .chain(self.dynamic[..self.n_dynamic].iter().copied())
+            .filter(|r| r.size > 0)
+    }
+}
+
+impl Iterator for UsableMemory<'_, '_> {
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (mut start, end) = match self.current.take() {
+                Some(current) => current,
+                None => {
+                    let region = self.memory.next()?;
+                    (region.address, region.end())
+                }
+            };
+
+            while start < end {
+                // Lowest reserved region overlapping what is left
+                let reserved = self
+                    .reservations()
+                    .filter(|r| r.address < end && r.end() > start)
+                    .min_by_key(|r| r.address);
+
+                match reserved {
+                    Some(reserved) if reserved.address <= start => start = reserved.end(),
+                    Some(reserved) => {
+                        self.current = Some((reserved.end(), end));
+                        return Some(Region {
+                            address: start,
+                            size: reserved.address - start,
+                        });
+                    }
+                    None => {
+                        return Some(Region {
+                            address: start,
+                            size: end - start,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{Fdt, FdtBuildError, FdtBuilder, FdtParseError, Node, PropValue, StringList};
+
+/// Errors returned by [`Fdt::apply_overlay`].
+#[derive(Debug)]
+pub enum OverlayError<'e> {
+    /// The base tree or the overlay could not be parsed.
+    Parse(FdtParseError<'e>),
+    /// A fragment has no target, or its target is not in the base tree.
+    TargetNotFound,
+    /// A label referenced by the overlay is not in the `__symbols__` node of the base tree, or
+    /// its node has no phandle.
+    MissingSymbol(&'e str),
+    /// A `__fixups__` or `__local_fixups__` entry is malformed, or does not point to a cell of
+    /// the overlay.
+    InvalidFixup,
+    /// The merged tree could not be built.
+    Build(FdtBuildError),
+}
+
+impl<'e> From<FdtParseError<'e>> for OverlayError<'e> {
+    fn from(value: FdtParseError<'e>) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl From<FdtBuildError> for OverlayError<'_> {
+    fn from(value: FdtBuildError) -> Self {
+        Self::Build(value)
+    }
+}
+
+impl<'d> Fdt<'d> {
+    /// Applies a device tree overlay to this tree, and returns the resulting blob.
+    ///
+    /// The overlay must have been compiled as a plugin: the phandles of its nodes are
+    /// renumbered after those of this tree according to `__local_fixups__`, its references
+    /// to labels of this tree are resolved with `__fixups__`, and the `__overlay__` node of
+    /// each of its fragments is merged into the node given by the `target` or `target-path`
+    /// property of the fragment. The labels of the overlay are added to `__symbols__`, so
+    /// that other overlays can be applied on top of it.
+    pub fn apply_overlay(&self, overlay: &Fdt<'d>) -> Result<Vec<u8>, OverlayError<'d>> {
+        let mut base = OwnedNode::new(&self.root_node()?);
+        let mut overlay = OwnedNode::new(&overlay.root_node()?);
+
+        // Renumber the phandles of the overlay after the ones of the base tree
+        let delta = base.max_phandle();
+        overlay.shift_phandles(delta);
+        if let Some(local_fixups) = overlay.take_child("__local_fixups__") {
+            overlay.shift_references(&local_fixups, delta)?;
+        }
+
+        // Resolve references to the labels of the base tree
+        if let Some(fixups) = overlay.take_child("__fixups__") {
+            for (label, value) in fixups.props {
+                let phandle = base
+                    .symbol(label)
+                    .and_then(|node| node.phandle())
+                    .ok_or(OverlayError::MissingSymbol(label))?;
+
+                let (_, fixups) = StringList::parse(&value).ok_or(OverlayError::InvalidFixup)?;
+                for fixup in fixups {
+                    overlay.fix_reference(fixup, phandle)?;
+                }
+            }
+        }
+
+        let symbols = overlay.take_child("__symbols__");
+
+        // Merge the fragments
+        let mut targets = Vec::new();
+        for mut fragment in core::mem::take(&mut overlay.children) {
+            let Some(contents) = fragment.take_child("__overlay__") else {
+                continue;
+            };
+
+            let path = if let Some(target) = fragment.property("target") {
+                let (_, phandle) = u32::parse(target).ok_or(OverlayError::TargetNotFound)?;
+                base.path_of(phandle).ok_or(OverlayError::TargetNotFound)?
+            } else if let Some(path) = fragment.property("target-path") {
+                let (_, path) = <&str>::parse(path).ok_or(OverlayError::TargetNotFound)?;
+                String::from(path)
+            } else {
+                return Err(OverlayError::TargetNotFound);
+            };
+
+            base.find_mut(&path)
+                .ok_or(OverlayError::TargetNotFound)?
+                .merge(contents);
+            targets.push((fragment.name, path));
+        }
+
+        // Translate the paths of the labels of the overlay to the merged tree
+        if let Some(symbols) = symbols {
+            if base.child("__symbols__").is_none() {
+                base.children.push(OwnedNode::empty("__symbols__"));
+            }
+            let base_symbols = base.find_mut("/__symbols__").unwrap();
+
+            for (label, value) in symbols.props {
+                let Some((_, path)) = <&str>::parse(&value) else {
+                    continue;
+                };
+                let Some(path) = targets.iter().find_map(|(fragment, target)| {
+                    let rest = path
+                        .strip_prefix('/')?
+                        .strip_prefix(fragment)?
+                        .strip_prefix("/__overlay__")
+                        .filter(|rest| rest.is_empty() || rest.starts_with('/'))?;
+                    let mut path = String::from(target.trim_end_matches('/'));
+                    path.push_str(rest);
+                    Some(path)
+                }) else {
+                    continue;
+                };
+
+                let mut value = Vec::from(if path.is_empty() { "/" } else { &path });
+                value.push(0);
+                base_symbols.set_property(label, value);
+            }
+        }
+
+        let mut builder = FdtBuilder::new();
+        builder.set_boot_cpuid(self.boot_cpuid());
+        for entry in self.reserved_memory_map() {
+            let entry = entry?;
+            builder.reserve_memory(entry.address, entry.size);
+        }
+        base.build(&mut builder)?;
+        Ok(builder.finish()?)
+    }
+}
+
+/// Mutable copy of a node and its descendants.
+struct OwnedNode<'d> {
+    name: &'d str,
+    props: Vec<(&'d str, Vec<u8>)>,
+    children: Vec<OwnedNode<'d>>,
+}
+
+impl<'d> OwnedNode<'d> {
+    fn new(node: &Node<'d, '_>) -> Self {
+        Self {
+            name: node.name,
+            props: node
+                .properties()
+                .filter_map(|p| Some((p.name()?, Vec::from(p.raw_value()))))
+                .collect(),
+            children: node.children().map(|child| Self::new(&child)).collect(),
+        }
+    }
+
+    fn empty(name: &'d str) -> Self {
+        Self {
+            name,
+            props: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn property(&self, name: &str) -> Option<&[u8]> {
+        self.props
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    fn property_mut(&mut self, name: &str) -> Option<&mut Vec<u8>> {
+        self.props
+            .iter_mut()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value)
+    }
+
+    fn set_property(&mut self, name: &'d str, value: Vec<u8>) {
+        match self.property_mut(name) {
+            Some(old) => *old = value,
+            None => self.props.push((name, value)),
+        }
+    }
+
+    fn phandle(&self) -> Option<u32> {
+        let value = self
+            .property("phandle")
+            .or_else(|| self.property("linux,phandle"))?;
+        Some(u32::parse(value)?.1)
+    }
+
+    fn child(&self, name: &str) -> Option<&Self> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn child_mut(&mut self, name: &str) -> Option<&mut Self> {
+        self.children.iter_mut().find(|c| c.name == name)
+    }
+
+    fn take_child(&mut self, name: &str) -> Option<Self> {
+        let i = self.children.iter().position(|c| c.name == name)?;
+        Some(self.children.remove(i))
+    }
+
+    /// Returns the descendant of this node at `path`, relative to this node.
+    fn find(&self, path: &str) -> Option<&Self> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| node.child(name))
+    }
+
+    fn find_mut(&mut self, path: &str) -> Option<&mut Self> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| node.child_mut(name))
+    }
+
+    /// Returns the path of the descendant of this node whose phandle is `phandle`.
+    fn path_of(&self, phandle: u32) -> Option<String> {
+        if self.phandle() == Some(phandle) {
+            return Some(String::from("/"));
+        }
+
+        self.children.iter().find_map(|child| {
+            let rest = child.path_of(phandle)?;
+            let mut path = String::from("/");
+            path.push_str(child.name);
+            if rest != "/" {
+                path.push_str(&rest);
+            }
+            Some(path)
+        })
+    }
+
+    /// Returns the node with the label `label` in the `__symbols__` node of this root node.
+    fn symbol(&self, label: &str) -> Option<&Self> {
+        let value = self.child("__symbols__")?.property(label)?;
+        let (_, path) = <&str>::parse(value)?;
+        self.find(path)
+    }
+
+    fn max_phandle(&self) -> u32 {
+        self.children
+            .iter()
+            .map(|child| child.max_phandle())
+            .chain(self.phandle())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn shift_phandles(&mut self, delta: u32) {
+        for name in ["phandle", "linux,phandle"] {
+            if let Some(cell) = self.property_mut(name).and_then(|v| cell_mut(v, 0)) {
+                update_cell(cell, |phandle| phandle.wrapping_add(delta));
+            }
+        }
+        for child in &mut self.children {
+            child.shift_phandles(delta);
+        }
+    }
+
+    /// Shifts the references of this node and its descendants to the nodes of the overlay,
+    /// whose offsets in each property are given by the matching node of `__local_fixups__`.
+    fn shift_references(&mut self, fixups: &Self, delta: u32) -> Result<(), OverlayError<'d>> {
+        for (name, offsets) in &fixups.props {
+            let value = self.property_mut(name).ok_or(OverlayError::InvalidFixup)?;
+            for offset in offsets.as_chunks::<4>().0 {
+                let cell = cell_mut(value, u32::from_be_bytes(*offset) as usize)
+                    .ok_or(OverlayError::InvalidFixup)?;
+                update_cell(cell, |phandle| phandle.wrapping_add(delta));
+            }
+        }
+
+        for child_fixups in &fixups.children {
+            self.child_mut(child_fixups.name)
+                .ok_or(OverlayError::InvalidFixup)?
+                .shift_references(child_fixups, delta)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `phandle` in the cell given by a `__fixups__` entry, `<path>:<property>:<offset>`.
+    fn fix_reference(&mut self, fixup: &str, phandle: u32) -> Result<(), OverlayError<'d>> {
+        let mut parts = fixup.rsplitn(3, ':');
+        let (Some(offset), Some(name), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(OverlayError::InvalidFixup);
+        };
+
+        let offset = offset.parse().map_err(|_| OverlayError::InvalidFixup)?;
+        let value = self
+            .find_mut(path)
+            .and_then(|node| node.property_mut(name))
+            .ok_or(OverlayError::InvalidFixup)?;
+        let cell = cell_mut(value, offset).ok_or(OverlayError::InvalidFixup)?;
+        update_cell(cell, |_| phandle);
+        Ok(())
+    }
+
+    /// Merges `other` into this node, its properties replacing the ones with the same name.
+    fn merge(&mut self, other: Self) {
+        for (name, value) in other.props {
+            self.set_property(name, value);
+        }
+        for child in other.children {
+            match self.child_mut(child.name) {
+                Some(node) => node.merge(child),
+                None => self.children.push(child),
+            }
+        }
+    }
+
+    fn build(&self, builder: &mut FdtBuilder) -> Result<(), FdtBuildError> {
+        builder.begin_node(self.name)?;
+        for (name, value) in &self.props {
+            builder.property(name, value.as_slice())?;
+        }
+        for child in &self.children {
+            child.build(builder)?;
+        }
+        builder.end_node()
+    }
+}
+
+fn cell_mut(value: &mut [u8], offset: usize) -> Option<&mut [u8; 4]> {
+    value
+        .get_mut(offset..offset.checked_add(4)?)?
+        .try_into()
+        .ok()
+}
+
+fn update_cell(cell: &mut [u8; 4], f: impl FnOnce(u32) -> u32) {
+    *cell = f(u32::from_be_bytes(*cell)).to_be_bytes();
+}
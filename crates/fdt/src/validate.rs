@@ -0,0 +1,240 @@
+use core::ops::Range;
+
+use crate::{FDT_BEGIN_NODE, FDT_END, FDT_END_NODE, FDT_NOP, FDT_PROP, Fdt, HEADER_SIZE, Node};
+
+/// Offsets of the fields of the header describing the blocks.
+const OFF_DT_STRUCT_FIELD: usize = 8;
+const OFF_DT_STRINGS_FIELD: usize = 12;
+const OFF_MEM_RSVMAP_FIELD: usize = 16;
+const SIZE_DT_STRUCT_FIELD: usize = 36;
+
+/// Defect found by [`Fdt::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdtValidationError {
+    /// Offset in the blob of the faulty header field, token or block.
+    pub offset: usize,
+    pub kind: ValidationErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// A block is outside of the blob, or overlaps the header or another block.
+    BlockOutOfBounds,
+    /// A block does not start or end on the boundary required by the format.
+    Misaligned,
+    /// The memory reservation block is not terminated by an empty entry.
+    UnterminatedReservations,
+    /// The structure block ends in the middle of a token.
+    Truncated,
+    /// A token of the structure block is not one of the known ones.
+    InvalidToken(u32),
+    /// The root node has a name, or another node has an empty name, one containing a `/` or one
+    /// which is not valid UTF-8.
+    InvalidNodeName,
+    /// The name of a property is not a non-empty UTF-8 string of the strings block.
+    InvalidPropertyName,
+    /// The value of a property extends past the end of the structure block.
+    PropertyOutOfBounds,
+    /// A property is outside of any node, or follows a child of its node.
+    MisplacedProperty,
+    /// A node is closed without being opened or left open, or there are several root nodes or
+    /// none.
+    UnbalancedNodes,
+    /// Data follows the `FDT_END` token closing the structure block.
+    TrailingData,
+    /// A node has two children with the same name.
+    DuplicateNode,
+    /// A node has two properties with the same name.
+    DuplicateProperty,
+}
+
+fn error(offset: usize, kind: ValidationErrorKind) -> FdtValidationError {
+    FdtValidationError { offset, kind }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos.checked_add(4)?)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+impl Fdt<'_> {
+    /// Checks that the blob is well-formed, which [`Fdt::from_bytes`] only does for its header.
+    ///
+    /// The blocks must fit in the blob without overlapping, the structure block must be a
+    /// well-formed token stream describing a single tree, with properties named by strings of
+    /// the strings block and values within bounds, and no node may have two children or two
+    /// properties with the same name. The other accessors of `Fdt` can panic or silently stop on
+    /// blobs failing these checks.
+    pub fn validate(&self) -> Result<(), FdtValidationError> {
+        use ValidationErrorKind::*;
+
+        let total = self.data.len();
+        let block = |off: u32, size: u32| {
+            let (off, size) = (off as usize, size as usize);
+            off.checked_add(size)
+                .filter(|&end| off >= HEADER_SIZE && end <= total)
+                .map(|end| off..end)
+        };
+
+        let structs = block(self.hdr.off_dt_struct, self.hdr.size_dt_struct)
+            .ok_or(error(OFF_DT_STRUCT_FIELD, BlockOutOfBounds))?;
+        if !structs.start.is_multiple_of(4) {
+            return Err(error(OFF_DT_STRUCT_FIELD, Misaligned));
+        }
+        if !structs.len().is_multiple_of(4) {
+            return Err(error(SIZE_DT_STRUCT_FIELD, Misaligned));
+        }
+
+        let strings = block(self.hdr.off_dt_strings, self.hdr.size_dt_strings)
+            .ok_or(error(OFF_DT_STRINGS_FIELD, BlockOutOfBounds))?;
+
+        // The size of the memory reservation block is given by its terminating entry
+        let rsvmap = self.hdr.off_mem_rsvmap as usize;
+        if !(HEADER_SIZE..=total).contains(&rsvmap) {
+            return Err(error(OFF_MEM_RSVMAP_FIELD, BlockOutOfBounds));
+        }
+        if !rsvmap.is_multiple_of(8) {
+            return Err(error(OFF_MEM_RSVMAP_FIELD, Misaligned));
+        }
+        let entries = self.data[rsvmap..]
+            .as_chunks::<16>()
+            .0
+            .iter()
+            .position(|entry| *entry == [0; 16])
+            .ok_or(error(rsvmap, UnterminatedReservations))?;
+        let rsvmap = rsvmap..rsvmap + (entries + 1) * 16;
+
+        if overlaps(&rsvmap, &structs) {
+            return Err(error(OFF_DT_STRUCT_FIELD, BlockOutOfBounds));
+        }
+        if overlaps(&rsvmap, &strings) || overlaps(&structs, &strings) {
+            return Err(error(OFF_DT_STRINGS_FIELD, BlockOutOfBounds));
+        }
+
+        self.validate_structs()?;
+
+        let root = self
+            .root_node()
+            .map_err(|_| error(structs.start, UnbalancedNodes))?;
+        validate_names(&root, structs.start)
+    }
+
+    /// Checks the token stream of the structure block, once the header is known to be valid.
+    fn validate_structs(&self) -> Result<(), FdtValidationError> {
+        use ValidationErrorKind::*;
+
+        let base = self.hdr.off_dt_struct as usize;
+        let data = self.structs();
+        let mut pos = 0;
+        let mut depth = 0usize;
+        let mut has_root = false;
+        // Whether the current node can still have properties, before its first child
+        let mut in_props = false;
+
+        loop {
+            let offset = base + pos;
+            let token = read_u32(data, pos).ok_or(error(offset, Truncated))?;
+            pos += 4;
+
+            match token {
+                FDT_BEGIN_NODE => {
+                    if depth == 0 && has_root {
+                        return Err(error(offset, UnbalancedNodes));
+                    }
+
+                    let len = data[pos..]
+                        .iter()
+                        .position(|&b| b == 0)
+                        .ok_or(error(offset, Truncated))?;
+                    let valid = match core::str::from_utf8(&data[pos..pos + len]) {
+                        Ok(name) if depth == 0 => name.is_empty(),
+                        Ok(name) => !name.is_empty() && !name.contains('/'),
+                        Err(_) => false,
+                    };
+                    if !valid {
+                        return Err(error(offset, InvalidNodeName));
+                    }
+
+                    pos = (pos + len + 1).next_multiple_of(4);
+                    depth += 1;
+                    has_root = true;
+                    in_props = true;
+                }
+                FDT_END_NODE => {
+                    depth = depth.checked_sub(1).ok_or(error(offset, UnbalancedNodes))?;
+                    in_props = false;
+                }
+                FDT_PROP => {
+                    if !in_props {
+                        return Err(error(offset, MisplacedProperty));
+                    }
+
+                    let len = read_u32(data, pos).ok_or(error(offset, Truncated))?;
+                    let name_off = read_u32(data, pos + 4).ok_or(error(offset, Truncated))?;
+                    pos += 8;
+
+                    let end = pos
+                        .checked_add(len as usize)
+                        .filter(|&end| end <= data.len())
+                        .ok_or(error(offset, PropertyOutOfBounds))?;
+                    if !self.is_valid_name(name_off) {
+                        return Err(error(offset, InvalidPropertyName));
+                    }
+
+                    pos = end.next_multiple_of(4);
+                }
+                FDT_NOP => {}
+                FDT_END => {
+                    if depth != 0 || !has_root {
+                        return Err(error(offset, UnbalancedNodes));
+                    }
+                    if pos != data.len() {
+                        return Err(error(base + pos, TrailingData));
+                    }
+                    return Ok(());
+                }
+                token => return Err(error(offset, InvalidToken(token))),
+            }
+        }
+    }
+
+    /// Returns whether `off` is the offset of a non-empty UTF-8 string of the strings block.
+    fn is_valid_name(&self, off: u32) -> bool {
+        let start = self.hdr.off_dt_strings as usize;
+        let strings = &self.data[start..start + self.hdr.size_dt_strings as usize];
+
+        let Some(s) = strings.get(off as usize..) else {
+            return false;
+        };
+        match s.iter().position(|&b| b == 0) {
+            Some(len) => len > 0 && core::str::from_utf8(&s[..len]).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Checks that no node of the subtree of `node` has two children or two properties with the
+/// same name.
+fn validate_names(node: &Node, base: usize) -> Result<(), FdtValidationError> {
+    for (i, prop) in node.properties().enumerate() {
+        if node.properties().take(i).any(|p| p.name() == prop.name()) {
+            return Err(error(
+                base + node.off,
+                ValidationErrorKind::DuplicateProperty,
+            ));
+        }
+    }
+
+    for (i, child) in node.children().enumerate() {
+        if node.children().take(i).any(|c| c.name == child.name) {
+            return Err(error(base + child.off, ValidationErrorKind::DuplicateNode));
+        }
+        validate_names(&child, base)?;
+    }
+
+    Ok(())
+}
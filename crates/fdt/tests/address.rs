@@ -0,0 +1,165 @@
+use fdt::{Fdt, FdtBuilder, Region};
+
+/// Builds a tree with nested buses using different cell sizes.
+fn build_buses() -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+
+    b.begin_node("").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+
+    b.begin_node("memory@80000000").unwrap();
+    b.property(
+        "reg",
+        [(0x80000000u64, 0x8000000u64), (0x100000000, 0x40000000)],
+    )
+    .unwrap();
+    b.end_node().unwrap();
+
+    // 32-bit bus mapped at 0x10000000
+    b.begin_node("soc").unwrap();
+    b.property("#address-cells", 1u32).unwrap();
+    b.property("#size-cells", 1u32).unwrap();
+    b.property("ranges", [(0u32, 0x10000000u64, 0x100000u32)])
+        .unwrap();
+
+    b.begin_node("uart@1000").unwrap();
+    b.property(
+        "reg",
+        [(0x1000u32, 0x100u32), (0x200000, 0x10), (0x2000, 0x100)],
+    )
+    .unwrap();
+    b.end_node().unwrap();
+
+    // Identity-mapped child bus
+    b.begin_node("bus@4000").unwrap();
+    b.property("#address-cells", 1u32).unwrap();
+    b.property("#size-cells", 1u32).unwrap();
+    b.property("ranges", ()).unwrap();
+    b.begin_node("timer@4000").unwrap();
+    b.property("reg", [(0x4000u32, 0x10u32)]).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    // Bus with its own address space
+    b.begin_node("i2c@5000").unwrap();
+    b.property("reg", [(0x5000u32, 0x100u32)]).unwrap();
+    b.property("#address-cells", 1u32).unwrap();
+    b.property("#size-cells", 0u32).unwrap();
+    b.begin_node("rtc@68").unwrap();
+    b.property("reg", 0x68u32).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+fn regions(fdt: &Fdt, path: &str) -> Option<Vec<(u64, u64)>> {
+    let node = fdt.find_by_path(path).unwrap().unwrap();
+    Some(node.reg()?.map(|r| (r.address, r.size)).collect())
+}
+
+#[test]
+fn cells() {
+    let blob = build_buses();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    let root = fdt.root_node().unwrap();
+    assert_eq!((root.address_cells(), root.size_cells()), (2, 2));
+
+    let i2c = fdt.find_by_path("/soc/i2c@5000").unwrap().unwrap();
+    assert_eq!((i2c.address_cells(), i2c.size_cells()), (1, 0));
+
+    // Defaults from the specification
+    let uart = fdt.find_by_path("/soc/uart@1000").unwrap().unwrap();
+    assert_eq!((uart.address_cells(), uart.size_cells()), (2, 1));
+}
+
+#[test]
+fn translate_reg() {
+    let blob = build_buses();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    assert_eq!(
+        regions(&fdt, "/memory@80000000"),
+        Some(vec![(0x80000000, 0x8000000), (0x100000000, 0x40000000)])
+    );
+
+    // The entry outside of the ranges of the bus is skipped
+    assert_eq!(
+        regions(&fdt, "/soc/uart@1000"),
+        Some(vec![(0x10001000, 0x100), (0x10002000, 0x100)])
+    );
+    assert_eq!(
+        regions(&fdt, "/soc/bus@4000/timer@4000"),
+        Some(vec![(0x10004000, 0x10)])
+    );
+    assert_eq!(
+        regions(&fdt, "/soc/i2c@5000"),
+        Some(vec![(0x10005000, 0x100)])
+    );
+
+    // I2C addresses are not visible from the CPU
+    assert_eq!(regions(&fdt, "/soc/i2c@5000/rtc@68"), None);
+    assert_eq!(regions(&fdt, "/soc"), None);
+
+    let soc = fdt.find_by_path("/soc").unwrap().unwrap();
+    assert_eq!(soc.translate_address(0xfffff), Some(0x100fffff));
+    assert_eq!(soc.translate_address(0x100000), None);
+}
+
+#[test]
+fn qemu_reg() {
+    let fdt = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+
+    let uart = fdt.find_compatible("ns16550a").unwrap().unwrap();
+    let mut reg = uart.reg().unwrap();
+    assert_eq!(
+        reg.next(),
+        Some(Region {
+            address: 0x10000000,
+            size: 0x100
+        })
+    );
+    assert_eq!(reg.next(), None);
+}
+
+/// Builds a tree with a device at 0x10 on a bus mapped through `ranges`, the root using
+/// `root_address_cells` address cells.
+fn build_bogus_cells(root_address_cells: u32, ranges: (u32, [u32; 4], u32)) -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+
+    b.begin_node("").unwrap();
+    b.property("#address-cells", root_address_cells).unwrap();
+    b.property("#size-cells", 1u32).unwrap();
+
+    b.begin_node("soc").unwrap();
+    b.property("reg", (0u32, 0x100u32)).unwrap();
+    b.property("#address-cells", 1u32).unwrap();
+    b.property("#size-cells", 1u32).unwrap();
+    b.property("ranges", ranges).unwrap();
+    b.begin_node("dev@10").unwrap();
+    b.property("reg", (0x10u32, 0x10u32)).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+#[test]
+fn bogus_cells() {
+    // Cell counts overflowing when added together
+    let blob = build_bogus_cells(u32::MAX, (0, [0; 4], 0x100));
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+    fdt.validate().unwrap();
+    assert_eq!(regions(&fdt, "/soc"), Some(vec![]));
+    assert_eq!(regions(&fdt, "/soc/dev@10"), Some(vec![]));
+
+    // Translated address overflowing
+    let blob = build_bogus_cells(4, (0, [u32::MAX; 4], 0x100));
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+    assert_eq!(regions(&fdt, "/soc/dev@10"), Some(vec![]));
+}
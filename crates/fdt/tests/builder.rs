@@ -0,0 +1,173 @@
+use fdt::{Fdt, FdtBuildError, FdtBuilder, Node, PropEncodedArray, StringList};
+
+/// Builds a small tree looking like the one of a RISC-V board.
+fn build_board() -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.set_boot_cpuid(1);
+    b.reserve_memory(0x80000000, 0x40000);
+
+    b.begin_node("").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+    b.property("compatible", "riscv-virtio").unwrap();
+
+    b.begin_node("memory@80000000").unwrap();
+    b.property("device_type", "memory").unwrap();
+    b.property("reg", [(0x80000000u64, 0x8000000u64)]).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("soc").unwrap();
+    b.property("compatible", "simple-bus").unwrap();
+    b.property("ranges", ()).unwrap();
+
+    b.begin_node("test@100000").unwrap();
+    b.property("compatible", ["sifive,test1", "sifive,test0", "syscon"])
+        .unwrap();
+    b.property("reg", [(0x100000u64, 0x1000u64)]).unwrap();
+    b.property("local-mac-address", [0x52u8, 0x54, 0x00, 0x12, 0x34, 0x56])
+        .unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+#[test]
+fn build_and_parse() {
+    let blob = build_board();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    assert_eq!(fdt.size() as usize, blob.len());
+    assert_eq!(fdt.boot_cpuid(), 1);
+
+    let reserved = fdt
+        .reserved_memory_map()
+        .map(|e| e.map(|e| (e.address, e.size)).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(reserved, [(0x80000000, 0x40000)]);
+
+    let root = fdt.root_node().unwrap();
+    assert_eq!(root.property::<u32>("#address-cells"), Some(2));
+    assert_eq!(root.property::<&str>("compatible"), Some("riscv-virtio"));
+    assert_eq!(root.children().count(), 2);
+
+    let memory = fdt.find_by_path("/memory@80000000").unwrap().unwrap();
+    let mut regs: PropEncodedArray<(u64, u64)> = memory.property("reg").unwrap();
+    assert_eq!(regs.next(), Some((0x80000000, 0x8000000)));
+    assert_eq!(regs.next(), None);
+
+    let soc = fdt.find_by_path("/soc").unwrap().unwrap();
+    let ranges = soc.properties().find(|p| p.name() == Some("ranges"));
+    assert_eq!(ranges.unwrap().raw_value(), b"");
+
+    let test = fdt.find_compatible("syscon").unwrap().unwrap();
+    assert_eq!(test.identifier(), "test@100000");
+    assert_eq!(test.parent().unwrap().identifier(), "soc");
+    let compatible: StringList = test.property("compatible").unwrap();
+    assert_eq!(
+        compatible.collect::<Vec<_>>(),
+        ["sifive,test1", "sifive,test0", "syscon"]
+    );
+    let mac = test
+        .properties()
+        .find(|p| p.name() == Some("local-mac-address"))
+        .unwrap();
+    assert_eq!(mac.raw_value(), [0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+}
+
+#[test]
+fn deduplicate_strings() {
+    let blob = build_board();
+
+    let occurrences = |needle: &[u8]| blob.windows(needle.len()).filter(|w| w == &needle).count();
+    assert_eq!(occurrences(b"compatible\0"), 1);
+    assert_eq!(occurrences(b"reg\0"), 1);
+}
+
+/// Copies `node` and its descendants into `b`.
+fn copy_node(b: &mut FdtBuilder, node: &Node) {
+    b.begin_node(node.identifier()).unwrap();
+    for prop in node.properties() {
+        b.property(prop.name().unwrap(), prop.raw_value()).unwrap();
+    }
+    for child in node.children() {
+        copy_node(b, &child);
+    }
+    b.end_node().unwrap();
+}
+
+/// Checks that `a` and `b` have the same names, properties and children.
+fn assert_same_tree(a: &Node, b: &Node) {
+    assert_eq!(a.identifier(), b.identifier());
+
+    let props = |n: &Node| {
+        n.properties()
+            .map(|p| (p.name().unwrap().to_string(), p.raw_value().to_vec()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(props(a), props(b));
+
+    let (a, b) = (
+        a.children().collect::<Vec<_>>(),
+        b.children().collect::<Vec<_>>(),
+    );
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(&b) {
+        assert_same_tree(a, b);
+    }
+}
+
+#[test]
+fn rebuild_qemu_dtb() {
+    let original = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+
+    let mut b = FdtBuilder::new();
+    b.set_boot_cpuid(original.boot_cpuid());
+    copy_node(&mut b, &original.root_node().unwrap());
+    let blob = b.finish().unwrap();
+
+    let rebuilt = Fdt::from_bytes(&blob).unwrap();
+    assert_same_tree(
+        &original.root_node().unwrap(),
+        &rebuilt.root_node().unwrap(),
+    );
+}
+
+#[test]
+fn build_errors() {
+    // Properties need a node, and must come before its children
+    let mut b = FdtBuilder::new();
+    assert_eq!(
+        b.property("model", "board"),
+        Err(FdtBuildError::MisplacedProperty)
+    );
+    b.begin_node("").unwrap();
+    b.begin_node("cpus").unwrap();
+    b.end_node().unwrap();
+    assert_eq!(
+        b.property("model", "board"),
+        Err(FdtBuildError::MisplacedProperty)
+    );
+
+    // Invalid names
+    assert_eq!(b.begin_node(""), Err(FdtBuildError::InvalidName));
+    assert_eq!(b.begin_node("a/b"), Err(FdtBuildError::InvalidName));
+    b.begin_node("soc").unwrap();
+    assert_eq!(b.property("", 1u32), Err(FdtBuildError::InvalidName));
+    b.end_node().unwrap();
+
+    // A single root node, named ""
+    b.end_node().unwrap();
+    assert_eq!(b.end_node(), Err(FdtBuildError::UnbalancedNodes));
+    assert_eq!(b.begin_node(""), Err(FdtBuildError::UnbalancedNodes));
+    assert!(b.finish().is_ok());
+
+    let mut b = FdtBuilder::new();
+    assert_eq!(b.begin_node("root"), Err(FdtBuildError::InvalidName));
+    assert_eq!(b.finish(), Err(FdtBuildError::UnbalancedNodes));
+
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+    assert_eq!(b.finish(), Err(FdtBuildError::UnbalancedNodes));
+}
@@ -0,0 +1,123 @@
+use fdt::{Fdt, FdtBuilder};
+
+/// Builds a tree with aliases, and the given `/chosen` properties.
+fn build_chosen(f: impl FnOnce(&mut FdtBuilder)) -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+
+    b.begin_node("aliases").unwrap();
+    b.property("serial0", "/soc/serial@10000000").unwrap();
+    b.property("soc", "/soc").unwrap();
+    b.property("loop", "loop").unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("chosen").unwrap();
+    f(&mut b);
+    b.end_node().unwrap();
+
+    b.begin_node("soc").unwrap();
+    b.begin_node("serial@10000000").unwrap();
+    b.end_node().unwrap();
+    b.begin_node("serial@10001000").unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+#[test]
+fn aliases() {
+    let blob = build_chosen(|_| {});
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+    let find = |path| {
+        fdt.find_by_path(path)
+            .unwrap()
+            .map(|n| n.identifier().to_string())
+    };
+
+    assert_eq!(fdt.alias("serial0").unwrap(), Some("/soc/serial@10000000"));
+    assert_eq!(fdt.alias("serial1").unwrap(), None);
+
+    assert_eq!(find("serial0").as_deref(), Some("serial@10000000"));
+    assert_eq!(
+        find("soc/serial@10001000").as_deref(),
+        Some("serial@10001000")
+    );
+    assert_eq!(find("soc/").as_deref(), Some("soc"));
+    assert_eq!(find("serial1"), None);
+    assert_eq!(find("soc/missing"), None);
+    // Aliases must be absolute paths
+    assert_eq!(find("loop"), None);
+}
+
+#[test]
+fn chosen() {
+    let blob = build_chosen(|b| {
+        b.property("bootargs", "console=ttyS0 init=/bin/init")
+            .unwrap();
+        b.property("stdout-path", "serial0:115200n8").unwrap();
+        b.property("linux,initrd-start", 0x88000000u32).unwrap();
+        b.property("linux,initrd-end", 0x88200000u32).unwrap();
+        b.property("rng-seed", [1u8, 2, 3, 4, 5]).unwrap();
+    });
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    assert_eq!(fdt.chosen().unwrap().unwrap().identifier(), "chosen");
+    assert_eq!(
+        fdt.bootargs().unwrap(),
+        Some("console=ttyS0 init=/bin/init")
+    );
+
+    let stdout = fdt.stdout().unwrap().unwrap();
+    assert_eq!(stdout.node.identifier(), "serial@10000000");
+    assert_eq!(stdout.options, Some("115200n8"));
+    assert_eq!(stdout.baud_rate(), Some(115200));
+
+    assert_eq!(fdt.initrd().unwrap(), Some(0x88000000..0x88200000));
+    assert_eq!(fdt.rng_seed().unwrap(), Some([1u8, 2, 3, 4, 5].as_slice()));
+}
+
+#[test]
+fn chosen_variants() {
+    // 64-bit initrd addresses and a legacy console property
+    let blob = build_chosen(|b| {
+        b.property("linux,stdout-path", "/soc/serial@10001000")
+            .unwrap();
+        b.property("linux,initrd-start", 0x1_0000_0000u64).unwrap();
+        b.property("linux,initrd-end", 0x1_0040_0000u64).unwrap();
+    });
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    let stdout = fdt.stdout().unwrap().unwrap();
+    assert_eq!(stdout.node.identifier(), "serial@10001000");
+    assert_eq!(stdout.options, None);
+    assert_eq!(stdout.baud_rate(), None);
+    assert_eq!(fdt.initrd().unwrap(), Some(0x1_0000_0000..0x1_0040_0000));
+    assert_eq!(fdt.bootargs().unwrap(), None);
+    assert_eq!(fdt.rng_seed().unwrap(), None);
+
+    // Invalid or incomplete properties
+    let blob = build_chosen(|b| {
+        b.property("stdout-path", "serial1").unwrap();
+        b.property("linux,initrd-start", [0u8; 6]).unwrap();
+        b.property("linux,initrd-end", 0x1000u32).unwrap();
+    });
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+    assert!(fdt.stdout().unwrap().is_none());
+    assert_eq!(fdt.initrd().unwrap(), None);
+}
+
+#[test]
+fn qemu_chosen() {
+    let fdt = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+
+    let stdout = fdt.stdout().unwrap().unwrap();
+    assert_eq!(Some(stdout.node), fdt.find_compatible("ns16550a").unwrap());
+    assert_eq!(stdout.options, None);
+
+    assert_eq!(fdt.rng_seed().unwrap().map(|seed| seed.len()), Some(32));
+    assert_eq!(fdt.bootargs().unwrap(), None);
+    assert_eq!(fdt.initrd().unwrap(), None);
+    assert_eq!(fdt.alias("serial0").unwrap(), None);
+}
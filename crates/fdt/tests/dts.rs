@@ -0,0 +1,86 @@
+#![cfg(feature = "std")]
+
+use fdt::{Fdt, FdtBuilder};
+
+#[test]
+fn to_dts() {
+    let mut b = FdtBuilder::new();
+    b.reserve_memory(0x80000000, 0x40000);
+
+    b.begin_node("").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("compatible", ["acme,board", "acme,soc"])
+        .unwrap();
+
+    b.begin_node("intc").unwrap();
+    b.property("phandle", 1u32).unwrap();
+    b.property("interrupt-controller", ()).unwrap();
+    b.property("#interrupt-cells", 2u32).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("serial@1000").unwrap();
+    b.property("phandle", 2u32).unwrap();
+    b.property("interrupt-parent", 1u32).unwrap();
+    b.property("interrupts-extended", [1u32, 3, 4, 1, 5, 6])
+        .unwrap();
+    b.property("label", "say \"hi\"").unwrap();
+    b.property("mac", [0x02u8, 0x54, 0x00]).unwrap();
+    b.property("empty-string", "").unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("__symbols__").unwrap();
+    b.property("uart0", "/serial@1000").unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    let blob = b.finish().unwrap();
+
+    let dts = Fdt::from_bytes(&blob).unwrap().to_dts().unwrap();
+    assert_eq!(
+        dts,
+        r#"/dts-v1/;
+
+/memreserve/ 0x0000000080000000 0x0000000000040000;
+
+/ {
+	#address-cells = <0x02>;
+	compatible = "acme,board", "acme,soc";
+
+	intc: intc {
+		phandle = <0x01>;
+		interrupt-controller;
+		#interrupt-cells = <0x02>;
+	};
+
+	uart0: serial@1000 {
+		phandle = <0x02>;
+		interrupt-parent = <&intc>;
+		interrupts-extended = <&intc 0x03 0x04 &intc 0x05 0x06>;
+		label = "say \"hi\"";
+		mac = [02 54 00];
+		empty-string = [00];
+	};
+
+	__symbols__ {
+		uart0 = "/serial@1000";
+	};
+};
+"#
+    );
+}
+
+#[test]
+fn qemu_to_dts() {
+    let fdt = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+    let dts = fdt.to_dts().unwrap();
+
+    assert!(dts.starts_with("/dts-v1/;\n\n/ {\n"));
+    assert!(dts.contains("\t\tregmap = <&test_100000>;\n"));
+    assert!(dts.contains("\t\ttest_100000: test@100000 {\n"));
+    assert!(dts.contains("\t\t\tinterrupt-parent = <&plic_c000000>;\n"));
+    assert!(dts.contains(
+        "\t\t\tinterrupts-extended = <&interrupt_controller 0x0b &interrupt_controller 0x09>;\n"
+    ));
+    assert!(dts.contains("\t\t\tcompatible = \"sifive,test1\", \"sifive,test0\", \"syscon\";\n"));
+    assert!(dts.ends_with("\t};\n};\n"));
+}
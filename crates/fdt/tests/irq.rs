@@ -0,0 +1,183 @@
+use fdt::{Fdt, FdtBuilder};
+
+const CPU_INTC: u32 = 1;
+const PLIC: u32 = 2;
+const GPIO: u32 = 3;
+
+/// Builds a tree with interrupt controllers, and devices using them directly or through an
+/// interrupt nexus.
+fn build_irqs() -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+
+    b.begin_node("").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+
+    b.begin_node("cpu-intc").unwrap();
+    b.property("phandle", CPU_INTC).unwrap();
+    b.property("interrupt-controller", ()).unwrap();
+    b.property("#interrupt-cells", 1u32).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("plic@c000000").unwrap();
+    b.property("phandle", PLIC).unwrap();
+    b.property("interrupt-controller", ()).unwrap();
+    b.property("#interrupt-cells", 1u32).unwrap();
+    b.property("#address-cells", 0u32).unwrap();
+    b.property("interrupts-extended", [CPU_INTC, 11, CPU_INTC, 9])
+        .unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("gpio@10060000").unwrap();
+    b.property("phandle", GPIO).unwrap();
+    b.property("interrupt-controller", ()).unwrap();
+    b.property("#interrupt-cells", 2u32).unwrap();
+    b.property("interrupt-parent", PLIC).unwrap();
+    b.property("interrupts", 20u32).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("serial@10000000").unwrap();
+    b.property("interrupt-parent", PLIC).unwrap();
+    b.property("interrupts", 10u32).unwrap();
+    b.end_node().unwrap();
+
+    // The interrupt parent is inherited by the children of this node
+    b.begin_node("keys").unwrap();
+    b.property("interrupt-parent", GPIO).unwrap();
+    b.begin_node("power").unwrap();
+    b.property("interrupts", [5u32, 1, 6, 2]).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("mixed").unwrap();
+    b.property("interrupts-extended", [PLIC, 3, GPIO, 7, 0])
+        .unwrap();
+    b.end_node().unwrap();
+
+    // Legacy PCI interrupts are swizzled by slot
+    b.begin_node("pci@30000000").unwrap();
+    b.property("#address-cells", 3u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+    b.property("#interrupt-cells", 1u32).unwrap();
+    b.property("interrupt-map-mask", [0x1800u32, 0, 0, 7])
+        .unwrap();
+    let mut map = Vec::new();
+    for slot in 0..2u32 {
+        for pin in 1..=4u32 {
+            map.extend([slot << 11, 0, 0, pin, PLIC, 0x20 + (slot + pin - 1) % 4]);
+        }
+    }
+    b.property("interrupt-map", map.as_slice()).unwrap();
+
+    b.begin_node("ethernet@1,0").unwrap();
+    b.property("reg", [0x800u32, 0, 0, 0, 0]).unwrap();
+    b.property("interrupts", 1u32).unwrap();
+    b.end_node().unwrap();
+
+    // Slot 9 is masked to slot 1
+    b.begin_node("ethernet@9,0").unwrap();
+    b.property("reg", [0x4800u32, 0, 0, 0, 0]).unwrap();
+    b.property("interrupts", 2u32).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("unmapped@2,0").unwrap();
+    b.property("reg", [0x1000u32, 0, 0, 0, 0]).unwrap();
+    b.property("interrupts", 1u32).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+/// Returns the controllers and specifiers of the interrupts of the node at `path`.
+fn interrupts(fdt: &Fdt, path: &str) -> Vec<(String, Vec<u32>)> {
+    let node = fdt.find_by_path(path).unwrap().unwrap();
+    node.interrupts()
+        .map(|irq| {
+            (
+                irq.controller.identifier().to_string(),
+                irq.specifier.cells().collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn node_by_phandle() {
+    let fdt = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+
+    let plic = fdt.node_by_phandle(3).unwrap().unwrap();
+    assert_eq!(plic.identifier(), "plic@c000000");
+    assert_eq!(plic.phandle(), Some(3));
+    assert_eq!(plic.parent().unwrap().identifier(), "soc");
+    assert_eq!(Some(plic), fdt.find_compatible("riscv,plic0").unwrap());
+
+    let intc = fdt.node_by_phandle(2).unwrap().unwrap();
+    assert_eq!(intc.parent().unwrap().identifier(), "cpu@0");
+
+    assert!(fdt.node_by_phandle(0x1234).unwrap().is_none());
+}
+
+#[test]
+fn qemu_interrupts() {
+    let fdt = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+
+    let uart = fdt.find_compatible("ns16550a").unwrap().unwrap();
+    let parent = uart.interrupt_parent().unwrap();
+    assert_eq!(parent.identifier(), "plic@c000000");
+    assert!(parent.is_interrupt_controller());
+    assert_eq!(parent.interrupt_cells(), Some(1));
+
+    let irqs = uart.interrupts().collect::<Vec<_>>();
+    assert_eq!(irqs.len(), 1);
+    assert_eq!(irqs[0].controller, parent);
+    assert_eq!(irqs[0].specifier.len(), 1);
+    assert_eq!(irqs[0].specifier.cell(0), Some(0x0a));
+
+    assert_eq!(
+        interrupts(&fdt, "/soc/plic@c000000"),
+        [
+            ("interrupt-controller".to_string(), vec![0x0b]),
+            ("interrupt-controller".to_string(), vec![0x09])
+        ]
+    );
+
+    // No interrupts
+    assert_eq!(interrupts(&fdt, "/soc/test@100000"), []);
+}
+
+#[test]
+fn resolve_interrupts() {
+    let blob = build_irqs();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+    let irq = |controller: &str, cells: &[u32]| (controller.to_string(), cells.to_vec());
+
+    assert_eq!(
+        interrupts(&fdt, "/serial@10000000"),
+        [irq("plic@c000000", &[10])]
+    );
+    assert_eq!(
+        interrupts(&fdt, "/gpio@10060000"),
+        [irq("plic@c000000", &[20])]
+    );
+    assert_eq!(
+        interrupts(&fdt, "/keys/power"),
+        [irq("gpio@10060000", &[5, 1]), irq("gpio@10060000", &[6, 2])]
+    );
+    assert_eq!(
+        interrupts(&fdt, "/mixed"),
+        [irq("plic@c000000", &[3]), irq("gpio@10060000", &[7, 0])]
+    );
+
+    // Through the interrupt map
+    assert_eq!(
+        interrupts(&fdt, "/pci@30000000/ethernet@1,0"),
+        [irq("plic@c000000", &[0x21])]
+    );
+    assert_eq!(
+        interrupts(&fdt, "/pci@30000000/ethernet@9,0"),
+        [irq("plic@c000000", &[0x22])]
+    );
+    assert_eq!(interrupts(&fdt, "/pci@30000000/unmapped@2,0"), []);
+}
@@ -0,0 +1,142 @@
+use fdt::{Fdt, FdtBuilder, Region};
+
+/// Builds a tree with RAM split across several nodes, and reservations of every kind.
+fn build_memory() -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.reserve_memory(0x87000000, 0x1000);
+
+    b.begin_node("").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+
+    b.begin_node("memory@80000000").unwrap();
+    b.property("device_type", "memory").unwrap();
+    b.property(
+        "reg",
+        [(0x80000000u64, 0x8000000u64), (0x100000000, 0x10000000)],
+    )
+    .unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("ram@c0000000").unwrap();
+    b.property("device_type", "memory").unwrap();
+    b.property("reg", [(0xc0000000u64, 0x1000000u64)]).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("reserved-memory").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+    b.property("ranges", ()).unwrap();
+
+    b.begin_node("mmode_resources@80000000").unwrap();
+    b.property("reg", [(0x80000000u64, 0x40000u64)]).unwrap();
+    b.property("no-map", ()).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("linux,cma").unwrap();
+    b.property("compatible", "shared-dma-pool").unwrap();
+    b.property("reusable", ()).unwrap();
+    b.property("size", 0x400000u64).unwrap();
+    b.property("alignment", 0x100000u64).unwrap();
+    b.property("alloc-ranges", [(0x80000000u64, 0x8000000u64)])
+        .unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("buffer").unwrap();
+    b.property("size", 0x200000u64).unwrap();
+    b.property("alignment", 0x200000u64).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("disabled@c0000000").unwrap();
+    b.property("reg", [(0xc0000000u64, 0x1000000u64)]).unwrap();
+    b.property("status", "disabled").unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+fn region(address: u64, size: u64) -> Region {
+    Region { address, size }
+}
+
+#[test]
+fn memory() {
+    let blob = build_memory();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    let memory = fdt.memory().unwrap().collect::<Vec<_>>();
+    assert_eq!(
+        memory,
+        [
+            region(0x80000000, 0x8000000),
+            region(0x100000000, 0x10000000),
+            region(0xc0000000, 0x1000000),
+        ]
+    );
+}
+
+#[test]
+fn reserved_memory() {
+    let blob = build_memory();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    let reserved = fdt.reserved_memory().unwrap().collect::<Vec<_>>();
+    assert_eq!(reserved.len(), 3);
+
+    let mmode = &reserved[0];
+    assert_eq!(
+        mmode.regions().unwrap().collect::<Vec<_>>(),
+        [region(0x80000000, 0x40000)]
+    );
+    assert!(mmode.no_map() && !mmode.reusable());
+    assert_eq!(mmode.size(), None);
+
+    let cma = &reserved[1];
+    assert!(cma.regions().is_none());
+    assert!(!cma.no_map() && cma.reusable());
+    assert_eq!(cma.size(), Some(0x400000));
+    assert_eq!(cma.alignment(), Some(0x100000));
+    assert_eq!(
+        cma.alloc_ranges().unwrap().collect::<Vec<_>>(),
+        [region(0x80000000, 0x8000000)]
+    );
+
+    assert_eq!(reserved[2].node.identifier(), "buffer");
+    assert!(reserved[2].alloc_ranges().is_none());
+}
+
+#[test]
+fn usable_memory() {
+    let blob = build_memory();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    let usable = fdt.usable_memory().unwrap().collect::<Vec<_>>();
+    assert_eq!(
+        usable,
+        [
+            // Without the firmware, the memory reservation block entry and the CMA pool
+            region(0x80040000, 0x6fc0000),
+            region(0x87001000, 0xbff000),
+            // Without the buffer, at the top of RAM
+            region(0x100000000, 0xfe00000),
+            region(0xc0000000, 0x1000000),
+        ]
+    );
+}
+
+#[test]
+fn qemu_memory() {
+    let fdt = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+
+    assert_eq!(
+        fdt.memory().unwrap().collect::<Vec<_>>(),
+        [region(0x80000000, 0x8000000)]
+    );
+    assert_eq!(fdt.reserved_memory().unwrap().count(), 0);
+    assert_eq!(
+        fdt.usable_memory().unwrap().collect::<Vec<_>>(),
+        [region(0x80000000, 0x8000000)]
+    );
+}
@@ -0,0 +1,268 @@
+use fdt::{Fdt, FdtBuilder, OverlayError, PropEncodedArray};
+
+/// Builds a base tree, as compiled by `dtc -@`.
+fn build_base() -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.set_boot_cpuid(2);
+    b.reserve_memory(0x80000000, 0x200000);
+
+    b.begin_node("").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+
+    b.begin_node("soc").unwrap();
+    b.property("#address-cells", 2u32).unwrap();
+    b.property("#size-cells", 2u32).unwrap();
+    b.property("ranges", ()).unwrap();
+
+    b.begin_node("plic@c000000").unwrap();
+    b.property("phandle", 1u32).unwrap();
+    b.property("interrupt-controller", ()).unwrap();
+    b.property("#interrupt-cells", 1u32).unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("serial@10000000").unwrap();
+    b.property("phandle", 2u32).unwrap();
+    b.property("compatible", "ns16550a").unwrap();
+    b.property("status", "disabled").unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("__symbols__").unwrap();
+    b.property("plic", "/soc/plic@c000000").unwrap();
+    b.property("uart0", "/soc/serial@10000000").unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+/// Builds an overlay, as compiled by `dtc -@` from:
+///
+/// ```dts
+/// /dts-v1/;
+/// /plugin/;
+///
+/// &uart0 {
+///     status = "okay";
+/// };
+///
+/// &{/soc} {
+///     rng: rng@10010000 {
+///         compatible = "virtio,mmio";
+///         interrupt-parent = <&plic>;
+///         interrupts = <12>;
+///     };
+///
+///     consumer {
+///         rng = <&rng>;
+///         uart = <&uart0>;
+///     };
+/// };
+/// ```
+fn build_overlay() -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+
+    b.begin_node("fragment@0").unwrap();
+    b.property("target", 0xffffffffu32).unwrap();
+    b.begin_node("__overlay__").unwrap();
+    b.property("status", "okay").unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("fragment@1").unwrap();
+    b.property("target-path", "/soc").unwrap();
+    b.begin_node("__overlay__").unwrap();
+    b.begin_node("rng@10010000").unwrap();
+    b.property("compatible", "virtio,mmio").unwrap();
+    b.property("interrupt-parent", 0xffffffffu32).unwrap();
+    b.property("interrupts", 12u32).unwrap();
+    b.property("phandle", 1u32).unwrap();
+    b.end_node().unwrap();
+    b.begin_node("consumer").unwrap();
+    b.property("rng", 1u32).unwrap();
+    b.property("uart", 0xffffffffu32).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("__symbols__").unwrap();
+    b.property("rng", "/fragment@1/__overlay__/rng@10010000")
+        .unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("__fixups__").unwrap();
+    b.property(
+        "uart0",
+        [
+            "/fragment@0:target:0",
+            "/fragment@1/__overlay__/consumer:uart:0",
+        ],
+    )
+    .unwrap();
+    b.property(
+        "plic",
+        "/fragment@1/__overlay__/rng@10010000:interrupt-parent:0",
+    )
+    .unwrap();
+    b.end_node().unwrap();
+
+    b.begin_node("__local_fixups__").unwrap();
+    b.begin_node("fragment@1").unwrap();
+    b.begin_node("__overlay__").unwrap();
+    b.begin_node("consumer").unwrap();
+    b.property("rng", 0u32).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+#[test]
+fn apply_overlay() {
+    let (base, overlay) = (build_base(), build_overlay());
+    let base = Fdt::from_bytes(&base).unwrap();
+    let blob = base
+        .apply_overlay(&Fdt::from_bytes(&overlay).unwrap())
+        .unwrap();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    // The header is preserved
+    assert_eq!(fdt.boot_cpuid(), 2);
+    let reserved = fdt.reserved_memory_map().next().unwrap().unwrap();
+    assert_eq!((reserved.address, reserved.size), (0x80000000, 0x200000));
+
+    // Properties are replaced
+    let uart = fdt.find_by_path("/soc/serial@10000000").unwrap().unwrap();
+    assert_eq!(uart.property::<&str>("status"), Some("okay"));
+    assert_eq!(uart.property::<&str>("compatible"), Some("ns16550a"));
+
+    // Nodes are added, with phandles renumbered after the ones of the base tree
+    let rng = fdt.find_by_path("/soc/rng@10010000").unwrap().unwrap();
+    assert_eq!(rng.phandle(), Some(3));
+    assert_eq!(fdt.node_by_phandle(3).unwrap(), Some(rng.clone()));
+
+    let irq = rng.interrupts().next().unwrap();
+    assert_eq!(irq.controller.identifier(), "plic@c000000");
+    assert_eq!(irq.specifier.cell(0), Some(12));
+
+    let consumer = fdt.find_by_path("/soc/consumer").unwrap().unwrap();
+    assert_eq!(consumer.property::<u32>("rng"), Some(3));
+    assert_eq!(consumer.property::<u32>("uart"), Some(2));
+
+    // The labels of the overlay can be used by the next ones
+    let symbols = fdt.find_by_path("/__symbols__").unwrap().unwrap();
+    assert_eq!(symbols.property::<&str>("rng"), Some("/soc/rng@10010000"));
+    assert_eq!(
+        symbols.property::<&str>("uart0"),
+        Some("/soc/serial@10000000")
+    );
+
+    // Fixup nodes are not copied
+    let root = fdt.root_node().unwrap();
+    let names = root
+        .children()
+        .map(|n| n.identifier().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["soc", "__symbols__"]);
+}
+
+#[test]
+fn apply_overlay_to_qemu_dtb() {
+    let base = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+    b.begin_node("fragment@0").unwrap();
+    b.property("target", 3u32).unwrap();
+    b.begin_node("__overlay__").unwrap();
+    b.property("riscv,ndev", 0x60u32).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.begin_node("fragment@1").unwrap();
+    b.property("target-path", "/").unwrap();
+    b.begin_node("__overlay__").unwrap();
+    b.begin_node("memory@c0000000").unwrap();
+    b.property("device_type", "memory").unwrap();
+    b.property("reg", [(0xc0000000u64, 0x10000000u64)]).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    let overlay = b.finish().unwrap();
+
+    let blob = base
+        .apply_overlay(&Fdt::from_bytes(&overlay).unwrap())
+        .unwrap();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+
+    let plic = fdt.find_compatible("riscv,plic0").unwrap().unwrap();
+    assert_eq!(plic.property::<u32>("riscv,ndev"), Some(0x60));
+
+    let memory = fdt.find_by_path("/memory@c0000000").unwrap().unwrap();
+    let mut reg: PropEncodedArray<(u64, u64)> = memory.property("reg").unwrap();
+    assert_eq!(reg.next(), Some((0xc0000000, 0x10000000)));
+
+    // The rest of the tree is unchanged
+    let root = fdt.root_node().unwrap();
+    assert_eq!(root.children().count(), 11);
+    assert!(fdt.find_compatible("sifive,test0").unwrap().is_some());
+}
+
+/// Builds an overlay with a single fragment targeting `target-path`, whose `__overlay__` node
+/// has a `ref` property fixed up by `fixup` to point to the node with `label`.
+fn build_overlay_with_fixup(target_path: &str, label: &str, fixup: &str) -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+    b.begin_node("fragment@0").unwrap();
+    b.property("target-path", target_path).unwrap();
+    b.begin_node("__overlay__").unwrap();
+    b.property("ref", 0xffffffffu32).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.begin_node("__fixups__").unwrap();
+    b.property(label, fixup).unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+#[test]
+fn overlay_errors() {
+    let base = build_base();
+    let base = Fdt::from_bytes(&base).unwrap();
+    let check = |target_path: &str, label: &str, fixup: &str, f: fn(&OverlayError) -> bool| {
+        let overlay = build_overlay_with_fixup(target_path, label, fixup);
+        let res = base.apply_overlay(&Fdt::from_bytes(&overlay).unwrap());
+        assert!(res.as_ref().is_err_and(f), "{fixup}: {res:?}");
+    };
+
+    let overlay = build_overlay_with_fixup("/soc", "plic", "/fragment@0/__overlay__:ref:0");
+    let blob = base
+        .apply_overlay(&Fdt::from_bytes(&overlay).unwrap())
+        .unwrap();
+    let fdt = Fdt::from_bytes(&blob).unwrap();
+    let soc = fdt.find_by_path("/soc").unwrap().unwrap();
+    assert_eq!(soc.property::<u32>("ref"), Some(1));
+
+    check("/soc", "gpio", "/fragment@0/__overlay__:ref:0", |e| {
+        matches!(e, OverlayError::MissingSymbol("gpio"))
+    });
+    check("/bus", "plic", "/fragment@0/__overlay__:ref:0", |e| {
+        matches!(e, OverlayError::TargetNotFound)
+    });
+    for fixup in [
+        "/fragment@0/__overlay__:ref:4",
+        "/fragment@0/__overlay__:other:0",
+        "/fragment@1/__overlay__:ref:0",
+        "/fragment@0/__overlay__:ref",
+    ] {
+        check("/soc", "plic", fixup, |e| {
+            matches!(e, OverlayError::InvalidFixup)
+        });
+    }
+}
@@ -0,0 +1,153 @@
+use fdt::{Fdt, FdtBuilder, FdtValidationError, ValidationErrorKind};
+
+/// Builds a tree whose structure block is laid out as:
+///
+/// ```text
+///  0: FDT_BEGIN_NODE ""
+///  8: FDT_PROP len=2 "compatible" "a"
+/// 24: FDT_BEGIN_NODE "cpus"
+/// 36: FDT_END_NODE
+/// 40: FDT_END_NODE
+/// 44: FDT_END
+/// ```
+fn build_tree() -> Vec<u8> {
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+    b.property("compatible", "a").unwrap();
+    b.begin_node("cpus").unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    b.finish().unwrap()
+}
+
+fn read_u32(blob: &[u8], off: usize) -> u32 {
+    u32::from_be_bytes(blob[off..off + 4].try_into().unwrap())
+}
+
+fn write_u32(blob: &mut [u8], off: usize, value: u32) {
+    blob[off..off + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Returns the result of validating `build_tree` after `corrupt` has been applied to it, and
+/// the offset of its structure block.
+fn validate_corrupted(
+    corrupt: impl FnOnce(&mut Vec<u8>, usize),
+) -> (usize, Result<(), FdtValidationError>) {
+    let mut blob = build_tree();
+    let structs = read_u32(&blob, 8) as usize;
+    corrupt(&mut blob, structs);
+    (structs, Fdt::from_bytes(&blob).unwrap().validate())
+}
+
+fn error(offset: usize, kind: ValidationErrorKind) -> Result<(), FdtValidationError> {
+    Err(FdtValidationError { offset, kind })
+}
+
+#[test]
+fn validate() {
+    let fdt = Fdt::from_bytes(include_bytes!("data/qemu-riscv.dtb")).unwrap();
+    assert_eq!(fdt.validate(), Ok(()));
+
+    let blob = build_tree();
+    assert_eq!(Fdt::from_bytes(&blob).unwrap().validate(), Ok(()));
+}
+
+#[test]
+fn validate_header() {
+    use ValidationErrorKind::*;
+
+    let (_, res) = validate_corrupted(|blob, _| {
+        let total = blob.len() as u32;
+        write_u32(blob, 8, total);
+    });
+    assert_eq!(res, error(8, BlockOutOfBounds));
+
+    let (_, res) = validate_corrupted(|blob, _| write_u32(blob, 36, 46));
+    assert_eq!(res, error(36, Misaligned));
+
+    let (_, res) = validate_corrupted(|blob, structs| write_u32(blob, 12, structs as u32));
+    assert_eq!(res, error(12, BlockOutOfBounds));
+
+    let (_, res) = validate_corrupted(|blob, _| write_u32(blob, 16, 44));
+    assert_eq!(res, error(16, Misaligned));
+
+    let (_, res) = validate_corrupted(|blob, _| {
+        let rsvmap = (blob.len() as u32 - 8) & !7;
+        write_u32(blob, 16, rsvmap);
+    });
+    assert!(matches!(
+        res,
+        Err(FdtValidationError {
+            kind: UnterminatedReservations,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn validate_structs() {
+    use ValidationErrorKind::*;
+
+    let (structs, res) = validate_corrupted(|blob, structs| write_u32(blob, structs + 36, 7));
+    assert_eq!(res, error(structs + 36, InvalidToken(7)));
+
+    // FDT_END_NODE of cpus replaced by FDT_NOP
+    let (structs, res) = validate_corrupted(|blob, structs| write_u32(blob, structs + 36, 4));
+    assert_eq!(res, error(structs + 44, UnbalancedNodes));
+
+    // FDT_END replaced by FDT_PROP, after the root node
+    let (structs, res) = validate_corrupted(|blob, structs| write_u32(blob, structs + 44, 3));
+    assert_eq!(res, error(structs + 44, MisplacedProperty));
+
+    let (structs, res) = validate_corrupted(|blob, structs| write_u32(blob, structs + 12, 0x1000));
+    assert_eq!(res, error(structs + 8, PropertyOutOfBounds));
+
+    let (structs, res) = validate_corrupted(|blob, structs| write_u32(blob, structs + 16, 0x1000));
+    assert_eq!(res, error(structs + 8, InvalidPropertyName));
+
+    let (structs, res) = validate_corrupted(|blob, structs| blob[structs + 28] = b'/');
+    assert_eq!(res, error(structs + 24, InvalidNodeName));
+
+    let (structs, res) = validate_corrupted(|blob, structs| blob[structs + 4] = b'a');
+    assert_eq!(res, error(structs, InvalidNodeName));
+
+    // The first bytes of the strings block moved to the structure block
+    let (structs, res) = validate_corrupted(|blob, _| {
+        for (field, delta) in [(36, 4), (12, 4), (32, -4)] {
+            let value = read_u32(blob, field).strict_add_signed(delta);
+            write_u32(blob, field, value);
+        }
+    });
+    assert_eq!(res, error(structs + 48, TrailingData));
+}
+
+#[test]
+fn validate_names() {
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+    b.begin_node("a").unwrap();
+    b.end_node().unwrap();
+    b.begin_node("a").unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    let blob = b.finish().unwrap();
+    let structs = read_u32(&blob, 8) as usize;
+    assert_eq!(
+        Fdt::from_bytes(&blob).unwrap().validate(),
+        error(structs + 20, ValidationErrorKind::DuplicateNode)
+    );
+
+    let mut b = FdtBuilder::new();
+    b.begin_node("").unwrap();
+    b.begin_node("a").unwrap();
+    b.property("status", "okay").unwrap();
+    b.property("status", "disabled").unwrap();
+    b.end_node().unwrap();
+    b.end_node().unwrap();
+    let blob = b.finish().unwrap();
+    let structs = read_u32(&blob, 8) as usize;
+    assert_eq!(
+        Fdt::from_bytes(&blob).unwrap().validate(),
+        error(structs + 8, ValidationErrorKind::DuplicateProperty)
+    );
+}
@@ -25,6 +25,11 @@ pub unsafe extern "C" fn arch_init(fdt_data: *const u8, kernel_rpt_va: usize) {
 
     // Initialize core subsystems
     earlycon::register();
+
+    // Fail early on a malformed FDT, rather than on the first inconsistency found while using it
+    if let Err(e) = fdt.validate() {
+        panic!("invalid FDT: {e:?}");
+    }
     sbi::show_info();
     trap::init();
     mm::setup_late(&fdt, VirtAddr::new(kernel_rpt_va));
@@ -21,7 +21,7 @@ use crate::{
         allocator::{BumpAllocator, BumpFrameAllocator, FrameAllocator},
     },
 };
-use fdt::{Fdt, PropEncodedArray};
+use fdt::Fdt;
 use mmu::PageTableWalker;
 use spin::Mutex;
 
@@ -128,21 +128,13 @@ pub fn setup_late(fdt: &Fdt, early_rpt: VirtAddr) {
         unsafe { PageTableWalker::new(&mut *(early_rpt.as_mut_ptr::<PageTable>())) };
 
     // Extract memory map from the FDT
-    let mem_region = fdt.find(|n| n.name() == "memory").unwrap().unwrap();
-    let (mem_base, mem_size) = mem_region
-        .property::<PropEncodedArray<(u64, u64)>>("reg")
-        .unwrap()
-        .next()
-        .unwrap();
-
-    let mem_size = mem_size as usize;
+    let mem_base = fdt.memory().unwrap().map(|r| r.address).min().unwrap();
 
     // Save the base address of the physical memory for quicker translations
     PHYS_MEM_OFFSET.store(mem_base, Ordering::Relaxed);
-    let mem_base = PhysAddr::new(mem_base as usize);
 
     // Set up a frame allocator for the unused physical memory
-    setup_frame_allocator(&early_kernel_mapper, mem_base, mem_size);
+    setup_frame_allocator(&early_kernel_mapper, fdt);
 
     // Now that we have a proper frame allocator, we can replace the early mappings with page
     // mappings that use properly tracked frames
@@ -177,15 +169,19 @@ pub fn setup_late(fdt: &Fdt, early_rpt: VirtAddr) {
             .unwrap();
 
         // Remap the whole physical memory
-        mapper
-            .map_range(
-                PHYS_TO_VIRT_OFFSET,
-                mem_base..(mem_base + mem_size),
-                PageSize::Mb,
-                EntryFlags::KERNEL,
-                gfa,
-            )
-            .unwrap();
+        for region in fdt.memory().unwrap() {
+            let start = PhysAddr::new(region.address as usize);
+            let end = PhysAddr::new(region.end() as usize);
+            mapper
+                .map_range(
+                    PHYS_TO_VIRT_OFFSET + (region.address - mem_base) as usize,
+                    start..end,
+                    PageSize::Mb,
+                    EntryFlags::KERNEL,
+                    gfa,
+                )
+                .unwrap();
+        }
     }
 
     // Preallocate and map some memory for the heap
@@ -227,16 +223,25 @@ pub fn setup_late(fdt: &Fdt, early_rpt: VirtAddr) {
     *MAPPER.lock() = Some(mapper);
 }
 
-fn setup_frame_allocator(ptw: &PageTableWalker, base: PhysAddr, len: usize) {
+fn setup_frame_allocator(ptw: &PageTableWalker, fdt: &Fdt) {
     // SAFETY: populated by the linker script
     let kernel_end = unsafe { VirtAddr::new(&_end as *const _ as usize) };
 
     let virt_base = kernel_end.align_up(PAGE_SIZE);
     let phys_base = ptw.virt_to_phys(virt_base).unwrap();
-    let phys_end = base + len;
 
     kprintln!("Available physical memory:");
-    kprintln!("  [{phys_base:016x} - {phys_end:016x}]");
+    for region in fdt.usable_memory().unwrap() {
+        kprintln!("  [{:016x} - {:016x}]", region.address, region.end());
+    }
+
+    // Allocate frames from the free memory following the kernel
+    let region = fdt
+        .usable_memory()
+        .unwrap()
+        .find(|r| (r.address..r.end()).contains(&(phys_base.as_usize() as u64)))
+        .expect("kernel not loaded in usable memory");
+    let phys_end = PhysAddr::new(region.end() as usize);
 
     // SAFETY: `phys_base` and `phys_end` are valid physical addresses
     *GFA.lock() = Some(unsafe { BumpFrameAllocator::new(phys_base, phys_end) });
@@ -29,17 +29,18 @@ impl Driver for SifivePlic {
     where
         Self: Sized,
     {
-        let (base, len) = node
-            .property::<(u64, u64)>("reg")
+        let reg = node
+            .reg()
+            .and_then(|mut reg| reg.next())
             .ok_or(DriverError::MissingRequiredProperty("reg"))?;
 
-        let pa_base = PhysAddr::new(base as usize);
+        let pa_base = PhysAddr::new(reg.address as usize);
         let size =
-            NonZeroUsize::new(len as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
+            NonZeroUsize::new(reg.size as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
 
         let regmap = mmio::mapper().iomap(pa_base, size).unwrap();
 
-        kprintln!("PLIC: {:#x} - {:#x}", base, base + len);
+        kprintln!("PLIC: {:#x} - {:#x}", reg.address, reg.end());
 
         irqchip::register_platform_irqchip(SifivePlic { _regmap: regmap });
 
@@ -3,7 +3,7 @@
 use core::iter::{self, FromIterator};
 
 use alloc::collections::VecDeque;
-use fdt::{Fdt, FdtParseError, Node, StringList};
+use fdt::{Fdt, FdtParseError, Node, PropertyError, StringList};
 
 pub mod earlycon;
 pub mod irqchip;
@@ -141,3 +141,12 @@ impl<'d> From<FdtParseError<'d>> for DriverError<'d> {
         Self::Fdt(value)
     }
 }
+
+impl From<PropertyError> for DriverError<'_> {
+    fn from(value: PropertyError) -> Self {
+        match value {
+            PropertyError::Missing(name) => Self::MissingRequiredProperty(name),
+            PropertyError::Invalid(name) => Self::InvalidPropertyValue(name),
+        }
+    }
+}
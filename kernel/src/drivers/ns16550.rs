@@ -2,7 +2,7 @@
 
 use core::{fmt::Write, hint, num::NonZeroUsize};
 
-use fdt::Node;
+use fdt::{FromNode, Node};
 
 use crate::{
     driver_info,
@@ -23,23 +23,55 @@ driver_info! {
 /// Device driver of the 16550 UART IC.
 pub struct Ns16550 {
     regmap: IoMapping,
+    reg_shift: u32,
+}
+
+/// Properties of a 16550 UART node.
+#[derive(FromNode)]
+struct Ns16550Config {
+    /// Frequency of the input clock, which may instead be described by a `clocks` property.
+    #[fdt("clock-frequency")]
+    clock_frequency: Option<u32>,
+    #[fdt("reg-shift", default = 0)]
+    reg_shift: u32,
 }
 
 impl Driver for Ns16550 {
     fn init<'d, 'fdt: 'd>(_: &DriverCtx, node: Node<'d, 'fdt>) -> Result<(), DriverError<'d>> {
-        let (base, size) = node
-            .property::<(u64, u64)>("reg")
+        // Only the UART selected as console by the firmware is used
+        let stdout = node.fdt().stdout()?;
+        if stdout.as_ref().is_some_and(|stdout| stdout.node != node) {
+            return Err(DriverError::DeviceNotFound);
+        }
+
+        let reg = node
+            .reg()
+            .and_then(|mut reg| reg.next())
             .ok_or(DriverError::MissingRequiredProperty("reg"))?;
 
-        let pa_base = PhysAddr::new(base as usize);
+        let pa_base = PhysAddr::new(reg.address as usize);
         let size =
-            NonZeroUsize::new(size as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
+            NonZeroUsize::new(reg.size as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
+
+        let config = Ns16550Config::from_node(&node)?;
 
         let regmap = mmio::mapper().iomap(pa_base, size).unwrap();
 
-        let mut slf = Self { regmap };
+        let mut slf = Self {
+            regmap,
+            reg_shift: config.reg_shift,
+        };
 
-        kprintln!("ns16550: UART at 0x{:x}", base);
+        match config.clock_frequency {
+            Some(freq) => kprintln!("ns16550: UART at 0x{:x}, clocked at {freq} Hz", reg.address),
+            None => kprintln!("ns16550: UART at 0x{:x}", reg.address),
+        }
+        if let Some(stdout) = stdout {
+            match stdout.baud_rate() {
+                Some(baud) => kprintln!("ns16550: selected as console ({baud} baud)"),
+                None => kprintln!("ns16550: selected as console"),
+            }
+        }
         writeln!(slf, "*** Hello, world! ***").ok();
 
         // TODO: register this as console device
@@ -52,22 +84,28 @@ impl Ns16550 {
     const RTHR: usize = 0;
     const LSR: usize = 5;
 
+    /// Returns the offset of a register, whose index is scaled by the `reg-shift` of the node.
+    fn offset(&self, reg: usize) -> usize {
+        reg << self.reg_shift
+    }
+
     /// Writes a single byte to the serial interface.
     pub fn put(&self, val: u8) {
-        while self.regmap.read::<u8>(Self::LSR) & 0b0010_0000 == 0 {
+        while self.regmap.read::<u8>(self.offset(Self::LSR)) & 0b0010_0000 == 0 {
             hint::spin_loop();
         }
-        self.regmap.write(Self::RTHR, val);
+        self.regmap.write(self.offset(Self::RTHR), val);
     }
 
     /// Returns the next received byte, or `None` if the Rx queue is empty.
     pub fn get(&self) -> Option<u8> {
-        self.data_ready().then(|| self.regmap.read(Self::RTHR))
+        self.data_ready()
+            .then(|| self.regmap.read(self.offset(Self::RTHR)))
     }
 
     /// Returns true if there is data available in the Rx FIFO.
     pub fn data_ready(&self) -> bool {
-        self.regmap.read::<u8>(Self::LSR) & 0x1 != 0
+        self.regmap.read::<u8>(self.offset(Self::LSR)) & 0x1 != 0
     }
 }
 
@@ -1,8 +1,9 @@
 //! Generic system controller.
 
-use core::num::NonZeroUsize;
+use alloc::collections::VecDeque;
+use core::{iter, num::NonZeroUsize};
 
-use fdt::{Fdt, Node, PropEncodedArray};
+use fdt::{FromNode, Node, StringList};
 
 use crate::{
     driver_info,
@@ -25,23 +26,27 @@ pub struct GenericSyscon {
     reboot: Option<SysconRegister>,
 }
 
+/// Register written by a `syscon-poweroff` or `syscon-reboot` node.
+#[derive(FromNode)]
 struct SysconRegister {
-    offset: usize,
+    #[fdt("offset")]
+    offset: u32,
+    #[fdt("value")]
     value: u32,
+    #[fdt("mask", default = u32::MAX)]
     _mask: u32,
 }
 
 impl Driver for GenericSyscon {
     fn init<'d, 'fdt: 'd>(_: &DriverCtx, node: Node<'d, 'fdt>) -> Result<(), DriverError<'d>> {
-        let mut regs = node
-            .property::<PropEncodedArray<(u64, u64)>>("reg")
+        let reg = node
+            .reg()
+            .and_then(|mut reg| reg.next())
             .ok_or(DriverError::MissingRequiredProperty("reg"))?;
 
-        let (base, len) = regs.next().expect("empty reg property");
-
-        let pa_base = PhysAddr::new(base as usize);
+        let pa_base = PhysAddr::new(reg.address as usize);
         let size =
-            NonZeroUsize::new(len as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
+            NonZeroUsize::new(reg.size as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
 
         let regmap = mmio::mapper().iomap(pa_base, size).unwrap();
 
@@ -52,10 +57,8 @@ impl Driver for GenericSyscon {
         };
 
         // Find poweroff and reboot nodes
-        if let Some(phandle) = node.property::<u32>("phandle") {
-            slf.poweroff = find_syscon_driver(node.fdt(), phandle, "syscon-poweroff")?;
-            slf.reboot = find_syscon_driver(node.fdt(), phandle, "syscon-reboot")?;
-        }
+        slf.poweroff = find_syscon_driver(&node, "syscon-poweroff")?;
+        slf.reboot = find_syscon_driver(&node, "syscon-reboot")?;
 
         syscon::register_provider(slf);
 
@@ -66,36 +69,49 @@ impl Driver for GenericSyscon {
 impl super::Syscon for GenericSyscon {
     fn poweroff(&self) {
         if let Some(ref reg) = self.poweroff {
-            self.regmap.write::<u32>(reg.offset, reg.value);
+            self.regmap.write::<u32>(reg.offset as usize, reg.value);
         }
     }
 
     fn reboot(&self) {
         if let Some(ref reg) = self.reboot {
-            self.regmap.write::<u32>(reg.offset, reg.value);
+            self.regmap.write::<u32>(reg.offset as usize, reg.value);
         }
     }
 }
 
+/// Finds the enabled `compatible` node using the registers of `syscon`, which references it with
+/// its `regmap` property, or is one of its children.
+///
+/// Every `compatible` node is considered, as there may be several of them, eg. one for each
+/// system controller or disabled ones.
 fn find_syscon_driver<'d>(
-    fdt: &'d Fdt,
-    phandle: u32,
+    syscon: &Node<'d, '_>,
     compatible: &str,
 ) -> Result<Option<SysconRegister>, DriverError<'d>> {
-    Ok(fdt
-        .find_compatible(compatible)?
-        .filter(|n| n.property::<u32>("regmap") == Some(phandle))
-        .and_then(parse_syscon_driver_node))
-}
+    let fdt = syscon.fdt();
+    let mut nodes = VecDeque::from_iter(iter::once(fdt.root_node()?));
+
+    while let Some(node) = nodes.pop_front() {
+        nodes.extend(node.children());
+
+        let is_compatible = node
+            .property::<StringList>("compatible")
+            .is_some_and(|mut c| c.any(|c| c == compatible));
+        let is_enabled = matches!(node.property::<&str>("status"), None | Some("okay" | "ok"));
+        if !is_compatible || !is_enabled {
+            continue;
+        }
 
-fn parse_syscon_driver_node(node: Node) -> Option<SysconRegister> {
-    let offset = node.property::<u32>("offset")? as usize;
-    let value = node.property::<u32>("value")?;
-    let mask = node.property::<u32>("mask").unwrap_or(u32::MAX);
+        let regmap = match node.property::<u32>("regmap") {
+            Some(phandle) => fdt.node_by_phandle(phandle)?,
+            None => node.parent(),
+        };
+
+        if regmap.as_ref() == Some(syscon) {
+            return Ok(Some(SysconRegister::from_node(&node)?));
+        }
+    }
 
-    Some(SysconRegister {
-        offset,
-        value,
-        _mask: mask,
-    })
+    Ok(None)
 }
@@ -1,4 +1,4 @@
-use core::{ffi::CStr, hint};
+use core::{ffi::CStr, fmt, hint, ptr};
 
 use alloc::string::String;
 use bitflags::bitflags;
@@ -8,15 +8,22 @@ use crate::{
         InterruptStatus, Status, VirtioDev, VirtioDriver,
         virtq::{Virtq, VirtqBuffer},
     },
-    mm::addr::DmaAddr,
+    mm::{addr::DmaAddr, dma::DmaObject},
 };
 
+/// Size of a sector, which is the unit of addressing regardless of the device's block size.
+const SECTOR_SIZE: usize = 512;
+
+/// Size of the buffer through which sectors are transferred.
+const BOUNCE_BUF_SIZE: usize = 4096;
+
 /// A virtio block device.
 pub struct VirtioBlkDev<D> {
     dev: D,
     virtq: Virtq,
     config: VirtioBlkConfig,
     features: DeviceFeatures,
+    bounce_buf: DmaObject<[u8; BOUNCE_BUF_SIZE]>,
 }
 
 impl<D: VirtioDev> VirtioBlkDev<D> {
@@ -32,17 +39,23 @@ impl<D: VirtioDev> VirtioBlkDev<D> {
                 | DeviceFeatures::SEG_MAX
                 | DeviceFeatures::GEOMETRY
                 | DeviceFeatures::BLK_SIZE
+                | DeviceFeatures::FLUSH
                 | DeviceFeatures::TOPOLOGY);
         dev.enable_device_features(0, features.bits());
 
         // Configure virtqueues
         let virtq = dev.allocate_virtq(0);
 
+        let bounce_buf = dev
+            .allocate_guest_mem([0_u8; BOUNCE_BUF_SIZE])
+            .expect("oom");
+
         let mut slf = Self {
             dev,
             virtq,
             features,
             config: VirtioBlkConfig::default(),
+            bounce_buf,
         };
 
         // Read configuration
@@ -108,7 +121,10 @@ impl<D: VirtioDev> VirtioBlkDev<D> {
         // SAFETY: buf has just been allocated and this is the only reference to it
         let buf_ref = unsafe { buf.as_ptr().as_ref().unwrap() };
 
-        self.transfer(VirtioBlkReqType::GetId, 0, buf.dma_addr(), 20);
+        let status = self.transfer(VirtioBlkReqType::GetId, 0, buf.dma_addr(), 20);
+        if status != VirtioBlkStatus::OK {
+            return None;
+        }
 
         let id = CStr::from_bytes_until_nul(buf_ref).ok()?;
         if id.is_empty() {
@@ -118,7 +134,29 @@ impl<D: VirtioDev> VirtioBlkDev<D> {
         id.to_str().ok().map(String::from)
     }
 
-    fn transfer(&mut self, kind: VirtioBlkReqType, sector: u64, data: DmaAddr, len: usize) {
+    /// Returns an error if `len` isn't a non-zero number of whole sectors, or if `len` bytes
+    /// starting from sector `lba` don't fall inside the device.
+    fn check_range(&self, lba: u64, len: usize) -> Result<(), VirtioBlkError> {
+        if len == 0 || !len.is_multiple_of(SECTOR_SIZE) {
+            return Err(VirtioBlkError::InvalidInput);
+        }
+
+        let sectors = (len / SECTOR_SIZE) as u64;
+        match lba.checked_add(sectors) {
+            Some(end) if end <= self.config.capacity => Ok(()),
+            _ => Err(VirtioBlkError::OutOfRange),
+        }
+    }
+
+    /// Submits a request and waits for its completion, returning the status reported by the
+    /// device.
+    fn transfer(
+        &mut self,
+        kind: VirtioBlkReqType,
+        sector: u64,
+        data: DmaAddr,
+        len: usize,
+    ) -> VirtioBlkStatus {
         use VirtioBlkReqType::*;
         use VirtqBuffer::*;
 
@@ -164,6 +202,67 @@ impl<D: VirtioDev> VirtioBlkDev<D> {
         }
 
         self.dev.clear_interrupts(InterruptStatus::USED_BUFFER);
+
+        // SAFETY: the request has been completed, so the device is done writing the status
+        let status = unsafe { ptr::read_volatile(&raw const (*blk_req.as_ptr()).status) };
+        VirtioBlkStatus(status)
+    }
+}
+
+impl<D: VirtioDev> ext2::BlockDevice for VirtioBlkDev<D> {
+    type Error = VirtioBlkError;
+
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.config.capacity
+    }
+
+    fn read_sectors(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_range(lba, buf.len())?;
+
+        for (i, chunk) in buf.chunks_mut(BOUNCE_BUF_SIZE).enumerate() {
+            let sector = lba + (i * BOUNCE_BUF_SIZE / SECTOR_SIZE) as u64;
+            let addr = self.bounce_buf.dma_addr();
+            self.transfer(VirtioBlkReqType::In, sector, addr, chunk.len())
+                .into_result()?;
+
+            // SAFETY: the device is done with the buffer and this is the only reference to it
+            let data = unsafe { self.bounce_buf.as_ptr().as_ref().unwrap() };
+            chunk.copy_from_slice(&data[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, lba: u64, buf: &[u8]) -> Result<(), Self::Error> {
+        self.check_range(lba, buf.len())?;
+
+        for (i, chunk) in buf.chunks(BOUNCE_BUF_SIZE).enumerate() {
+            // SAFETY: no request is in flight, so this is the only reference to the buffer
+            let data = unsafe { self.bounce_buf.as_mut_ptr().as_mut().unwrap() };
+            data[..chunk.len()].copy_from_slice(chunk);
+
+            let sector = lba + (i * BOUNCE_BUF_SIZE / SECTOR_SIZE) as u64;
+            let addr = self.bounce_buf.dma_addr();
+            self.transfer(VirtioBlkReqType::Out, sector, addr, chunk.len())
+                .into_result()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // Without a volatile write cache, writes are durable as soon as they complete
+        if !self.features.contains(DeviceFeatures::FLUSH) {
+            return Ok(());
+        }
+
+        let addr = self.bounce_buf.dma_addr();
+        self.transfer(VirtioBlkReqType::Flush, 0, addr, 0)
+            .into_result()
     }
 }
 
@@ -184,6 +283,71 @@ impl VirtioBlkReq {
     const TRAILER_SIZE: usize = 1;
 }
 
+/// Status of a completed request, as written by the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VirtioBlkStatus(u8);
+
+impl VirtioBlkStatus {
+    const OK: Self = Self(0);
+    const IOERR: Self = Self(1);
+
+    fn into_result(self) -> Result<(), VirtioBlkError> {
+        match self {
+            Self::OK => Ok(()),
+            Self::IOERR => Err(VirtioBlkError::Io),
+            _ => Err(VirtioBlkError::Unsupported),
+        }
+    }
+}
+
+/// Errors reported by a virtio block device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtioBlkError {
+    /// The device failed to carry out the request.
+    Io,
+    /// The request is not supported by the device.
+    Unsupported,
+    /// The request falls outside of the device.
+    OutOfRange,
+    /// The device ended before all the requested data could be read.
+    UnexpectedEof,
+    /// The device accepted no data while writing.
+    WriteZero,
+    /// An invalid argument was provided, eg. a negative seek.
+    InvalidInput,
+}
+
+impl fmt::Display for VirtioBlkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io => write!(f, "I/O error"),
+            Self::Unsupported => write!(f, "unsupported request"),
+            Self::OutOfRange => write!(f, "sector out of range"),
+            Self::UnexpectedEof => write!(f, "unexpected end of device"),
+            Self::WriteZero => write!(f, "write zero"),
+            Self::InvalidInput => write!(f, "invalid input"),
+        }
+    }
+}
+
+impl ext2::IoError for VirtioBlkError {
+    fn is_interrupted(&self) -> bool {
+        false
+    }
+
+    fn new_unexpected_eof_error() -> Self {
+        Self::UnexpectedEof
+    }
+
+    fn new_write_zero_error() -> Self {
+        Self::WriteZero
+    }
+
+    fn new_invalid_input_error() -> Self {
+        Self::InvalidInput
+    }
+}
+
 /// Supported request types for a VirtIO block device.
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
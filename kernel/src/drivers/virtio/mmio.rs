@@ -33,13 +33,14 @@ pub struct VirtioMmio {
 
 impl Driver for VirtioMmio {
     fn init<'d, 'fdt: 'd>(_: &DriverCtx, node: Node) -> Result<(), DriverError<'d>> {
-        let (base, size) = node
-            .property::<(u64, u64)>("reg")
+        let reg = node
+            .reg()
+            .and_then(|mut reg| reg.next())
             .ok_or(DriverError::MissingRequiredProperty("reg"))?;
 
-        let pa_base = PhysAddr::new(base as usize);
+        let pa_base = PhysAddr::new(reg.address as usize);
         let size =
-            NonZeroUsize::new(size as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
+            NonZeroUsize::new(reg.size as usize).ok_or(DriverError::InvalidPropertyValue("reg"))?;
 
         let regmap = mmio::mapper().iomap(pa_base, size).unwrap();
 
@@ -68,7 +69,10 @@ impl Driver for VirtioMmio {
             _ => todo!("unsupported virtio device"),
         };
 
-        kprintln!("virtio-mmio: new device {vendor_id:x}:{dev_id:x} at 0x{base:x}");
+        kprintln!(
+            "virtio-mmio: new device {vendor_id:x}:{dev_id:x} at 0x{:x}",
+            reg.address
+        );
 
         // TODO: register this as block device
 
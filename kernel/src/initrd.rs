@@ -2,6 +2,7 @@
 
 use core::fmt;
 
+use cpio::{CpioError, CpioTree};
 use fdt::Fdt;
 
 use crate::{
@@ -11,41 +12,33 @@ use crate::{
 
 /// Represents an initial ramdisk.
 pub struct Initrd {
-    data: &'static [u8],
+    tree: CpioTree<'static>,
 }
 
 impl Initrd {
-    /// Creates a new `Initrd` from the given data.
-    pub fn new(data: &'static [u8]) -> Self {
-        Initrd { data }
+    /// Creates a new `Initrd` from the given data, indexing the files of the archive.
+    pub fn new(data: &'static [u8]) -> Result<Self, InitrdError> {
+        let tree = CpioTree::new(data).map_err(InitrdError::Invalid)?;
+        Ok(Initrd { tree })
     }
 
-    /// Returns the contents of a file in the initrd by its path.
+    /// Returns the directory tree of the initrd.
+    pub fn tree(&self) -> &CpioTree<'static> {
+        &self.tree
+    }
+
+    /// Returns the contents of a regular file in the initrd by its path, following symlinks.
     pub fn find_file(&self, path: &str) -> Option<&'static [u8]> {
-        cpio::find_file(self.data, path).ok().flatten()
+        let file = self.tree.lookup(path).ok()?;
+        cpio::is_reg(file.stat().mode).then(|| file.data())
     }
 }
 
 /// Loads initrd data from the location specified in the FDT.
 pub fn load_from_fdt(fdt: &Fdt) -> Result<Initrd, InitrdError> {
-    let chosen = fdt
-        .find_by_path("/chosen")
-        .map_err(|_| InitrdError::NotFound)?;
-
-    let start = chosen
-        .as_ref()
-        .and_then(|node| {
-            node.property::<(u32, u32)>("linux,initrd-start")
-                .map(|(hi, lo)| PhysAddr::new(((hi as usize) << 32) | (lo as usize)))
-        })
-        .ok_or(InitrdError::NotFound)?;
-
-    let end = chosen
-        .and_then(|node| {
-            node.property::<(u32, u32)>("linux,initrd-end")
-                .map(|(hi, lo)| PhysAddr::new(((hi as usize) << 32) | (lo as usize)))
-        })
-        .ok_or(InitrdError::NotFound)?;
+    let range = fdt.initrd().ok().flatten().ok_or(InitrdError::NotFound)?;
+    let start = PhysAddr::new(range.start as usize);
+    let end = PhysAddr::new(range.end as usize);
 
     let len = (end - start).as_usize();
 
@@ -62,7 +55,7 @@ pub fn load_from_fdt(fdt: &Fdt) -> Result<Initrd, InitrdError> {
         core::slice::from_raw_parts(ptr, len)
     };
 
-    Ok(Initrd::new(initrd_data))
+    Initrd::new(initrd_data)
 }
 
 /// Errors related to initrd.
@@ -70,12 +63,15 @@ pub fn load_from_fdt(fdt: &Fdt) -> Result<Initrd, InitrdError> {
 pub enum InitrdError {
     /// The initrd was not found in the FDT.
     NotFound,
+    /// The initrd is not a valid cpio archive.
+    Invalid(CpioError),
 }
 
 impl fmt::Display for InitrdError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             InitrdError::NotFound => write!(f, "initrd not reference in FDT"),
+            InitrdError::Invalid(e) => write!(f, "invalid initrd: {e}"),
         }
     }
 }
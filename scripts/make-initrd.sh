@@ -22,5 +22,4 @@ mkdir -p $OUTDIR/initrd/bin
 cp "$INIT_OUTDIR/init" $OUTDIR/initrd/bin/init
 
 # Create cpio initrd image
-cd $OUTDIR/initrd
-find . -print0 | perl -0pe 's|^\./||' | cpio -0 -o --format=newc > ../initrd.cpio
+cargo run -q -p mkcpio -- $OUTDIR/initrd $OUTDIR/initrd.cpio
@@ -0,0 +1,327 @@
+//! Inspects and edits ext2 images from the host, without loop mounts or root privileges.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    os::unix::fs::PermissionsExt,
+    process::ExitCode,
+};
+
+use ext2::{Error, FileType, Inode, Read as _, StdIoWrapper, Write as _};
+
+type FileSystem = ext2::FileSystem<StdIoWrapper<fs::File>>;
+
+const USAGE: &str = "usage: ext2tool <image> <command> [args...]
+
+commands:
+    info                        show the superblock
+    ls [path]                   list a directory
+    cat <path>                  print the contents of a file
+    put <source> <path> [mode]  copy a host file into the image, replacing any existing file
+    mkdir [-p] <path> [mode]    create a directory, and its parents with -p
+    rm <path>                   remove a file or an empty directory
+    stat <path>                 show the inode of a file, without following a final symlink
+    fsck [-y]                   check the file system, and repair what can be with -y";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [image, command, args @ ..] = &args[..] else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match run(image, command, &args) {
+        Ok(code) => code,
+        // Like other tools, stop quietly when the output is closed, eg. by `head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ext2tool: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(image: &str, command: &str, args: &[&str]) -> io::Result<ExitCode> {
+    let writable = matches!(command, "put" | "mkdir" | "rm") || args == ["-y"];
+    let file = OpenOptions::new().read(true).write(writable).open(image)?;
+    let fs = if writable {
+        FileSystem::new(StdIoWrapper::new(file))?
+    } else {
+        // Replaying the journal would write to the image
+        FileSystem::new_read_only(StdIoWrapper::new(file))?
+    };
+    if fs.needs_recovery() {
+        eprintln!("ext2tool: the journal needs recovery, its last changes are not shown");
+    }
+
+    match (command, args) {
+        ("info", []) => info(&fs)?,
+        ("ls", []) => ls(&fs, "/")?,
+        ("ls", [path]) => ls(&fs, path)?,
+        ("cat", [path]) => cat(&fs, path)?,
+        ("put", [source, path]) => put(&fs, source, path, None)?,
+        ("put", [source, path, mode]) => put(&fs, source, path, Some(parse_mode(mode)?))?,
+        ("mkdir", [path]) => mkdir(&fs, path, 0o755, false)?,
+        ("mkdir", ["-p", path]) => mkdir(&fs, path, 0o755, true)?,
+        ("mkdir", [path, mode]) => mkdir(&fs, path, parse_mode(mode)?, false)?,
+        ("mkdir", ["-p", path, mode]) => mkdir(&fs, path, parse_mode(mode)?, true)?,
+        ("rm", [path]) => rm(&fs, path)?,
+        ("stat", [path]) => stat(&fs, path)?,
+        ("fsck", []) => return fsck(&fs, false),
+        ("fsck", ["-y"]) => return fsck(&fs, true),
+        _ => {
+            eprintln!("{USAGE}");
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+
+    fs.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn parse_mode(mode: &str) -> io::Result<u16> {
+    u16::from_str_radix(mode, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid mode"))
+}
+
+/// Splits `path` into its parent directory and its last component.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("/", path),
+    }
+}
+
+/// Resolves `path` like [`ext2::FileSystem::lookup`], except that a symlink in the last
+/// component is not followed.
+fn lookup_link(fs: &FileSystem, path: &str) -> Result<u32, Error<io::Error>> {
+    let (parent, name) = split_path(path);
+    if name.is_empty() || name == "." || name == ".." {
+        return fs.lookup(path);
+    }
+
+    let entry = fs.dir(fs.lookup(parent)?)?.find(name.as_bytes())?;
+    entry.map(|e| e.ino()).ok_or(Error::NotFound)
+}
+
+fn info(fs: &FileSystem) -> io::Result<()> {
+    let uuid = fs.uuid();
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+    println!(
+        "Volume name:      {}",
+        String::from_utf8_lossy(&fs.volume_name())
+    );
+    println!(
+        "UUID:             {}-{}-{}-{}-{}",
+        hex(&uuid[0..4]),
+        hex(&uuid[4..6]),
+        hex(&uuid[6..8]),
+        hex(&uuid[8..10]),
+        hex(&uuid[10..16])
+    );
+    println!("Revision:         {}", fs.revision());
+    println!(
+        "Features:         compat {:#x}, incompat {:#x}, ro_compat {:#x}",
+        fs.feature_compat(),
+        fs.feature_incompat(),
+        fs.feature_ro_compat()
+    );
+    println!("Block size:       {}", fs.block_size());
+    println!(
+        "Blocks:           {} ({} free, {} reserved)",
+        fs.blocks_count(),
+        fs.free_blocks_count(),
+        fs.reserved_blocks_count()
+    );
+    println!(
+        "Inodes:           {} ({} free)",
+        fs.inodes_count(),
+        fs.free_inodes_count()
+    );
+    println!("Inode size:       {}", fs.inode_size());
+    println!("Groups:           {}", fs.group_count());
+    println!("Blocks per group: {}", fs.blocks_per_group());
+    println!("Inodes per group: {}", fs.inodes_per_group());
+    Ok(())
+}
+
+/// Returns the type and permissions of `inode` in the format of `ls -l`.
+fn mode_string(inode: &Inode) -> String {
+    let kind = match inode.file_type() {
+        FileType::Fifo => 'p',
+        FileType::CharDevice => 'c',
+        FileType::Directory => 'd',
+        FileType::BlockDevice => 'b',
+        FileType::RegularFile => '-',
+        FileType::Symlink => 'l',
+        FileType::Socket => 's',
+        FileType::Unknown => '?',
+    };
+
+    let perm = inode.permissions();
+    let mut s = String::from(kind);
+    for shift in [6, 3, 0] {
+        let bits = (perm >> shift) & 7;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(if bits & 1 != 0 { 'x' } else { '-' });
+    }
+    s
+}
+
+fn ls(fs: &FileSystem, path: &str) -> io::Result<()> {
+    let dir = fs.dir(fs.lookup(path)?)?;
+    let mut out = io::stdout().lock();
+
+    for entry in dir.iter() {
+        let entry = entry?;
+        let inode = fs.inode(entry.ino())?;
+        let name = String::from_utf8_lossy(entry.name_bytes());
+
+        write!(
+            out,
+            "{:>7} {} {:>3} {:>5} {:>5} {:>10} {}",
+            entry.ino(),
+            mode_string(&inode),
+            inode.links_count(),
+            inode.uid(),
+            inode.gid(),
+            inode.size(),
+            name
+        )?;
+        if inode.file_type() == FileType::Symlink {
+            let target = fs.read_link(entry.ino())?;
+            write!(out, " -> {}", String::from_utf8_lossy(&target))?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn cat(fs: &FileSystem, path: &str) -> io::Result<()> {
+    let mut file = fs.open(path)?;
+    let mut out = io::stdout().lock();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        out.write_all(&buf[..n])?;
+    }
+}
+
+fn put(fs: &FileSystem, source: &str, path: &str, mode: Option<u16>) -> io::Result<()> {
+    let data = fs::read(source)?;
+    let mode = match mode {
+        Some(mode) => mode,
+        None => (fs::metadata(source)?.permissions().mode() & 0o7777) as u16,
+    };
+
+    let mut file = match fs.open(path) {
+        Ok(mut file) if file.inode().file_type() == FileType::RegularFile => {
+            file.set_len(0)?;
+            file
+        }
+        Ok(_) => return Err(Error::<io::Error>::IsADirectory.into()),
+        Err(Error::NotFound) => fs.create(path, mode)?,
+        Err(e) => return Err(e.into()),
+    };
+    file.write_all(&data)?;
+
+    Ok(())
+}
+
+fn mkdir(fs: &FileSystem, path: &str, mode: u16, parents: bool) -> io::Result<()> {
+    if !parents {
+        fs.mkdir(path, mode)?;
+        return Ok(());
+    }
+
+    let mut prefix = String::new();
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        prefix.push('/');
+        prefix.push_str(component);
+        match fs.mkdir(&prefix, mode) {
+            Ok(_) | Err(Error::AlreadyExists) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+fn rm(fs: &FileSystem, path: &str) -> io::Result<()> {
+    let inode = fs.inode(lookup_link(fs, path)?)?;
+    if inode.file_type() == FileType::Directory {
+        fs.rmdir(path)?;
+    } else {
+        fs.unlink(path)?;
+    }
+    Ok(())
+}
+
+fn stat(fs: &FileSystem, path: &str) -> io::Result<()> {
+    let ino = lookup_link(fs, path)?;
+    let inode = fs.inode(ino)?;
+
+    println!("Inode:  {ino}");
+    println!(
+        "Mode:   {} ({:04o})",
+        mode_string(&inode),
+        inode.permissions()
+    );
+    println!("Links:  {}", inode.links_count());
+    println!("Uid:    {}", inode.uid());
+    println!("Gid:    {}", inode.gid());
+    println!("Size:   {}", inode.size());
+    println!("Blocks: {} sectors", inode.sectors());
+    println!("Flags:  {:#x}", inode.flags());
+    println!("Atime:  {}", inode.atime());
+    println!("Ctime:  {}", inode.ctime());
+    println!("Mtime:  {}", inode.mtime());
+    if inode.file_type() == FileType::Symlink {
+        println!("Target: {}", String::from_utf8_lossy(&fs.read_link(ino)?));
+    }
+    for attr in fs.xattrs(ino)? {
+        println!(
+            "Xattr:  {} ({} bytes)",
+            String::from_utf8_lossy(attr.name_bytes()),
+            attr.value().len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks the file system, returning the exit codes of `e2fsck`: 0 if it's clean, 1 if all the
+/// problems were fixed, and 4 if some remain.
+fn fsck(fs: &FileSystem, repair: bool) -> io::Result<ExitCode> {
+    let report = fs.check(repair)?;
+    for problem in &report.problems {
+        let fixed = if report.repaired && problem.is_repairable() {
+            " (fixed)"
+        } else {
+            ""
+        };
+        println!("{problem:?}{fixed}");
+    }
+    fs.flush()?;
+
+    Ok(if report.is_clean() {
+        ExitCode::SUCCESS
+    } else if report.remaining().next().is_none() {
+        ExitCode::from(1)
+    } else {
+        ExitCode::from(4)
+    })
+}
@@ -0,0 +1,34 @@
+//! Prints a flattened device tree blob as device tree source, without needing `dtc`.
+
+use std::{env, fs, process::ExitCode};
+
+use fdt::Fdt;
+
+const USAGE: &str = "usage: fdtdump <dtb>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [path] = &args[..] else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("fdtdump: {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match Fdt::from_bytes(&data).and_then(|fdt| fdt.to_dts()) {
+        Ok(dts) => {
+            print!("{dts}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("fdtdump: {path}: invalid device tree: {e:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
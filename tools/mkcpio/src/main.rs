@@ -0,0 +1,99 @@
+//! Packs a directory into a `newc` cpio archive, without needing `cpio`.
+//!
+//! Entries are named relative to the directory, which is itself stored as `.`, and children are
+//! stored after their parent in name order, so that archives are reproducible.
+
+use std::{
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::Path,
+    process::ExitCode,
+};
+
+use cpio::{DeviceKind, Metadata, NewcWriter, StdWriter};
+
+const USAGE: &str = "usage: mkcpio <dir> <archive>";
+
+type Writer = NewcWriter<StdWriter<BufWriter<File>>>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [dir, archive] = &args[..] else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    match pack(Path::new(dir), Path::new(archive)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mkcpio: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn pack(dir: &Path, archive: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = NewcWriter::new(StdWriter::new(BufWriter::new(File::create(archive)?)));
+    append(&mut writer, dir, ".")?;
+    writer.finish()?.into_inner().flush()?;
+    Ok(())
+}
+
+/// Appends `path` to the archive as `name`, followed by its children if it is a directory.
+fn append(writer: &mut Writer, path: &Path, name: &str) -> Result<(), Box<dyn Error>> {
+    let metadata = fs::symlink_metadata(path)?;
+    let meta = Metadata {
+        mode: metadata.mode() & 0o7777,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mtime: metadata.mtime().clamp(0, u32::MAX.into()) as u32,
+    };
+
+    let ty = metadata.file_type();
+    if ty.is_dir() {
+        writer.append_dir(name, meta)?;
+
+        let mut entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let file_name = entry.file_name();
+            let file_name = file_name
+                .to_str()
+                .ok_or_else(|| format!("{}: name is not valid UTF-8", entry.path().display()))?;
+            let child = match name {
+                "." => file_name.to_owned(),
+                _ => format!("{name}/{file_name}"),
+            };
+            append(writer, &entry.path(), &child)?;
+        }
+    } else if ty.is_file() {
+        writer.append_file(name, meta, &fs::read(path)?)?;
+    } else if ty.is_symlink() {
+        let target = fs::read_link(path)?;
+        let target = target
+            .to_str()
+            .ok_or_else(|| format!("{}: target is not valid UTF-8", path.display()))?;
+        writer.append_symlink(name, meta, target)?;
+    } else if ty.is_char_device() || ty.is_block_device() {
+        let kind = match ty.is_char_device() {
+            true => DeviceKind::Char,
+            false => DeviceKind::Block,
+        };
+        let (major, minor) = split_dev(metadata.rdev());
+        writer.append_device(name, meta, kind, major, minor)?;
+    } else {
+        eprintln!("mkcpio: {}: skipping special file", path.display());
+    }
+
+    Ok(())
+}
+
+/// Splits a Linux device number into its major and minor numbers.
+fn split_dev(dev: u64) -> (u32, u32) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    (major as u32, minor as u32)
+}